@@ -0,0 +1,441 @@
+use crate::evdev::{Contact, Position, TouchState};
+use ::sdl2::event::Event;
+use ::sdl2::keyboard::Keycode;
+use ::sdl2::mouse::MouseButton;
+use ::std::sync::mpsc::Sender;
+
+// SDL reports mouse events that it synthesizes from touch events with this id.
+const TOUCH_MOUSE_ID: u32 = ::std::u32::MAX;
+
+// Holding one of these keys adds a finger at the current mouse position.
+const FINGER_KEYS: [Keycode; 10] = [
+    Keycode::Num1,
+    Keycode::Num2,
+    Keycode::Num3,
+    Keycode::Num4,
+    Keycode::Num5,
+    Keycode::Num6,
+    Keycode::Num7,
+    Keycode::Num8,
+    Keycode::Num9,
+    Keycode::Num0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pointer {
+    Mouse,
+    Key(Keycode),
+    Finger(i64),
+}
+
+pub struct DevInput {
+    sender: Sender<TouchState>,
+    touch_width: i32,
+    touch_height: i32,
+    pointers: Vec<(Pointer, i32)>,
+    next_tracking_id: i32,
+    mouse_position: Position,
+}
+
+impl DevInput {
+    pub fn new(sender: Sender<TouchState>, touch_width: i32, touch_height: i32) -> DevInput {
+        DevInput {
+            sender,
+            touch_width,
+            touch_height,
+            pointers: vec![],
+            next_tracking_id: 0,
+            mouse_position: Position { x: 0, y: 0 },
+        }
+    }
+
+    pub fn handle(&mut self, event: &Event, window_size: (u32, u32)) {
+        for touch_state in self.touch_states(event, window_size) {
+            if self.sender.send(touch_state).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn touch_states(&mut self, event: &Event, window_size: (u32, u32)) -> Vec<TouchState> {
+        match *event {
+            Event::MouseButtonDown {
+                which,
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } if which != TOUCH_MOUSE_ID => {
+                self.mouse_position = self.window_to_touch(x, y, window_size);
+                self.press(
+                    Pointer::Mouse,
+                    self.mouse_position.clone(),
+                    Contact::default(),
+                )
+            }
+            Event::MouseMotion { which, x, y, .. } if which != TOUCH_MOUSE_ID => {
+                self.mouse_position = self.window_to_touch(x, y, window_size);
+                let mouse_position = self.mouse_position.clone();
+                self.pointers
+                    .iter()
+                    .filter(|(pointer, _)| !matches!(pointer, Pointer::Finger(_)))
+                    .map(|(_, tracking_id)| TouchState::Touch {
+                        tracking_id: *tracking_id,
+                        position: mouse_position.clone(),
+                        contact: Contact::default(),
+                    })
+                    .collect()
+            }
+            Event::MouseButtonUp {
+                which,
+                mouse_btn: MouseButton::Left,
+                ..
+            } if which != TOUCH_MOUSE_ID => self.release(Pointer::Mouse),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } if FINGER_KEYS.contains(&keycode) => self.press(
+                Pointer::Key(keycode),
+                self.mouse_position.clone(),
+                Contact::default(),
+            ),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } if FINGER_KEYS.contains(&keycode) => self.release(Pointer::Key(keycode)),
+            Event::FingerDown {
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            }
+            | Event::FingerMotion {
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            } => {
+                let position = Position {
+                    x: (x * self.touch_width as f32) as i32,
+                    y: (y * self.touch_height as f32) as i32,
+                };
+                let contact = Contact {
+                    pressure: Some(pressure),
+                    size: None,
+                };
+                self.press(Pointer::Finger(finger_id), position, contact)
+            }
+            Event::FingerUp { finger_id, .. } => self.release(Pointer::Finger(finger_id)),
+            _ => vec![],
+        }
+    }
+
+    fn window_to_touch(&self, x: i32, y: i32, window_size: (u32, u32)) -> Position {
+        Position {
+            x: (x as f32 * self.touch_width as f32 / window_size.0 as f32) as i32,
+            y: (y as f32 * self.touch_height as f32 / window_size.1 as f32) as i32,
+        }
+    }
+
+    fn press(&mut self, pointer: Pointer, position: Position, contact: Contact) -> Vec<TouchState> {
+        let tracking_id = match self.pointers.iter().find(|(p, _)| *p == pointer) {
+            Some((_, tracking_id)) => *tracking_id,
+            None => {
+                let tracking_id = self.next_tracking_id;
+                self.next_tracking_id += 1;
+                self.pointers.push((pointer, tracking_id));
+                tracking_id
+            }
+        };
+        vec![TouchState::Touch {
+            tracking_id,
+            position,
+            contact,
+        }]
+    }
+
+    fn release(&mut self, pointer: Pointer) -> Vec<TouchState> {
+        match self.pointers.iter().position(|(p, _)| *p == pointer) {
+            Some(index) => {
+                let (_, tracking_id) = self.pointers.remove(index);
+                vec![TouchState::NoTouch { tracking_id }]
+            }
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::sdl2::keyboard::Mod;
+    use ::sdl2::mouse::MouseState;
+    use ::std::sync::mpsc::channel;
+
+    const WINDOW_SIZE: (u32, u32) = (800, 600);
+
+    fn dev_input() -> DevInput {
+        DevInput::new(channel().0, 1600, 3000)
+    }
+
+    fn mouse_down(x: i32, y: i32) -> Event {
+        Event::MouseButtonDown {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x,
+            y,
+        }
+    }
+
+    fn mouse_up() -> Event {
+        Event::MouseButtonUp {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    fn mouse_motion(x: i32, y: i32) -> Event {
+        Event::MouseMotion {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            mousestate: MouseState::from_sdl_state(0),
+            x,
+            y,
+            xrel: 0,
+            yrel: 0,
+        }
+    }
+
+    fn key_down(keycode: Keycode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::empty(),
+            repeat: false,
+        }
+    }
+
+    fn key_up(keycode: Keycode) -> Event {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(keycode),
+            scancode: None,
+            keymod: Mod::empty(),
+            repeat: false,
+        }
+    }
+
+    fn touch(tracking_id: i32, x: i32, y: i32) -> TouchState {
+        TouchState::Touch {
+            tracking_id,
+            position: Position { x, y },
+            contact: Contact::default(),
+        }
+    }
+
+    fn handle(dev_input: &mut DevInput, events: Vec<Event>) -> Vec<TouchState> {
+        events
+            .iter()
+            .flat_map(|event| dev_input.touch_states(event, WINDOW_SIZE))
+            .collect()
+    }
+
+    mod mouse {
+        use super::*;
+
+        #[test]
+        fn translates_clicks_into_touch_coordinates() {
+            assert_eq!(
+                handle(&mut dev_input(), vec![mouse_down(400, 100), mouse_up()]),
+                vec![touch(0, 800, 500), TouchState::NoTouch { tracking_id: 0 }]
+            );
+        }
+
+        #[test]
+        fn tracks_motion_while_the_button_is_pressed() {
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![mouse_down(0, 0), mouse_motion(100, 100), mouse_up()]
+                ),
+                vec![
+                    touch(0, 0, 0),
+                    touch(0, 200, 500),
+                    TouchState::NoTouch { tracking_id: 0 }
+                ]
+            );
+        }
+
+        #[test]
+        fn ignores_motion_without_pressed_button() {
+            assert_eq!(
+                handle(&mut dev_input(), vec![mouse_motion(100, 100)]),
+                vec![]
+            );
+        }
+
+        #[test]
+        fn uses_new_tracking_ids_for_every_click() {
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![mouse_down(0, 0), mouse_up(), mouse_down(0, 0)]
+                ),
+                vec![
+                    touch(0, 0, 0),
+                    TouchState::NoTouch { tracking_id: 0 },
+                    touch(1, 0, 0)
+                ]
+            );
+        }
+
+        #[test]
+        fn ignores_mouse_events_emulated_from_touches() {
+            let event = Event::MouseButtonDown {
+                timestamp: 0,
+                window_id: 0,
+                which: TOUCH_MOUSE_ID,
+                mouse_btn: MouseButton::Left,
+                clicks: 1,
+                x: 0,
+                y: 0,
+            };
+            assert_eq!(handle(&mut dev_input(), vec![event]), vec![]);
+        }
+    }
+
+    mod keyboard {
+        use super::*;
+
+        #[test]
+        fn adds_fingers_at_the_mouse_position() {
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![
+                        mouse_motion(100, 100),
+                        key_down(Keycode::Num1),
+                        key_down(Keycode::Num2),
+                        key_up(Keycode::Num1),
+                    ]
+                ),
+                vec![
+                    touch(0, 200, 500),
+                    touch(1, 200, 500),
+                    TouchState::NoTouch { tracking_id: 0 }
+                ]
+            );
+        }
+
+        #[test]
+        fn moves_held_keys_with_the_mouse() {
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![key_down(Keycode::Num1), mouse_motion(100, 100)]
+                ),
+                vec![touch(0, 0, 0), touch(0, 200, 500)]
+            );
+        }
+
+        #[test]
+        fn ignores_other_keys() {
+            assert_eq!(handle(&mut dev_input(), vec![key_down(Keycode::A)]), vec![]);
+        }
+    }
+
+    mod fingers {
+        use super::*;
+
+        fn finger_event(finger_id: i64, x: f32, y: f32) -> Event {
+            finger_event_with_pressure(finger_id, x, y, 1.0)
+        }
+
+        fn finger_event_with_pressure(finger_id: i64, x: f32, y: f32, pressure: f32) -> Event {
+            Event::FingerDown {
+                timestamp: 0,
+                touch_id: 0,
+                finger_id,
+                x,
+                y,
+                dx: 0.0,
+                dy: 0.0,
+                pressure,
+            }
+        }
+
+        fn finger_touch(tracking_id: i32, x: i32, y: i32, pressure: f32) -> TouchState {
+            TouchState::Touch {
+                tracking_id,
+                position: Position { x, y },
+                contact: Contact {
+                    pressure: Some(pressure),
+                    size: None,
+                },
+            }
+        }
+
+        #[test]
+        fn translates_normalized_coordinates() {
+            assert_eq!(
+                handle(&mut dev_input(), vec![finger_event(7, 0.5, 0.25)]),
+                vec![finger_touch(0, 800, 750, 1.0)]
+            );
+        }
+
+        #[test]
+        fn relays_the_pressure() {
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![finger_event_with_pressure(7, 0.5, 0.25, 0.3)]
+                ),
+                vec![finger_touch(0, 800, 750, 0.3)]
+            );
+        }
+
+        #[test]
+        fn tracks_multiple_fingers() {
+            let finger_up = Event::FingerUp {
+                timestamp: 0,
+                touch_id: 0,
+                finger_id: 7,
+                x: 0.0,
+                y: 0.0,
+                dx: 0.0,
+                dy: 0.0,
+                pressure: 0.0,
+            };
+            assert_eq!(
+                handle(
+                    &mut dev_input(),
+                    vec![
+                        finger_event(7, 0.0, 0.0),
+                        finger_event(8, 0.5, 0.5),
+                        finger_up,
+                    ]
+                ),
+                vec![
+                    finger_touch(0, 0, 0, 1.0),
+                    finger_touch(1, 800, 1500, 1.0),
+                    TouchState::NoTouch { tracking_id: 0 }
+                ]
+            );
+        }
+    }
+}
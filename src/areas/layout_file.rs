@@ -0,0 +1,763 @@
+use crate::areas::layouts::{hexagons, max_number_of_hexagons, HexagonsConfig};
+use crate::areas::shape::Shape;
+use crate::areas::{Area, Areas, AreasConfig, Orientation};
+use crate::evdev::Position;
+use crate::AddMessage;
+use crate::ErrorString;
+use ::std::convert::TryFrom;
+use ::std::fs;
+use ::toml::value::Table;
+use ::toml::Value;
+
+const CONFIG_KEYS: [&str; 8] = [
+    "name",
+    "orientation",
+    "u",
+    "v",
+    "column_range",
+    "row_range",
+    "start_midi_note",
+    "row_interval",
+];
+
+const HEXAGONS_KEYS: [&str; 5] = [
+    "name",
+    "radius",
+    "start_midi_note",
+    "right_interval",
+    "up_right_interval",
+];
+
+const PARALLELOGRAM_KEYS: [&str; 5] = ["shape", "base", "u", "v", "note"];
+
+const ROUND_SHAPE_KEYS: [&str; 4] = ["shape", "center", "radius", "note"];
+
+const POLYGON_KEYS: [&str; 3] = ["shape", "corners", "note"];
+
+// Small radii on big touch devices would create millions of hexagons.
+const MAX_HEXAGONS: i64 = 10_000;
+
+// A layout is either generated from the same parameters as the built-in
+// layouts (parallelograms or hexagons), or given as an explicit list of
+// areas.
+#[derive(Debug, Clone, PartialEq)]
+enum LayoutDefinition {
+    Config {
+        orientation: Orientation,
+        u: Position,
+        v: Position,
+        column_range: (i32, i32),
+        row_range: (i32, i32),
+        start_midi_note: i32,
+        row_interval: i32,
+    },
+    Hexagons {
+        radius: i32,
+        start_midi_note: i32,
+        right_interval: i32,
+        up_right_interval: i32,
+    },
+    Areas(Vec<Area>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Layout {
+    name: String,
+    // where the layout is in the file, used for error messages
+    description: String,
+    definition: LayoutDefinition,
+}
+
+impl Layout {
+    fn to_areas(&self, touch_width: i32, touch_height: i32) -> Result<Areas, ErrorString> {
+        Ok(match &self.definition {
+            LayoutDefinition::Config {
+                orientation,
+                u,
+                v,
+                column_range,
+                row_range,
+                start_midi_note,
+                row_interval,
+            } => Areas::new(AreasConfig {
+                touch_width,
+                touch_height,
+                orientation: orientation.clone(),
+                u: u.clone(),
+                v: v.clone(),
+                column_range: *column_range,
+                row_range: *row_range,
+                start_midi_note: *start_midi_note,
+                row_interval: *row_interval,
+            }),
+            LayoutDefinition::Hexagons {
+                radius,
+                start_midi_note,
+                right_interval,
+                up_right_interval,
+            } => {
+                let number = max_number_of_hexagons(touch_width, touch_height, *radius);
+                if number > MAX_HEXAGONS {
+                    return Err(ErrorString(format!(
+                        "{}: radius: {} is too small for a touch device of {}x{}, \
+                         it would create up to {} hexagons (at most {} are allowed)",
+                        self.description, radius, touch_width, touch_height, number, MAX_HEXAGONS
+                    )));
+                }
+                hexagons(HexagonsConfig {
+                    touch_width,
+                    touch_height,
+                    radius: *radius,
+                    start_note: *start_midi_note,
+                    right_interval: *right_interval,
+                    up_right_interval: *up_right_interval,
+                })
+            }
+            LayoutDefinition::Areas(areas) => {
+                Areas::from_areas(areas.clone(), touch_width, touch_height)
+            }
+        })
+    }
+}
+
+// A table from the layout file, together with a description of where it is,
+// used for error messages.
+struct Entry<'a> {
+    description: String,
+    table: &'a Table,
+}
+
+impl<'a> Entry<'a> {
+    fn error<T>(&self, message: String) -> Result<T, ErrorString> {
+        Err(ErrorString(format!("{}: {}", self.description, message)))
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), ErrorString> {
+        for key in self.table.keys() {
+            if !allowed.contains(&key.as_str()) {
+                return self.error(format!(
+                    "unknown key: {}, possible keys: {}",
+                    key,
+                    allowed.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<&'a Value, ErrorString> {
+        match self.table.get(key) {
+            Some(value) => Ok(value),
+            None => self.error(format!("missing key: {}", key)),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<&'a str, ErrorString> {
+        match self.get(key)?.as_str() {
+            Some(string) => Ok(string),
+            None => self.error(format!("{}: expected a string", key)),
+        }
+    }
+
+    fn integer(&self, key: &str) -> Result<i32, ErrorString> {
+        match self
+            .get(key)?
+            .as_integer()
+            .and_then(|integer| i32::try_from(integer).ok())
+        {
+            Some(integer) => Ok(integer),
+            None => self.error(format!("{}: expected an integer", key)),
+        }
+    }
+
+    fn pair(&self, key: &str) -> Result<(i32, i32), ErrorString> {
+        match integer_pair(self.get(key)?) {
+            Some(pair) => Ok(pair),
+            None => self.error(format!("{}: expected two integers, e.g. [0, 10]", key)),
+        }
+    }
+
+    fn positions(&self, key: &str) -> Result<Vec<Position>, ErrorString> {
+        let positions: Option<Vec<Position>> = self.get(key)?.as_array().and_then(|array| {
+            array
+                .iter()
+                .map(|value| integer_pair(value).map(|(x, y)| Position { x, y }))
+                .collect()
+        });
+        match positions {
+            Some(positions) => Ok(positions),
+            None => self.error(format!(
+                "{}: expected a list of positions, e.g. [[0, 0], [10, 0], [0, 10]]",
+                key
+            )),
+        }
+    }
+
+    fn position(&self, key: &str) -> Result<Position, ErrorString> {
+        let (x, y) = self.pair(key)?;
+        Ok(Position { x, y })
+    }
+
+    fn positive_integer(&self, key: &str) -> Result<i32, ErrorString> {
+        match self.integer(key)? {
+            integer if integer > 0 => Ok(integer),
+            _ => self.error(format!("{}: expected a positive integer", key)),
+        }
+    }
+
+    fn range(&self, key: &str) -> Result<(i32, i32), ErrorString> {
+        let (start, end) = self.pair(key)?;
+        if start >= end {
+            return self.error(format!("{}: the start has to be smaller than the end", key));
+        }
+        Ok((start, end))
+    }
+
+    // The vectors spanning a parallelogram must not be parallel.
+    fn vectors(&self) -> Result<(Position, Position), ErrorString> {
+        let u = self.position("u")?;
+        let v = self.position("v")?;
+        if i64::from(u.x) * i64::from(v.y) == i64::from(v.x) * i64::from(u.y) {
+            return self.error("u and v must not be parallel".to_string());
+        }
+        Ok((u, v))
+    }
+
+    fn orientation(&self) -> Result<Orientation, ErrorString> {
+        match self.string("orientation")? {
+            "Portrait" => Ok(Orientation::Portrait),
+            "Landscape" => Ok(Orientation::Landscape),
+            orientation => self.error(format!(
+                "unknown orientation: {}, possible values: Portrait, Landscape",
+                orientation
+            )),
+        }
+    }
+}
+
+fn integer_pair(value: &Value) -> Option<(i32, i32)> {
+    let integers: Option<Vec<i32>> = value.as_array().and_then(|array| {
+        array
+            .iter()
+            .map(|value| {
+                value
+                    .as_integer()
+                    .and_then(|integer| i32::try_from(integer).ok())
+            })
+            .collect()
+    });
+    match integers.as_deref() {
+        Some([a, b]) => Some((*a, *b)),
+        _ => None,
+    }
+}
+
+fn parse_shape(entry: &Entry) -> Result<Shape, ErrorString> {
+    let shape = match entry.table.get("shape") {
+        None => "parallelogram",
+        Some(_) => entry.string("shape")?,
+    };
+    match shape {
+        "parallelogram" => {
+            entry.check_keys(&PARALLELOGRAM_KEYS)?;
+            let (u, v) = entry.vectors()?;
+            Ok(Shape::Parallelogram {
+                base: entry.position("base")?,
+                u,
+                v,
+            })
+        }
+        "hexagon" | "circle" => {
+            entry.check_keys(&ROUND_SHAPE_KEYS)?;
+            let center = entry.position("center")?;
+            let radius = entry.positive_integer("radius")?;
+            Ok(if shape == "hexagon" {
+                Shape::Hexagon { center, radius }
+            } else {
+                Shape::Circle { center, radius }
+            })
+        }
+        "polygon" => {
+            entry.check_keys(&POLYGON_KEYS)?;
+            let corners = entry.positions("corners")?;
+            if corners.len() < 3 {
+                return entry.error("corners: a polygon needs at least three corners".to_string());
+            }
+            Ok(Shape::Polygon { corners })
+        }
+        shape => entry.error(format!(
+            "unknown shape: {}, possible values: parallelogram, hexagon, circle, polygon",
+            shape
+        )),
+    }
+}
+
+fn parse_area(entry: Entry) -> Result<Area, ErrorString> {
+    Ok(Area::new(parse_shape(&entry)?, entry.integer("note")?))
+}
+
+fn parse_layout(entry: Entry) -> Result<Layout, ErrorString> {
+    let name = entry.string("name")?.to_string();
+    let entry = Entry {
+        description: format!("{} ({})", entry.description, name),
+        table: entry.table,
+    };
+    let definition = match entry.table.get("areas") {
+        Some(areas) => {
+            entry.check_keys(&["name", "areas"])?;
+            let tables = match areas.as_array() {
+                Some(tables) if !tables.is_empty() => tables,
+                _ => return entry.error("areas: expected a non-empty list of areas".to_string()),
+            };
+            let mut areas = vec![];
+            for (index, table) in tables.iter().enumerate() {
+                let description = format!("{}, area {}", entry.description, index + 1);
+                match table.as_table() {
+                    Some(table) => areas.push(parse_area(Entry { description, table })?),
+                    None => return Err(ErrorString(format!("{}: expected a table", description))),
+                }
+            }
+            LayoutDefinition::Areas(areas)
+        }
+        None if entry.table.contains_key("radius") => {
+            entry.check_keys(&HEXAGONS_KEYS)?;
+            LayoutDefinition::Hexagons {
+                radius: entry.positive_integer("radius")?,
+                start_midi_note: entry.integer("start_midi_note")?,
+                right_interval: entry.integer("right_interval")?,
+                up_right_interval: entry.integer("up_right_interval")?,
+            }
+        }
+        None => {
+            entry.check_keys(&CONFIG_KEYS)?;
+            let (u, v) = entry.vectors()?;
+            LayoutDefinition::Config {
+                orientation: entry.orientation()?,
+                u,
+                v,
+                column_range: entry.range("column_range")?,
+                row_range: entry.range("row_range")?,
+                start_midi_note: entry.integer("start_midi_note")?,
+                row_interval: entry.integer("row_interval")?,
+            }
+        }
+    };
+    Ok(Layout {
+        name,
+        description: entry.description,
+        definition,
+    })
+}
+
+fn parse(contents: &str) -> Result<Vec<Layout>, ErrorString> {
+    let value: Value = contents.parse()?;
+    let tables = match value.get("layout").and_then(Value::as_array) {
+        Some(tables) if !tables.is_empty() => tables,
+        _ => return Err("no layouts found, expected [[layout]] tables".into()),
+    };
+    let mut layouts: Vec<Layout> = vec![];
+    for (index, table) in tables.iter().enumerate() {
+        let description = format!("layout {}", index + 1);
+        let layout = match table.as_table() {
+            Some(table) => parse_layout(Entry { description, table })?,
+            None => return Err(ErrorString(format!("{}: expected a table", description))),
+        };
+        if layouts.iter().any(|other| other.name == layout.name) {
+            return Err(ErrorString(format!(
+                "layout {}: duplicate layout name: {}",
+                index + 1,
+                layout.name
+            )));
+        }
+        layouts.push(layout);
+    }
+    Ok(layouts)
+}
+
+fn find_layout(layouts: Vec<Layout>, name: Option<&str>) -> Result<Layout, ErrorString> {
+    let names: Vec<String> = layouts.iter().map(|layout| layout.name.clone()).collect();
+    match name {
+        None => layouts
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorString::from("no layouts found")),
+        Some(name) => layouts
+            .into_iter()
+            .find(|layout| layout.name == name)
+            .ok_or_else(|| {
+                ErrorString(format!(
+                    "layout not found: {}, available layouts: {}",
+                    name,
+                    names.join(", ")
+                ))
+            }),
+    }
+}
+
+// Loads the layout with the given name, or the first one in the file.
+pub fn load(
+    file: &str,
+    name: Option<&str>,
+    touch_width: i32,
+    touch_height: i32,
+) -> Result<Areas, ErrorString> {
+    let contents = fs::read_to_string(file).add_message(format!("can't read {}", file))?;
+    let layouts = parse(&contents).add_message(file.to_string())?;
+    find_layout(layouts, name)?
+        .to_areas(touch_width, touch_height)
+        .add_message(file.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sound::tuning::Tuning;
+
+    const LAYOUTS: &str = r#"
+        [[layout]]
+        name = "fourths"
+        orientation = "Landscape"
+        u = [100, 0]
+        v = [0, -100]
+        column_range = [0, 8]
+        row_range = [0, 6]
+        start_midi_note = 36
+        row_interval = 5
+
+        [[layout]]
+        name = "pads"
+        [[layout.areas]]
+        base = [0, 0]
+        u = [400, 0]
+        v = [0, 300]
+        note = 36
+        [[layout.areas]]
+        base = [400, 0]
+        u = [400, 0]
+        v = [0, 300]
+        note = 38
+    "#;
+
+    fn parse_error(contents: &str) -> String {
+        match parse(contents) {
+            Ok(layouts) => panic!("expected an error, got: {:?}", layouts),
+            Err(ErrorString(message)) => message,
+        }
+    }
+
+    #[test]
+    fn parses_layouts_from_parameters() {
+        let layouts = parse(LAYOUTS).unwrap();
+        assert_eq!(
+            layouts[0],
+            Layout {
+                name: "fourths".to_string(),
+                description: "layout 1 (fourths)".to_string(),
+                definition: LayoutDefinition::Config {
+                    orientation: Orientation::Landscape,
+                    u: Position { x: 100, y: 0 },
+                    v: Position { x: 0, y: -100 },
+                    column_range: (0, 8),
+                    row_range: (0, 6),
+                    start_midi_note: 36,
+                    row_interval: 5,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn parses_layouts_from_explicit_areas() {
+        let layouts = parse(LAYOUTS).unwrap();
+        assert_eq!(
+            layouts[1],
+            Layout {
+                name: "pads".to_string(),
+                description: "layout 2 (pads)".to_string(),
+                definition: LayoutDefinition::Areas(vec![
+                    Area::new(
+                        Shape::Parallelogram {
+                            base: Position { x: 0, y: 0 },
+                            u: Position { x: 400, y: 0 },
+                            v: Position { x: 0, y: 300 },
+                        },
+                        36
+                    ),
+                    Area::new(
+                        Shape::Parallelogram {
+                            base: Position { x: 400, y: 0 },
+                            u: Position { x: 400, y: 0 },
+                            v: Position { x: 0, y: 300 },
+                        },
+                        38
+                    ),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_other_shapes() {
+        let layouts = parse(
+            r#"
+            [[layout]]
+            name = "shapes"
+            [[layout.areas]]
+            shape = "hexagon"
+            center = [100, 100]
+            radius = 50
+            note = 60
+            [[layout.areas]]
+            shape = "circle"
+            center = [300, 100]
+            radius = 40
+            note = 62
+            [[layout.areas]]
+            shape = "polygon"
+            corners = [[0, 200], [100, 200], [50, 300]]
+            note = 64
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            layouts[0].definition,
+            LayoutDefinition::Areas(vec![
+                Area::new(
+                    Shape::Hexagon {
+                        center: Position { x: 100, y: 100 },
+                        radius: 50,
+                    },
+                    60
+                ),
+                Area::new(
+                    Shape::Circle {
+                        center: Position { x: 300, y: 100 },
+                        radius: 40,
+                    },
+                    62
+                ),
+                Area::new(
+                    Shape::Polygon {
+                        corners: vec![
+                            Position { x: 0, y: 200 },
+                            Position { x: 100, y: 200 },
+                            Position { x: 50, y: 300 },
+                        ],
+                    },
+                    64
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn generates_the_same_areas_as_the_built_in_layouts() {
+        let layouts = parse(LAYOUTS).unwrap();
+        assert_eq!(
+            layouts[0].to_areas(800, 600).unwrap().areas,
+            crate::areas::layouts::grid(800, 600, 8, 6, 36, &Tuning::default()).areas
+        );
+    }
+
+    #[test]
+    fn generates_hexagons() {
+        let layouts = parse(
+            r#"
+            [[layout]]
+            name = "wicki-hayden"
+            radius = 50
+            start_midi_note = 36
+            right_interval = 2
+            up_right_interval = 7
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            layouts[0].definition,
+            LayoutDefinition::Hexagons {
+                radius: 50,
+                start_midi_note: 36,
+                right_interval: 2,
+                up_right_interval: 7,
+            }
+        );
+        assert_eq!(
+            layouts[0].to_areas(800, 600).unwrap().areas,
+            hexagons(HexagonsConfig {
+                touch_width: 800,
+                touch_height: 600,
+                radius: 50,
+                start_note: 36,
+                right_interval: 2,
+                up_right_interval: 7,
+            })
+            .areas
+        );
+    }
+
+    #[test]
+    fn uses_the_first_layout_by_default() {
+        let layout = find_layout(parse(LAYOUTS).unwrap(), None).unwrap();
+        assert_eq!(layout.name, "fourths");
+    }
+
+    #[test]
+    fn finds_layouts_by_name() {
+        let layout = find_layout(parse(LAYOUTS).unwrap(), Some("pads")).unwrap();
+        assert_eq!(layout.name, "pads");
+    }
+
+    #[test]
+    fn lists_the_available_layouts_for_unknown_names() {
+        let ErrorString(message) = find_layout(parse(LAYOUTS).unwrap(), Some("foo")).unwrap_err();
+        assert_eq!(
+            message,
+            "layout not found: foo, available layouts: fourths, pads"
+        );
+    }
+
+    mod validation {
+        use super::*;
+
+        #[test]
+        fn rejects_files_without_layouts() {
+            assert_eq!(
+                parse_error("foo = 1"),
+                "no layouts found, expected [[layout]] tables"
+            );
+        }
+
+        #[test]
+        fn rejects_invalid_toml() {
+            parse_error("[[layout]");
+        }
+
+        #[test]
+        fn points_at_missing_keys() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("row_interval = 5", "")),
+                "layout 1 (fourths): missing key: row_interval"
+            );
+        }
+
+        #[test]
+        fn points_at_layouts_without_names() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("name = \"pads\"", "")),
+                "layout 2: missing key: name"
+            );
+        }
+
+        #[test]
+        fn points_at_unknown_keys() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("row_interval", "row_intervall")),
+                "layout 1 (fourths): unknown key: row_intervall, possible keys: \
+                 name, orientation, u, v, column_range, row_range, start_midi_note, row_interval"
+            );
+        }
+
+        #[test]
+        fn points_at_invalid_vectors() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("u = [100, 0]", "u = [100]")),
+                "layout 1 (fourths): u: expected two integers, e.g. [0, 10]"
+            );
+        }
+
+        #[test]
+        fn rejects_parallel_vectors() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("v = [0, -100]", "v = [-200, 0]")),
+                "layout 1 (fourths): u and v must not be parallel"
+            );
+        }
+
+        #[test]
+        fn rejects_empty_ranges() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("row_range = [0, 6]", "row_range = [6, 6]")),
+                "layout 1 (fourths): row_range: the start has to be smaller than the end"
+            );
+        }
+
+        #[test]
+        fn rejects_unknown_orientations() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("Landscape", "Diagonal")),
+                "layout 1 (fourths): unknown orientation: Diagonal, \
+                 possible values: Portrait, Landscape"
+            );
+        }
+
+        #[test]
+        fn points_at_the_offending_area() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("note = 38", "note = \"D\"")),
+                "layout 2 (pads), area 2: note: expected an integer"
+            );
+        }
+
+        #[test]
+        fn rejects_unknown_shapes() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("note = 38", "note = 38\nshape = \"star\"")),
+                "layout 2 (pads), area 2: unknown shape: star, \
+                 possible values: parallelogram, hexagon, circle, polygon"
+            );
+        }
+
+        #[test]
+        fn rejects_keys_of_other_shapes() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("note = 38", "note = 38\nradius = 3")),
+                "layout 2 (pads), area 2: unknown key: radius, possible keys: shape, base, u, v, note"
+            );
+        }
+
+        #[test]
+        fn rejects_polygons_with_less_than_three_corners() {
+            assert_eq!(
+                parse_error(
+                    "[[layout]]\nname = \"a\"\n[[layout.areas]]\n\
+                     shape = \"polygon\"\ncorners = [[0, 0], [1, 1]]\nnote = 60"
+                ),
+                "layout 1 (a), area 1: corners: a polygon needs at least three corners"
+            );
+        }
+
+        #[test]
+        fn rejects_non_positive_radii() {
+            assert_eq!(
+                parse_error(
+                    "[[layout]]\nname = \"a\"\n[[layout.areas]]\n\
+                     shape = \"circle\"\ncenter = [0, 0]\nradius = 0\nnote = 60"
+                ),
+                "layout 1 (a), area 1: radius: expected a positive integer"
+            );
+        }
+
+        #[test]
+        fn rejects_duplicate_names() {
+            assert_eq!(
+                parse_error(&LAYOUTS.replace("pads", "fourths")),
+                "layout 2: duplicate layout name: fourths"
+            );
+        }
+
+        #[test]
+        fn rejects_radii_that_would_create_too_many_hexagons() {
+            let layouts = parse(
+                "[[layout]]\nname = \"tiny\"\nradius = 1\n\
+                 start_midi_note = 36\nright_interval = 2\nup_right_interval = 7",
+            )
+            .unwrap();
+            let ErrorString(message) = layouts[0].to_areas(16383, 9570).unwrap_err();
+            assert_eq!(
+                message,
+                "layout 1 (tiny): radius: 1 is too small for a touch device of 16383x9570, \
+                 it would create up to 156846792 hexagons (at most 10000 are allowed)"
+            );
+        }
+    }
+}
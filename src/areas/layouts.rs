@@ -1,7 +1,13 @@
-use crate::areas::{Areas, AreasConfig, Orientation};
+use crate::areas::shape::Shape;
+use crate::areas::{Area, Areas, AreasConfig, Glide, KeyRow, Orientation};
 use crate::evdev::Position;
+use crate::sound::tuning::Tuning;
+use sdl2::pixels::Color;
 
-pub fn parallelograms(touch_width: i32, touch_height: i32) -> Areas {
+// The built-in layouts are given in (12edo) semitones and are translated into
+// the steps of the tuning.
+
+pub fn parallelograms(touch_width: i32, touch_height: i32, tuning: &Tuning) -> Areas {
     Areas::new(AreasConfig {
         touch_width,
         touch_height,
@@ -10,9 +16,10 @@ pub fn parallelograms(touch_width: i32, touch_height: i32) -> Areas {
         v: Position { x: -1000, y: -200 },
         column_range: (-3, 8),
         row_range: (0, 17),
-        start_midi_note: 24,
-        row_interval: 5,
+        start_midi_note: tuning.steps(24),
+        row_interval: tuning.steps(5),
     })
+    .with_tuning(tuning.clone())
 }
 
 pub fn grid(
@@ -21,6 +28,7 @@ pub fn grid(
     row_length: i32,
     number_of_rows: i32,
     start_midi_note: i32,
+    tuning: &Tuning,
 ) -> Areas {
     Areas::new(AreasConfig {
         touch_width,
@@ -36,13 +44,276 @@ pub fn grid(
         },
         column_range: (0, row_length),
         row_range: (0, number_of_rows),
-        start_midi_note,
-        row_interval: 5,
+        start_midi_note: tuning.steps(start_midi_note),
+        row_interval: tuning.steps(5),
     })
+    .with_tuning(tuning.clone())
+}
+
+pub fn grid2(touch_width: i32, touch_height: i32, tuning: &Tuning) -> Areas {
+    grid(touch_width, touch_height, 15, 10, 36, tuning)
 }
 
-pub fn grid2(touch_width: i32, touch_height: i32) -> Areas {
-    grid(touch_width, touch_height, 15, 10, 36)
+pub struct HexagonsConfig {
+    pub touch_width: i32,
+    pub touch_height: i32,
+    // distance from the center of a hexagon to its corners
+    pub radius: i32,
+    pub start_note: i32,
+    // interval between a hexagon and its right neighbour
+    pub right_interval: i32,
+    // interval between a hexagon and its upper right neighbour
+    pub up_right_interval: i32,
+}
+
+// Tiles the whole screen with hexagons (with a corner at the top), starting
+// with the base note in the lower left corner.
+pub fn hexagons(
+    HexagonsConfig {
+        touch_width,
+        touch_height,
+        radius,
+        start_note,
+        right_interval,
+        up_right_interval,
+    }: HexagonsConfig,
+) -> Areas {
+    let (column_width, row_height) = hexagon_spacing(radius);
+    let number_of_rows = touch_height / row_height + 2;
+    let mut areas = vec![];
+    for row in 0..number_of_rows {
+        let row_offset = row * column_width / 2;
+        let first_column = -((row_offset + column_width - 1) / column_width);
+        let last_column = (touch_width - row_offset) / column_width + 1;
+        for column in first_column..=last_column {
+            areas.push(Area::new(
+                Shape::Hexagon {
+                    center: Position {
+                        x: row_offset + column * column_width,
+                        y: touch_height - row * row_height,
+                    },
+                    radius,
+                },
+                start_note + column * right_interval + row * up_right_interval,
+            ));
+        }
+    }
+    Areas::from_areas(areas, touch_width, touch_height)
+}
+
+pub fn hexagon_spacing(radius: i32) -> (i32, i32) {
+    // Rounding down makes neighbouring hexagons overlap slightly instead of
+    // leaving gaps between them.
+    let column_width = (3.0_f32.sqrt() * radius as f32) as i32;
+    let row_height = 3 * radius / 2;
+    (column_width, row_height)
+}
+
+// An upper bound for the number of hexagons that hexagons() tiles the screen
+// with, so that small radii can be rejected before generating them.
+pub fn max_number_of_hexagons(touch_width: i32, touch_height: i32, radius: i32) -> i64 {
+    let (column_width, row_height) = hexagon_spacing(radius);
+    let columns = i64::from(touch_width / column_width + 3);
+    let rows = i64::from(touch_height / row_height + 2);
+    columns * rows
+}
+
+// An isomorphic keyboard layout: every interval has the same shape wherever
+// it's played. Adjacent hexagons differ by the given intervals, the interval
+// to the upper left neighbour is up_right_interval - right_interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IsomorphicLayout {
+    pub right_interval: i32,
+    pub up_right_interval: i32,
+    pub start_midi_note: i32,
+    // number of hexagons in a row
+    pub columns: i32,
+}
+
+// whole tones to the right, fifths to the upper right
+pub const WICKI_HAYDEN: IsomorphicLayout = IsomorphicLayout {
+    right_interval: 2,
+    up_right_interval: 7,
+    start_midi_note: 36,
+    columns: 10,
+};
+
+// fifths to the right, major thirds to the upper right and minor thirds to
+// the lower right
+pub const HARMONIC_TABLE: IsomorphicLayout = IsomorphicLayout {
+    right_interval: 7,
+    up_right_interval: 4,
+    start_midi_note: 24,
+    columns: 8,
+};
+
+// whole tones to the right, semitones to the upper right
+pub const JANKO: IsomorphicLayout = IsomorphicLayout {
+    right_interval: 2,
+    up_right_interval: 1,
+    start_midi_note: 36,
+    columns: 14,
+};
+
+// whole tones to the right, semitones to the upper left
+pub const BOSANQUET_WILSON: IsomorphicLayout = IsomorphicLayout {
+    right_interval: 2,
+    up_right_interval: 3,
+    start_midi_note: 36,
+    columns: 12,
+};
+
+// fifths to the right, whole tones to the upper right
+pub const FOKKER: IsomorphicLayout = IsomorphicLayout {
+    right_interval: 7,
+    up_right_interval: 2,
+    start_midi_note: 24,
+    columns: 8,
+};
+
+impl IsomorphicLayout {
+    pub fn areas(&self, touch_width: i32, touch_height: i32, tuning: &Tuning) -> Areas {
+        hexagons(HexagonsConfig {
+            touch_width,
+            touch_height,
+            radius: (touch_width as f32 / self.columns as f32 / 3.0_f32.sqrt()).round() as i32,
+            start_note: tuning.steps(self.start_midi_note),
+            right_interval: tuning.steps(self.right_interval),
+            up_right_interval: tuning.steps(self.up_right_interval),
+        })
+        .with_tuning(tuning.clone())
+    }
+}
+
+const WHITE_KEY_COLOR: Color = Color {
+    r: 240,
+    g: 235,
+    b: 220,
+    a: 0xff,
+};
+
+const BLACK_KEY_COLOR: Color = Color {
+    r: 40,
+    g: 40,
+    b: 40,
+    a: 0xff,
+};
+
+// One or more rows of piano keys, the lowest row at the bottom. Each row
+// continues where the row below ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Piano {
+    // moved down by a semitone if it's a black key
+    pub start_midi_note: i32,
+    pub octaves: i32,
+    pub rows: i32,
+}
+
+pub const PIANO: Piano = Piano {
+    start_midi_note: 48,
+    octaves: 2,
+    rows: 2,
+};
+
+fn is_black_key(note: i32) -> bool {
+    matches!(note.rem_euclid(12), 1 | 3 | 6 | 8 | 10)
+}
+
+fn piano_key(shape: Shape, semitone: i32, note: i32) -> Area {
+    Area {
+        shape,
+        color: Some(if is_black_key(semitone) {
+            BLACK_KEY_COLOR
+        } else {
+            WHITE_KEY_COLOR
+        }),
+        note,
+        outline: true,
+    }
+}
+
+// The keys are laid out in (12edo) semitones, in other tunings every key
+// plays the note that comes closest to its semitone.
+impl Piano {
+    pub fn areas(&self, touch_width: i32, touch_height: i32, tuning: &Tuning) -> Areas {
+        let start_note = if is_black_key(self.start_midi_note) {
+            self.start_midi_note - 1
+        } else {
+            self.start_midi_note
+        };
+        let notes_per_row = 12 * self.octaves;
+        let white_key_width = touch_width as f32 / (7 * self.octaves) as f32;
+        let black_key_width = (white_key_width * 0.6).round() as i32;
+        let row_height = touch_height / self.rows;
+        let black_key_height = row_height * 3 / 5;
+        // Black keys come first, so that they win when looking up the area
+        // for a position.
+        let mut black_keys = vec![];
+        let mut white_keys = vec![];
+        let mut key_rows = vec![];
+        for row in 0..self.rows {
+            let bottom = touch_height - row * row_height;
+            let top = bottom - row_height;
+            let row_start = start_note + row * notes_per_row;
+            let mut white_key_index = 0;
+            let mut centers = vec![];
+            for semitone in row_start..row_start + notes_per_row {
+                let white_key_border = |index: i32| (index as f32 * white_key_width).round() as i32;
+                let note = tuning.steps(semitone);
+                if is_black_key(semitone) {
+                    let center = white_key_border(white_key_index);
+                    centers.push((center, note));
+                    black_keys.push(piano_key(
+                        Shape::Parallelogram {
+                            base: Position {
+                                x: center - black_key_width / 2,
+                                y: top + black_key_height,
+                            },
+                            u: Position {
+                                x: black_key_width,
+                                y: 0,
+                            },
+                            v: Position {
+                                x: 0,
+                                y: -black_key_height,
+                            },
+                        },
+                        semitone,
+                        note,
+                    ));
+                } else {
+                    let left = white_key_border(white_key_index);
+                    let right = white_key_border(white_key_index + 1);
+                    centers.push(((left + right) / 2, note));
+                    white_keys.push(piano_key(
+                        Shape::Parallelogram {
+                            base: Position { x: left, y: bottom },
+                            u: Position {
+                                x: right - left,
+                                y: 0,
+                            },
+                            v: Position {
+                                x: 0,
+                                y: -row_height,
+                            },
+                        },
+                        semitone,
+                        note,
+                    ));
+                    white_key_index += 1;
+                }
+            }
+            key_rows.push(KeyRow {
+                top,
+                bottom,
+                centers,
+            });
+        }
+        black_keys.append(&mut white_keys);
+        Areas::from_areas(black_keys, touch_width, touch_height)
+            .with_tuning(tuning.clone())
+            .with_glide(Glide::Keys(key_rows))
+    }
 }
 
 #[cfg(test)]
@@ -56,7 +327,7 @@ mod test {
 
         #[test]
         fn has_the_base_note_in_the_lower_right_corner() {
-            let areas = grid(800, 600, 80, 60, 0).areas;
+            let areas = grid(800, 600, 80, 60, 0, &Tuning::default()).areas;
             assert_eq!(
                 areas[0].shape,
                 Shape::Parallelogram {
@@ -69,7 +340,7 @@ mod test {
 
         #[test]
         fn takes_the_screen_size_into_account() {
-            let areas = grid(8000, 1200, 80, 60, 0).areas;
+            let areas = grid(8000, 1200, 80, 60, 0, &Tuning::default()).areas;
             assert_eq!(
                 areas[0].shape,
                 Shape::Parallelogram {
@@ -82,7 +353,7 @@ mod test {
 
         #[test]
         fn renders_the_bottom_row() {
-            let areas = grid(800, 600, 80, 60, 0).areas;
+            let areas = grid(800, 600, 80, 60, 0, &Tuning::default()).areas;
             for (i, area) in areas.iter().take(80).enumerate() {
                 assert_eq!(
                     area.shape,
@@ -102,15 +373,15 @@ mod test {
 
         #[test]
         fn bottom_row_are_semitones() {
-            let areas = grid(800, 600, 80, 60, 0).areas;
+            let areas = grid(800, 600, 80, 60, 0, &Tuning::default()).areas;
             for (i, area) in areas.iter().take(80).enumerate() {
-                assert_eq!(area.midi_note, i as i32)
+                assert_eq!(area.note, i as i32)
             }
         }
 
         #[test]
         fn renders_a_second_row() {
-            let areas = grid(800, 600, 80, 60, 0).areas;
+            let areas = grid(800, 600, 80, 60, 0, &Tuning::default()).areas;
             for i in 0..80 {
                 assert_eq!(
                     areas[i as usize + 80],
@@ -128,7 +399,7 @@ mod test {
 
         #[test]
         fn renders_the_top_row() {
-            let areas = grid(800, 600, 80, 60, 0).areas;
+            let areas = grid(800, 600, 80, 60, 0, &Tuning::default()).areas;
             for i in 0..80 {
                 assert_eq!(
                     areas[i as usize + 80 * 59],
@@ -144,18 +415,327 @@ mod test {
             }
         }
 
+        #[test]
+        fn steps_through_the_notes_of_the_tuning() {
+            let areas = grid(800, 600, 80, 60, 36, &Tuning::edo(31)).areas;
+            assert_eq!(areas[0].note, 93);
+            assert_eq!(areas[1].note, 94);
+            assert_eq!(areas[80].note, 93 + 13);
+        }
+
         #[test]
         fn allows_to_configure_the_number_of_rectangles() {
-            assert_eq!(grid(800, 600, 80, 60, 0).areas.len(), 80 * 60);
-            assert_eq!(grid(800, 600, 10, 6, 0).areas.len(), 10 * 6);
+            assert_eq!(
+                grid(800, 600, 80, 60, 0, &Tuning::default()).areas.len(),
+                80 * 60
+            );
+            assert_eq!(
+                grid(800, 600, 10, 6, 0, &Tuning::default()).areas.len(),
+                10 * 6
+            );
         }
 
         #[test]
         fn allows_to_configure_the_base_note() {
-            let areas = grid(800, 600, 80, 60, 36).areas;
-            assert_eq!(areas[0].midi_note, 36);
-            assert_eq!(areas[1].midi_note, 37);
-            assert_eq!(areas[80].midi_note, 41);
+            let areas = grid(800, 600, 80, 60, 36, &Tuning::default()).areas;
+            assert_eq!(areas[0].note, 36);
+            assert_eq!(areas[1].note, 37);
+            assert_eq!(areas[80].note, 41);
+        }
+    }
+
+    mod hexagons {
+        use super::*;
+
+        fn config(right_interval: i32, up_right_interval: i32) -> HexagonsConfig {
+            HexagonsConfig {
+                touch_width: 800,
+                touch_height: 600,
+                radius: 100,
+                start_note: 36,
+                right_interval,
+                up_right_interval,
+            }
+        }
+
+        fn note_at(areas: &Areas, x: i32, y: i32) -> Option<i32> {
+            areas
+                .area_index(&Position { x, y })
+                .map(|index| areas.areas[index].note)
+        }
+
+        #[test]
+        fn has_the_base_note_in_the_lower_left_corner() {
+            let areas = hexagons(config(2, 7));
+            assert_eq!(
+                areas.areas[0],
+                Area::new(
+                    Shape::Hexagon {
+                        center: Position { x: 0, y: 600 },
+                        radius: 100,
+                    },
+                    36
+                )
+            );
+        }
+
+        #[test]
+        fn uses_the_interval_to_the_right_neighbour() {
+            let areas = hexagons(config(2, 7));
+            assert_eq!(note_at(&areas, 173, 600), Some(38));
+            assert_eq!(note_at(&areas, 346, 600), Some(40));
+        }
+
+        #[test]
+        fn uses_the_interval_to_the_upper_right_neighbour() {
+            let areas = hexagons(config(2, 7));
+            assert_eq!(note_at(&areas, 86, 450), Some(43));
+            assert_eq!(note_at(&areas, 173, 300), Some(50));
+        }
+
+        #[test]
+        fn allows_to_configure_the_intervals() {
+            let areas = hexagons(config(7, 4));
+            assert_eq!(note_at(&areas, 173, 600), Some(43));
+            assert_eq!(note_at(&areas, 86, 450), Some(40));
+            assert_eq!(note_at(&areas, -87, 450), Some(33));
+        }
+
+        #[test]
+        fn covers_the_whole_screen() {
+            let areas = hexagons(config(2, 7));
+            for x in (0..=800).step_by(10) {
+                for y in (0..=600).step_by(10) {
+                    assert!(note_at(&areas, x, y).is_some(), "({}, {})", x, y);
+                }
+            }
+        }
+
+        #[test]
+        fn estimates_an_upper_bound_for_the_number_of_hexagons() {
+            for radius in &[3, 7, 50, 100, 1000] {
+                let config = HexagonsConfig {
+                    radius: *radius,
+                    ..config(2, 7)
+                };
+                let bound = max_number_of_hexagons(800, 600, *radius);
+                let number = hexagons(config).areas.len() as i64;
+                assert!(number <= bound, "radius {}: {} > {}", radius, number, bound);
+                assert!(
+                    number * 2 > bound,
+                    "radius {}: {} vs {}",
+                    radius,
+                    number,
+                    bound
+                );
+            }
+        }
+    }
+
+    mod isomorphic_layouts {
+        use super::*;
+
+        // Returns the notes of the hexagon in the lower left corner, its
+        // right neighbour and its upper right neighbour.
+        fn neighbours(layout: IsomorphicLayout) -> (i32, i32, i32) {
+            let areas = layout.areas(800, 600, &Tuning::default()).areas;
+            let base = areas[0].note;
+            let right = areas[1].note;
+            let up_right = areas
+                .iter()
+                .find(|area| match &area.shape {
+                    Shape::Hexagon { center, .. } => center.x > 0 && center.y < 600,
+                    _ => false,
+                })
+                .unwrap()
+                .note;
+            (base, right, up_right)
+        }
+
+        #[test]
+        fn wicki_hayden_has_fifths_to_the_upper_right() {
+            assert_eq!(neighbours(WICKI_HAYDEN), (36, 38, 43));
+        }
+
+        #[test]
+        fn harmonic_table_has_fifths_to_the_right() {
+            assert_eq!(neighbours(HARMONIC_TABLE), (24, 31, 28));
+        }
+
+        #[test]
+        fn janko_has_semitones_to_the_upper_right() {
+            assert_eq!(neighbours(JANKO), (36, 38, 37));
+        }
+
+        #[test]
+        fn bosanquet_wilson_has_semitones_to_the_upper_left() {
+            assert_eq!(neighbours(BOSANQUET_WILSON), (36, 38, 39));
+        }
+
+        #[test]
+        fn fokker_has_whole_tones_to_the_upper_right() {
+            assert_eq!(neighbours(FOKKER), (24, 31, 26));
+        }
+
+        #[test]
+        fn translates_the_intervals_into_the_tuning() {
+            let areas = WICKI_HAYDEN.areas(800, 600, &Tuning::edo(31));
+            assert_eq!(areas.areas[0].note, 93);
+            assert_eq!(areas.areas[1].note, 98);
+            assert_eq!(areas.tuning, Tuning::edo(31));
+        }
+
+        #[test]
+        fn allows_to_configure_the_start_note() {
+            let layout = IsomorphicLayout {
+                start_midi_note: 48,
+                ..WICKI_HAYDEN
+            };
+            assert_eq!(neighbours(layout), (48, 50, 55));
+        }
+
+        #[test]
+        fn allows_to_configure_the_number_of_columns() {
+            let radius = |layout: IsomorphicLayout| match layout
+                .areas(800, 600, &Tuning::default())
+                .areas[0]
+                .shape
+            {
+                Shape::Hexagon { radius, .. } => radius,
+                _ => panic!("expected a hexagon"),
+            };
+            assert_eq!(
+                radius(IsomorphicLayout {
+                    columns: 4,
+                    ..JANKO
+                }),
+                115
+            );
+            assert_eq!(
+                radius(IsomorphicLayout {
+                    columns: 8,
+                    ..JANKO
+                }),
+                58
+            );
+        }
+    }
+
+    mod piano {
+        use super::*;
+
+        const ONE_OCTAVE: Piano = Piano {
+            start_midi_note: 60,
+            octaves: 1,
+            rows: 1,
+        };
+
+        fn note_at(areas: &Areas, x: i32, y: i32) -> Option<i32> {
+            areas
+                .area_index(&Position { x, y })
+                .map(|index| areas.areas[index].note)
+        }
+
+        #[test]
+        fn has_seven_white_and_five_black_keys_per_octave() {
+            let areas = ONE_OCTAVE.areas(700, 100, &Tuning::default()).areas;
+            let colors = |color| {
+                areas
+                    .iter()
+                    .filter(|area| area.color == Some(color))
+                    .count()
+            };
+            assert_eq!(colors(WHITE_KEY_COLOR), 7);
+            assert_eq!(colors(BLACK_KEY_COLOR), 5);
+        }
+
+        #[test]
+        fn lays_out_white_keys_from_left_to_right() {
+            let areas = ONE_OCTAVE.areas(700, 100, &Tuning::default());
+            let notes: Vec<Option<i32>> =
+                (0..7).map(|i| note_at(&areas, i * 100 + 50, 90)).collect();
+            assert_eq!(
+                notes,
+                vec![60, 62, 64, 65, 67, 69, 71]
+                    .into_iter()
+                    .map(Some)
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn black_keys_take_priority_over_white_keys() {
+            let areas = ONE_OCTAVE.areas(700, 100, &Tuning::default());
+            assert_eq!(note_at(&areas, 100, 30), Some(61));
+            assert_eq!(note_at(&areas, 110, 70), Some(62));
+            assert_eq!(note_at(&areas, 350, 30), Some(65));
+            assert_eq!(note_at(&areas, 400, 30), Some(66));
+        }
+
+        #[test]
+        fn black_keys_are_shorter_and_narrower() {
+            let areas = ONE_OCTAVE.areas(700, 100, &Tuning::default()).areas;
+            assert_eq!(
+                areas[0],
+                piano_key(
+                    Shape::Parallelogram {
+                        base: Position { x: 70, y: 60 },
+                        u: Position { x: 60, y: 0 },
+                        v: Position { x: 0, y: -60 },
+                    },
+                    61,
+                    61
+                )
+            );
+        }
+
+        #[test]
+        fn stacks_rows_from_the_bottom() {
+            let areas = Piano {
+                rows: 2,
+                ..ONE_OCTAVE
+            }
+            .areas(700, 200, &Tuning::default());
+            assert_eq!(note_at(&areas, 50, 190), Some(60));
+            assert_eq!(note_at(&areas, 50, 90), Some(72));
+            assert_eq!(note_at(&areas, 100, 30), Some(73));
+        }
+
+        #[test]
+        fn starts_on_a_white_key() {
+            let areas = Piano {
+                start_midi_note: 61,
+                ..ONE_OCTAVE
+            }
+            .areas(700, 100, &Tuning::default());
+            assert_eq!(note_at(&areas, 50, 90), Some(60));
+        }
+
+        #[test]
+        fn plays_the_closest_notes_of_other_tunings() {
+            let areas = ONE_OCTAVE.areas(700, 100, &Tuning::edo(31));
+            let white_keys: Vec<Option<i32>> =
+                (0..7).map(|i| note_at(&areas, i * 100 + 50, 90)).collect();
+            assert_eq!(
+                white_keys,
+                vec![155, 160, 165, 168, 173, 178, 183]
+                    .into_iter()
+                    .map(Some)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(note_at(&areas, 100, 30), Some(158));
+        }
+
+        #[test]
+        fn keeps_seven_white_and_five_black_keys_in_other_tunings() {
+            for tuning in &[Tuning::edo(5), Tuning::edo(19), Tuning::edo(31)] {
+                let areas = ONE_OCTAVE.areas(700, 100, tuning).areas;
+                let black_keys = areas
+                    .iter()
+                    .filter(|area| area.color == Some(BLACK_KEY_COLOR))
+                    .count();
+                assert_eq!((areas.len(), black_keys), (12, 5));
+            }
         }
     }
 }
@@ -1,35 +1,69 @@
+pub mod dev_input;
+pub mod layout_file;
 pub mod layouts;
+pub mod mounting;
 pub mod note_event_source;
 pub mod render;
 pub mod shape;
+pub mod spatial_index;
+pub mod touch_filter;
 
 use crate::evdev::Position;
-use crate::sound::midi::midi_to_frequency;
+use crate::sound::tuning::Tuning;
+use layouts::hexagon_spacing;
 use sdl2::pixels::Color;
-use shape::Shape;
+use shape::{parallelogram_coordinates, Shape};
+use spatial_index::SpatialIndex;
 
 #[derive(Clone, Debug, PartialEq)]
 struct Area {
     shape: Shape,
-    color: Color,
-    midi_note: i32,
+    // None colors the area by the position of its note in the scale.
+    color: Option<Color>,
+    // scale degree in the tuning of the areas, the same as the midi note in 12edo
+    note: i32,
+    // Draws a black line around the area, e.g. to separate neighbouring keys
+    // of the same color.
+    outline: bool,
 }
 
 impl Area {
-    fn new(shape: Shape, midi_note: i32) -> Area {
+    fn new(shape: Shape, note: i32) -> Area {
         Area {
             shape,
-            color: Areas::make_color(midi_note),
-            midi_note,
+            color: None,
+            note,
+            outline: false,
         }
     }
 }
 
+// How continuous pitches move between the notes of neighbouring areas, see
+// `Areas::continuous_frequency`.
+#[derive(Clone, Debug, PartialEq)]
+enum Glide {
+    // along the rows of parallelograms and hexagons
+    Rows,
+    // from the center of one key to the next, e.g. on a piano
+    Keys(Vec<KeyRow>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct KeyRow {
+    top: i32,
+    bottom: i32,
+    // the horizontal centers of the keys with their notes, from left to right
+    centers: Vec<(i32, i32)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Areas {
     areas: Vec<Area>,
     touch_width: i32,
     touch_height: i32,
+    tuning: Tuning,
+    glide: Glide,
+    index: SpatialIndex,
 }
 
 pub struct AreasConfig {
@@ -44,6 +78,7 @@ pub struct AreasConfig {
     pub row_interval: i32,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum Orientation {
     Portrait,
     Landscape,
@@ -86,29 +121,151 @@ impl Areas {
                 ));
             }
         }
+        Areas::from_areas(areas, touch_width, touch_height)
+    }
+
+    fn from_areas(areas: Vec<Area>, touch_width: i32, touch_height: i32) -> Areas {
+        let bounding_boxes: Vec<(Position, Position)> =
+            areas.iter().map(|area| area.shape.bounding_box()).collect();
         Areas {
+            index: SpatialIndex::new(&bounding_boxes, touch_width, touch_height),
             areas,
             touch_width,
             touch_height,
+            tuning: Tuning::default(),
+            glide: Glide::Rows,
         }
     }
 
+    pub fn with_tuning(self, tuning: Tuning) -> Areas {
+        Areas { tuning, ..self }
+    }
+
+    fn with_glide(self, glide: Glide) -> Areas {
+        Areas { glide, ..self }
+    }
+
     pub fn frequency(&self, position: &Position) -> Option<f32> {
+        self.area_index(position)
+            .and_then(|index| self.tuning.frequency(self.areas[index].note))
+    }
+
+    // Glides between the frequencies of neighbouring areas, hitting a note's
+    // frequency in the middle of its area: along the rows of parallelograms
+    // (u) and hexagons, or from key to key in layouts made of keys. Other
+    // shapes and areas without neighbours keep their frequency.
+    pub fn continuous_frequency(&self, position: &Position) -> Option<f32> {
+        let area = &self.areas[self.area_index(position)?];
+        let frequency = self.tuning.frequency(area.note)?;
+        let continuous_frequency = match &self.glide {
+            Glide::Rows => self.glide_along_row(area, frequency, position),
+            Glide::Keys(rows) => self.glide_between_keys(rows, position),
+        };
+        Some(continuous_frequency.unwrap_or(frequency))
+    }
+
+    fn glide_along_row(&self, area: &Area, frequency: f32, position: &Position) -> Option<f32> {
+        // The offset from the center of the area to the center of its right
+        // neighbour, and the position relative to the center in multiples of
+        // that offset.
+        let (center, step, offset) = match &area.shape {
+            Shape::Parallelogram { base, u, v } => {
+                let (u_component, _) = parallelogram_coordinates(base, u, v, position);
+                let center = Position {
+                    x: base.x + (u.x + v.x) / 2,
+                    y: base.y + (u.y + v.y) / 2,
+                };
+                (center, u.clone(), u_component - 0.5)
+            }
+            Shape::Hexagon { center, radius } => {
+                let (column_width, _) = hexagon_spacing(*radius);
+                let offset = (position.x - center.x) as f32 / column_width as f32;
+                (
+                    center.clone(),
+                    Position {
+                        x: column_width,
+                        y: 0,
+                    },
+                    offset,
+                )
+            }
+            _ => return None,
+        };
+        let direction = if offset < 0.0 { -1 } else { 1 };
+        let neighbour_center = Position {
+            x: center.x + direction * step.x,
+            y: center.y + direction * step.y,
+        };
+        let neighbour_frequency = self.frequency(&neighbour_center)?;
+        Some(frequency * (neighbour_frequency / frequency).powf(offset.abs()))
+    }
+
+    fn glide_between_keys(&self, rows: &[KeyRow], position: &Position) -> Option<f32> {
+        let row = rows
+            .iter()
+            .find(|row| row.top <= position.y && position.y <= row.bottom)?;
+        let right = row.centers.iter().position(|(x, _)| *x > position.x)?;
+        let (left_x, left_note) = row.centers[right.checked_sub(1)?];
+        let (right_x, right_note) = row.centers[right];
+        let left_frequency = self.tuning.frequency(left_note)?;
+        let right_frequency = self.tuning.frequency(right_note)?;
+        let fraction = (position.x - left_x) as f32 / (right_x - left_x) as f32;
+        Some(left_frequency * (right_frequency / left_frequency).powf(fraction))
+    }
+
+    // See `Shape::normalized_position`.
+    pub fn normalized_position(&self, position: &Position) -> Option<(f32, f32)> {
+        self.area_index(position)
+            .map(|index| self.areas[index].shape.normalized_position(position))
+    }
+
+    // The first area containing the position.
+    pub fn area_index(&self, position: &Position) -> Option<usize> {
+        match self.index.candidates(position) {
+            Some(candidates) => candidates
+                .iter()
+                .copied()
+                .find(|index| self.areas[*index].shape.contains(position)),
+            None => self.linear_area_index(position),
+        }
+    }
+
+    // Same as area_index, without the spatial index.
+    fn linear_area_index(&self, position: &Position) -> Option<usize> {
         self.areas
             .iter()
-            .find(|area| area.shape.contains(position))
-            .map(|area| midi_to_frequency(area.midi_note))
+            .position(|area| area.shape.contains(position))
     }
 
-    fn make_color(midi_note: i32) -> Color {
+    pub fn distance(&self, area_index: usize, position: &Position) -> f32 {
+        self.areas[area_index].shape.distance(position)
+    }
+
+    fn color(&self, area: &Area) -> Color {
+        area.color
+            .unwrap_or_else(|| Areas::make_color(&self.tuning, area.note))
+    }
+
+    // Goes through the hues along the circle of fifths of the tuning, or
+    // along the scale if its fifths don't reach all notes.
+    fn make_color(tuning: &Tuning, note: i32) -> Color {
         use palette::rgb::Rgb;
         use palette::rgb::Srgb;
         use palette::Hsv;
 
-        let hue_number = (midi_note * 7) % 12;
+        let size = tuning.notes_per_period();
+        let fifth = tuning.steps(7).rem_euclid(size);
+        let hue_number = match (1..size).find(|inverse| (inverse * fifth) % size == 1) {
+            Some(inverse) => (note * inverse).rem_euclid(size),
+            None => note.rem_euclid(size),
+        };
 
-        let c: Rgb<_, u8> =
-            Srgb::from(Hsv::new(hue_number as f32 * 30.0 + 240.0, 1.0, 1.0)).into_format();
+        let c: Rgb<_, u8> = Srgb::from(Hsv::new(
+            hue_number as f32 * (360.0 / size as f32) + 240.0,
+            1.0,
+            1.0,
+        ))
+        .into_format();
         Areas::convert_color(c)
     }
 
@@ -129,12 +286,18 @@ mod test {
 
             #[test]
             fn returns_blue_for_the_middle_c() {
-                assert_eq!(Areas::make_color(60), Color::RGB(0, 0, 255));
+                assert_eq!(
+                    Areas::make_color(&Tuning::default(), 60),
+                    Color::RGB(0, 0, 255)
+                );
             }
 
             #[test]
             fn returns_blue_one_octave_higher() {
-                assert_eq!(Areas::make_color(72), Color::RGB(0, 0, 255));
+                assert_eq!(
+                    Areas::make_color(&Tuning::default(), 72),
+                    Color::RGB(0, 0, 255)
+                );
             }
 
             #[test]
@@ -145,17 +308,282 @@ mod test {
                 let mut color = Hsv::from(Srgb::new(0.0, 0.0, 1.0));
                 color.hue += 360.0 / 12.0;
                 assert_eq!(
-                    Areas::make_color(7),
+                    Areas::make_color(&Tuning::default(), 7),
                     Areas::convert_color(Srgb::from(color).into_format())
                 );
                 color.hue += 360.0 / 12.0;
                 assert_eq!(
-                    Areas::make_color(62),
+                    Areas::make_color(&Tuning::default(), 62),
+                    Areas::convert_color(Srgb::from(color).into_format())
+                );
+            }
+
+            #[test]
+            fn cycles_through_the_fifths_of_other_tunings() {
+                use palette::Hsv;
+                use palette::Srgb;
+
+                let tuning = Tuning::edo(31);
+                assert_eq!(
+                    Areas::make_color(&tuning, 0),
+                    Areas::make_color(&tuning, 31)
+                );
+                let mut color = Hsv::from(Srgb::new(0.0, 0.0, 1.0));
+                color.hue += 360.0 / 31.0;
+                assert_eq!(
+                    Areas::make_color(&tuning, 18),
                     Areas::convert_color(Srgb::from(color).into_format())
                 );
             }
         }
 
+        mod continuous_frequency {
+            use super::*;
+            use crate::areas::layouts::{self, HexagonsConfig, Piano};
+
+            fn areas() -> Areas {
+                Areas::new(AreasConfig {
+                    touch_width: 800,
+                    touch_height: 600,
+                    orientation: Orientation::Portrait,
+                    u: Position { x: -0, y: -10 },
+                    v: Position { x: -6, y: -6 },
+                    column_range: (-1, 60),
+                    row_range: (0, 134),
+                    start_midi_note: 48,
+                    row_interval: 7,
+                })
+            }
+
+            fn assert_note(frequency: Option<f32>, note: f32) {
+                let expected = 440.0 * 2.0_f32.powf((note - 69.0) / 12.0);
+                let frequency = frequency.unwrap();
+                assert!(
+                    (frequency - expected).abs() < 0.01,
+                    "{} should be close to {}",
+                    frequency,
+                    expected
+                );
+            }
+
+            #[test]
+            fn is_the_frequency_of_the_note_in_the_middle_of_its_area() {
+                let areas = areas();
+                let middle = Position { x: 797, y: 592 };
+                assert_eq!(
+                    areas.continuous_frequency(&middle),
+                    areas.frequency(&middle)
+                );
+                assert_note(areas.continuous_frequency(&middle), 48.0);
+            }
+
+            #[test]
+            fn is_halfway_between_two_notes_on_their_border() {
+                assert_note(
+                    areas().continuous_frequency(&Position { x: 797, y: 587 }),
+                    48.5,
+                );
+            }
+
+            #[test]
+            fn glides_towards_the_neighbouring_notes() {
+                assert_note(
+                    areas().continuous_frequency(&Position { x: 797, y: 595 }),
+                    47.7,
+                );
+                assert_note(
+                    areas().continuous_frequency(&Position { x: 797, y: 584 }),
+                    48.8,
+                );
+            }
+
+            fn hexagons() -> Areas {
+                layouts::hexagons(HexagonsConfig {
+                    touch_width: 800,
+                    touch_height: 600,
+                    radius: 200,
+                    start_note: 48,
+                    right_interval: 2,
+                    up_right_interval: 7,
+                })
+            }
+
+            #[test]
+            fn glides_along_the_rows_of_hexagons() {
+                assert_note(
+                    hexagons().continuous_frequency(&Position { x: 346, y: 590 }),
+                    50.0,
+                );
+                assert_note(
+                    hexagons().continuous_frequency(&Position { x: 519, y: 590 }),
+                    51.0,
+                );
+                assert_note(
+                    hexagons().continuous_frequency(&Position { x: 173, y: 590 }),
+                    49.0,
+                );
+            }
+
+            fn piano() -> Areas {
+                Piano {
+                    start_midi_note: 60,
+                    octaves: 1,
+                    rows: 1,
+                }
+                .areas(700, 100, &Tuning::default())
+            }
+
+            #[test]
+            fn glides_from_the_center_of_one_piano_key_to_the_next() {
+                let areas = piano();
+                assert_note(
+                    areas.continuous_frequency(&Position { x: 150, y: 90 }),
+                    62.0,
+                );
+                assert_note(
+                    areas.continuous_frequency(&Position { x: 100, y: 30 }),
+                    61.0,
+                );
+                assert_note(
+                    areas.continuous_frequency(&Position { x: 100, y: 90 }),
+                    61.0,
+                );
+                assert_note(areas.continuous_frequency(&Position { x: 75, y: 90 }), 60.5);
+                assert_note(
+                    areas.continuous_frequency(&Position { x: 300, y: 90 }),
+                    64.5,
+                );
+            }
+
+            #[test]
+            fn keeps_the_frequency_of_the_outermost_piano_keys() {
+                assert_note(
+                    piano().continuous_frequency(&Position { x: 20, y: 90 }),
+                    60.0,
+                );
+            }
+
+            #[test]
+            fn keeps_the_frequency_for_other_shapes() {
+                let areas = Areas::from_areas(
+                    vec![Area::new(
+                        Shape::Circle {
+                            center: Position { x: 100, y: 100 },
+                            radius: 50,
+                        },
+                        60,
+                    )],
+                    800,
+                    600,
+                );
+                let position = Position { x: 130, y: 100 };
+                assert_note(areas.continuous_frequency(&position), 60.0);
+            }
+
+            #[test]
+            fn returns_none_outside_of_all_areas() {
+                assert_eq!(
+                    areas().continuous_frequency(&Position { x: 900, y: 0 }),
+                    None
+                );
+            }
+        }
+
+        mod area_index {
+            use super::*;
+            use crate::areas::layouts::{
+                grid, hexagons, parallelograms, HexagonsConfig, BOSANQUET_WILSON, PIANO,
+            };
+            use ::std::time::Instant;
+
+            fn big_grid() -> Areas {
+                Areas::new(AreasConfig {
+                    touch_width: 800,
+                    touch_height: 600,
+                    orientation: Orientation::Portrait,
+                    u: Position { x: -0, y: -10 },
+                    v: Position { x: -6, y: -6 },
+                    column_range: (-1, 60),
+                    row_range: (0, 134),
+                    start_midi_note: 36,
+                    row_interval: 7,
+                })
+            }
+
+            fn layouts() -> Vec<Areas> {
+                let tuning = Tuning::default();
+                vec![
+                    big_grid(),
+                    parallelograms(800, 600, &tuning),
+                    grid(800, 600, 80, 60, 0, &tuning),
+                    hexagons(HexagonsConfig {
+                        touch_width: 800,
+                        touch_height: 600,
+                        radius: 40,
+                        start_note: 36,
+                        right_interval: 2,
+                        up_right_interval: 7,
+                    }),
+                    BOSANQUET_WILSON.areas(800, 600, &tuning),
+                    PIANO.areas(800, 600, &tuning),
+                ]
+            }
+
+            #[test]
+            fn finds_the_same_areas_as_a_linear_search() {
+                for areas in layouts() {
+                    for x in (-20..820).step_by(7) {
+                        for y in (-20..620).step_by(7) {
+                            let position = Position { x, y };
+                            assert_eq!(
+                                areas.area_index(&position),
+                                areas.linear_area_index(&position),
+                                "at {:?}",
+                                position
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Run with `just bench`.
+            #[test]
+            #[ignore]
+            fn benchmark() {
+                let areas = big_grid();
+                let positions: Vec<Position> = (0..800)
+                    .step_by(7)
+                    .flat_map(|x| (0..600).step_by(7).map(move |y| Position { x, y }))
+                    .collect();
+                let measure =
+                    |description: &str, area_index: &dyn Fn(&Position) -> Option<usize>| {
+                        let start = Instant::now();
+                        let mut found = 0;
+                        for _ in 0..10 {
+                            for position in positions.iter() {
+                                if area_index(position).is_some() {
+                                    found += 1;
+                                }
+                            }
+                        }
+                        println!(
+                            "{}: {:?} for {} lookups in {} areas ({} found)",
+                            description,
+                            start.elapsed(),
+                            positions.len() * 10,
+                            areas.areas.len(),
+                            found
+                        );
+                    };
+                measure("linear search", &|position: &Position| {
+                    areas.linear_area_index(position)
+                });
+                measure("spatial index", &|position: &Position| {
+                    areas.area_index(position)
+                });
+            }
+        }
+
         mod new {
             use super::*;
 
@@ -256,10 +684,7 @@ mod test {
                 })
                 .areas;
                 assert_eq!(
-                    areas[0..3]
-                        .iter()
-                        .map(|x| &x.midi_note)
-                        .collect::<Vec<&i32>>(),
+                    areas[0..3].iter().map(|x| &x.note).collect::<Vec<&i32>>(),
                     vec![&35, &36, &37]
                 );
             }
@@ -313,10 +738,7 @@ mod test {
                 })
                 .areas;
                 assert_eq!(
-                    areas[62..65]
-                        .iter()
-                        .map(|x| &x.midi_note)
-                        .collect::<Vec<&i32>>(),
+                    areas[62..65].iter().map(|x| &x.note).collect::<Vec<&i32>>(),
                     vec![&43, &44, &45]
                 );
             }
@@ -336,10 +758,7 @@ mod test {
                 })
                 .areas;
                 assert_eq!(
-                    areas[62..65]
-                        .iter()
-                        .map(|x| &x.midi_note)
-                        .collect::<Vec<&i32>>(),
+                    areas[62..65].iter().map(|x| &x.note).collect::<Vec<&i32>>(),
                     vec![&40, &41, &42]
                 );
             }
@@ -359,10 +778,7 @@ mod test {
                 })
                 .areas;
                 assert_eq!(
-                    areas[0..3]
-                        .iter()
-                        .map(|x| &x.midi_note)
-                        .collect::<Vec<&i32>>(),
+                    areas[0..3].iter().map(|x| &x.note).collect::<Vec<&i32>>(),
                     vec![&47, &48, &49]
                 );
             }
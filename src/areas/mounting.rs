@@ -0,0 +1,238 @@
+use crate::cli;
+use crate::evdev::{DeviceInfo, Position, TouchState};
+
+// Clockwise rotation of the mounted screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Degrees0,
+    Degrees90,
+    Degrees180,
+    Degrees270,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation::Degrees0
+    }
+}
+
+// Converts between device coordinates (as reported by the touch device and
+// as used for rendering into the window) and instrument coordinates (as used
+// by the layouts). Positions are rotated first and mirrored afterwards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mounting {
+    pub rotation: Rotation,
+    pub mirror_x: bool,
+    pub mirror_y: bool,
+    pub device_width: i32,
+    pub device_height: i32,
+}
+
+impl Mounting {
+    pub fn new(cli_args: &cli::Args, device_info: &DeviceInfo) -> Mounting {
+        Mounting {
+            rotation: cli_args.rotation,
+            mirror_x: cli_args.mirror_x,
+            mirror_y: cli_args.mirror_y,
+            device_width: device_info.width(),
+            device_height: device_info.height(),
+        }
+    }
+
+    fn is_sideways(&self) -> bool {
+        match self.rotation {
+            Rotation::Degrees0 | Rotation::Degrees180 => false,
+            Rotation::Degrees90 | Rotation::Degrees270 => true,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        if self.is_sideways() {
+            self.device_height
+        } else {
+            self.device_width
+        }
+    }
+
+    pub fn height(&self) -> i32 {
+        if self.is_sideways() {
+            self.device_width
+        } else {
+            self.device_height
+        }
+    }
+
+    fn mirror(&self, position: Position) -> Position {
+        Position {
+            x: if self.mirror_x {
+                self.width() - position.x
+            } else {
+                position.x
+            },
+            y: if self.mirror_y {
+                self.height() - position.y
+            } else {
+                position.y
+            },
+        }
+    }
+
+    pub fn to_instrument(&self, position: &Position) -> Position {
+        let (width, height) = (self.device_width, self.device_height);
+        let Position { x, y } = *position;
+        self.mirror(match self.rotation {
+            Rotation::Degrees0 => Position { x, y },
+            Rotation::Degrees90 => Position {
+                x: height - y,
+                y: x,
+            },
+            Rotation::Degrees180 => Position {
+                x: width - x,
+                y: height - y,
+            },
+            Rotation::Degrees270 => Position { x: y, y: width - x },
+        })
+    }
+
+    pub fn to_device(&self, position: &Position) -> Position {
+        let (width, height) = (self.device_width, self.device_height);
+        let Position { x, y } = self.mirror(position.clone());
+        match self.rotation {
+            Rotation::Degrees0 => Position { x, y },
+            Rotation::Degrees90 => Position {
+                x: y,
+                y: height - x,
+            },
+            Rotation::Degrees180 => Position {
+                x: width - x,
+                y: height - y,
+            },
+            Rotation::Degrees270 => Position { x: width - y, y: x },
+        }
+    }
+
+    pub fn apply(&self, touch_state: TouchState) -> TouchState {
+        match touch_state {
+            TouchState::Touch {
+                tracking_id,
+                position,
+                contact,
+            } => TouchState::Touch {
+                tracking_id,
+                position: self.to_instrument(&position),
+                contact,
+            },
+            TouchState::NoTouch { .. } => touch_state,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::evdev::Contact;
+
+    pub fn mounting(rotation: Rotation, mirror_x: bool, mirror_y: bool) -> Mounting {
+        Mounting {
+            rotation,
+            mirror_x,
+            mirror_y,
+            device_width: 400,
+            device_height: 300,
+        }
+    }
+
+    pub fn unrotated() -> Mounting {
+        mounting(Rotation::Degrees0, false, false)
+    }
+
+    fn to_instrument(mounting: Mounting, x: i32, y: i32) -> Position {
+        mounting.to_instrument(&Position { x, y })
+    }
+
+    #[test]
+    fn leaves_positions_alone_by_default() {
+        assert_eq!(
+            to_instrument(unrotated(), 10, 20),
+            Position { x: 10, y: 20 }
+        );
+        assert_eq!((unrotated().width(), unrotated().height()), (400, 300));
+    }
+
+    #[test]
+    fn rotates_by_90_degrees() {
+        let mounting = mounting(Rotation::Degrees90, false, false);
+        assert_eq!((mounting.width(), mounting.height()), (300, 400));
+        assert_eq!(to_instrument(mounting, 0, 0), Position { x: 300, y: 0 });
+    }
+
+    #[test]
+    fn rotates_by_180_degrees() {
+        let mounting = mounting(Rotation::Degrees180, false, false);
+        assert_eq!((mounting.width(), mounting.height()), (400, 300));
+        assert_eq!(to_instrument(mounting, 10, 20), Position { x: 390, y: 280 });
+    }
+
+    #[test]
+    fn rotates_by_270_degrees() {
+        let mounting = mounting(Rotation::Degrees270, false, false);
+        assert_eq!((mounting.width(), mounting.height()), (300, 400));
+        assert_eq!(to_instrument(mounting, 0, 0), Position { x: 0, y: 400 });
+    }
+
+    #[test]
+    fn mirrors_after_rotating() {
+        assert_eq!(
+            to_instrument(mounting(Rotation::Degrees0, true, false), 10, 20),
+            Position { x: 390, y: 20 }
+        );
+        assert_eq!(
+            to_instrument(mounting(Rotation::Degrees0, false, true), 10, 20),
+            Position { x: 10, y: 280 }
+        );
+        assert_eq!(
+            to_instrument(mounting(Rotation::Degrees90, true, false), 0, 0),
+            Position { x: 0, y: 0 }
+        );
+    }
+
+    #[test]
+    fn converts_back_to_device_coordinates() {
+        for &rotation in &[
+            Rotation::Degrees0,
+            Rotation::Degrees90,
+            Rotation::Degrees180,
+            Rotation::Degrees270,
+        ] {
+            for &(mirror_x, mirror_y) in &[(false, false), (true, false), (false, true)] {
+                let mounting = mounting(rotation, mirror_x, mirror_y);
+                let position = Position { x: 10, y: 20 };
+                assert_eq!(
+                    mounting.to_device(&mounting.to_instrument(&position)),
+                    position
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transforms_touch_states() {
+        let mounting = mounting(Rotation::Degrees180, false, false);
+        assert_eq!(
+            mounting.apply(TouchState::Touch {
+                tracking_id: 3,
+                position: Position { x: 10, y: 20 },
+                contact: Contact::default(),
+            }),
+            TouchState::Touch {
+                tracking_id: 3,
+                position: Position { x: 390, y: 280 },
+                contact: Contact::default(),
+            }
+        );
+        assert_eq!(
+            mounting.apply(TouchState::NoTouch { tracking_id: 3 }),
+            TouchState::NoTouch { tracking_id: 3 }
+        );
+    }
+}
@@ -1,46 +1,121 @@
 use crate::areas::Areas;
-use crate::evdev::TouchState;
-use crate::sound::{mk_voices, NoteEvent, POLYPHONY};
+use crate::evdev::{Position, TouchState};
+use crate::sound::voice_allocator::VoiceAllocator;
+use crate::sound::NoteEvent;
+use ::std::collections::HashMap;
+
+custom_derive! {
+#[derive(Debug, Clone, Copy, IterVariants(PitchModeVariants), PartialEq)]
+    pub enum PitchMode {
+        // Every touch plays the frequency of the note under it.
+        Discrete,
+        // The frequency follows the position inside the areas, see
+        // `Areas::continuous_frequency`.
+        Continuous,
+        // Touches start on the frequency of the note under them and glide
+        // continuously while moving.
+        Glide,
+    }
+}
+
+impl Default for PitchMode {
+    fn default() -> PitchMode {
+        PitchMode::Discrete
+    }
+}
 
 pub struct NoteEventSource {
     areas: Areas,
     touch_state_source: Box<dyn Iterator<Item = TouchState>>,
-    state: [NoteEvent; POLYPHONY],
+    voice_allocator: VoiceAllocator,
+    state: Vec<NoteEvent>,
+    pitch_mode: PitchMode,
+    // For PitchMode::Glide: the ratio between the snapped and the continuous
+    // frequency when the touch started, by tracking id.
+    glide_factors: HashMap<i32, f32>,
 }
 
 impl NoteEventSource {
     pub fn new(
         areas: Areas,
         touch_state_source: impl Iterator<Item = TouchState> + 'static,
+        voice_allocator: VoiceAllocator,
     ) -> NoteEventSource {
         NoteEventSource {
             areas,
             touch_state_source: Box::new(touch_state_source),
-            state: mk_voices(NoteEvent::NoteOff),
+            state: vec![NoteEvent::NoteOff; voice_allocator.polyphony()],
+            voice_allocator,
+            pitch_mode: PitchMode::default(),
+            glide_factors: HashMap::new(),
+        }
+    }
+
+    pub fn with_pitch_mode(self, pitch_mode: PitchMode) -> NoteEventSource {
+        NoteEventSource { pitch_mode, ..self }
+    }
+
+    fn frequency(&mut self, tracking_id: i32, position: &Position) -> Option<f32> {
+        match self.pitch_mode {
+            PitchMode::Discrete => self.areas.frequency(position),
+            PitchMode::Continuous => self.areas.continuous_frequency(position),
+            PitchMode::Glide => {
+                let continuous_frequency = self.areas.continuous_frequency(position)?;
+                match self.glide_factors.get(&tracking_id) {
+                    Some(glide_factor) => Some(continuous_frequency * glide_factor),
+                    None => {
+                        let frequency = self.areas.frequency(position)?;
+                        self.glide_factors
+                            .insert(tracking_id, frequency / continuous_frequency);
+                        Some(frequency)
+                    }
+                }
+            }
         }
     }
 }
 
 impl Iterator for NoteEventSource {
-    type Item = [NoteEvent; POLYPHONY];
+    type Item = Vec<NoteEvent>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.touch_state_source.next().map(|touchstate| {
-            let (tracking_id, note_event) = match touchstate {
-                TouchState::NoTouch { tracking_id } => (tracking_id, NoteEvent::NoteOff),
+            match touchstate {
+                TouchState::NoTouch { tracking_id } => {
+                    self.glide_factors.remove(&tracking_id);
+                    if let Some(voice) = self.voice_allocator.release(tracking_id) {
+                        self.state[voice] = NoteEvent::NoteOff;
+                    }
+                }
                 TouchState::Touch {
                     position,
                     tracking_id,
-                    ..
-                } => (
-                    tracking_id,
-                    match self.areas.frequency(&position) {
-                        Some(frequency) => NoteEvent::NoteOn(frequency),
-                        None => NoteEvent::NoteOff,
-                    },
-                ),
-            };
-            self.state[(tracking_id % POLYPHONY as i32) as usize] = note_event;
+                    contact,
+                } => {
+                    let (note_event, level) = match self.frequency(tracking_id, &position) {
+                        Some(frequency) => {
+                            let velocity = contact.intensity();
+                            let (u, v) = self
+                                .areas
+                                .normalized_position(&position)
+                                .unwrap_or((0.5, 0.5));
+                            (
+                                NoteEvent::NoteOn {
+                                    frequency,
+                                    velocity,
+                                    u,
+                                    v,
+                                },
+                                velocity,
+                            )
+                        }
+                        None => (NoteEvent::NoteOff, 0.0),
+                    };
+                    if let Some(voice) = self.voice_allocator.touch(tracking_id, level) {
+                        self.state[voice] = note_event;
+                    }
+                }
+            }
             self.state.clone()
         })
     }
@@ -50,9 +125,15 @@ impl Iterator for NoteEventSource {
 pub mod test {
     use super::NoteEvent::*;
     use super::*;
-    use crate::evdev::Position;
+    use crate::evdev::{Contact, Position};
     use crate::sound::midi::midi_to_frequency;
     use crate::sound::test::mk_test_voices;
+    use crate::sound::voice_allocator::VoiceStealing;
+    use crate::sound::DEFAULT_POLYPHONY;
+
+    fn voice_allocator(polyphony: usize) -> VoiceAllocator {
+        VoiceAllocator::new(polyphony, VoiceStealing::default())
+    }
 
     mod note_event_source {
         use super::*;
@@ -72,6 +153,24 @@ pub mod test {
             })
         }
 
+        // The touches in these tests are all at the same position inside of
+        // their areas.
+        fn note_on(frequency: f32) -> NoteEvent {
+            note_on_with_velocity(frequency, 1.0)
+        }
+
+        fn note_on_with_velocity(frequency: f32, velocity: f32) -> NoteEvent {
+            let (u, v) = areas(48)
+                .normalized_position(&Position { x: 798, y: 595 })
+                .unwrap();
+            NoteOn {
+                frequency,
+                velocity,
+                u,
+                v,
+            }
+        }
+
         #[test]
         fn yields_frequencies() {
             let mut frequencies = NoteEventSource::new(
@@ -79,12 +178,14 @@ pub mod test {
                 vec![TouchState::Touch {
                     tracking_id: 0,
                     position: Position { x: 798, y: 595 },
+                    contact: Contact::default(),
                 }]
                 .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
             );
             assert_eq!(
                 frequencies.next().unwrap()[0],
-                NoteOn(midi_to_frequency(48))
+                note_on(midi_to_frequency(48))
             );
         }
 
@@ -93,6 +194,7 @@ pub mod test {
             let mut frequencies = NoteEventSource::new(
                 areas(48),
                 vec![TouchState::NoTouch { tracking_id: 0 }].into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
             );
             assert_eq!(frequencies.next().unwrap()[0], NoteOff);
         }
@@ -104,56 +206,135 @@ pub mod test {
                 vec![TouchState::Touch {
                     tracking_id: 0,
                     position: Position { x: 798, y: 595 },
+                    contact: Contact::default(),
                 }]
                 .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
             );
             assert_eq!(
                 frequencies.next().unwrap()[0],
-                NoteOn(midi_to_frequency(49))
+                note_on(midi_to_frequency(49))
             );
         }
 
         #[test]
-        fn uses_the_tracking_id_as_voice_index_if_possible() {
-            for i in 0..POLYPHONY {
-                println!("i: {}", i);
-                let mut frequencies = NoteEventSource::new(
-                    areas(48),
-                    vec![TouchState::Touch {
-                        tracking_id: i as i32,
-                        position: Position { x: 798, y: 595 },
-                    }]
-                    .into_iter(),
-                );
-                assert_eq!(
-                    frequencies.next(),
-                    Some(mk_test_voices(vec![(i, NoteOn(midi_to_frequency(48)))]))
-                );
+        fn uses_the_touch_intensity_as_velocity() {
+            let mut frequencies = NoteEventSource::new(
+                areas(48),
+                vec![TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 798, y: 595 },
+                    contact: Contact {
+                        pressure: Some(0.4),
+                        size: None,
+                    },
+                }]
+                .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
+            );
+            assert_eq!(
+                frequencies.next().unwrap()[0],
+                note_on_with_velocity(midi_to_frequency(48), 0.4)
+            );
+        }
+
+        fn touch(tracking_id: i32) -> TouchState {
+            TouchState::Touch {
+                tracking_id,
+                position: Position { x: 798, y: 595 },
+                contact: Contact::default(),
             }
         }
 
         #[test]
-        fn wraps_around_when_tracking_numbers_get_too_big() {
-            for tracking_id in (POLYPHONY as i32)..(POLYPHONY as i32 * 3) {
-                println!("tracking_id: {}", tracking_id);
+        fn uses_the_first_free_voice_for_new_touches() {
+            let mut frequencies = NoteEventSource::new(
+                areas(48),
+                vec![touch(7)].into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
+            );
+            assert_eq!(
+                frequencies.next(),
+                Some(mk_test_voices(vec![(0, note_on(midi_to_frequency(48)))]))
+            );
+        }
+
+        #[test]
+        fn handles_big_tracking_ids() {
+            for tracking_id in (DEFAULT_POLYPHONY as i32)..(DEFAULT_POLYPHONY as i32 * 3) {
                 let mut frequencies = NoteEventSource::new(
                     areas(48),
-                    vec![TouchState::Touch {
-                        tracking_id,
-                        position: Position { x: 798, y: 595 },
-                    }]
-                    .into_iter(),
+                    vec![touch(tracking_id)].into_iter(),
+                    voice_allocator(DEFAULT_POLYPHONY),
                 );
                 assert_eq!(
                     frequencies.next(),
-                    Some(mk_test_voices(vec![(
-                        (tracking_id % (POLYPHONY as i32)) as usize,
-                        NoteOn(midi_to_frequency(48))
-                    )]))
+                    Some(mk_test_voices(vec![(0, note_on(midi_to_frequency(48)))]))
                 );
             }
         }
 
+        #[test]
+        fn does_not_cut_off_touches_whose_tracking_ids_differ_by_the_polyphony() {
+            let tracking_id = 3;
+            let mut frequencies = NoteEventSource::new(
+                areas(48),
+                vec![
+                    touch(tracking_id),
+                    touch(tracking_id + DEFAULT_POLYPHONY as i32),
+                ]
+                .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
+            );
+            frequencies.next();
+            assert_eq!(
+                frequencies.next(),
+                Some(mk_test_voices(vec![
+                    (0, note_on(midi_to_frequency(48))),
+                    (1, note_on(midi_to_frequency(48)))
+                ]))
+            );
+        }
+
+        #[test]
+        fn steals_voices_when_all_voices_are_in_use() {
+            let mut frequencies = NoteEventSource::new(
+                areas(48),
+                vec![
+                    touch(0),
+                    touch(1),
+                    TouchState::Touch {
+                        tracking_id: 2,
+                        position: Position { x: 10000, y: 10000 },
+                        contact: Contact::default(),
+                    },
+                    TouchState::NoTouch { tracking_id: 0 },
+                ]
+                .into_iter(),
+                voice_allocator(2),
+            );
+            frequencies.next();
+            frequencies.next();
+            assert_eq!(
+                frequencies.next(),
+                Some(vec![NoteOff, note_on(midi_to_frequency(48))])
+            );
+            assert_eq!(
+                frequencies.next(),
+                Some(vec![NoteOff, note_on(midi_to_frequency(48))])
+            );
+        }
+
+        #[test]
+        fn uses_the_configured_number_of_voices() {
+            let mut frequencies =
+                NoteEventSource::new(areas(48), vec![touch(4)].into_iter(), voice_allocator(3));
+            assert_eq!(
+                frequencies.next(),
+                Some(vec![note_on(midi_to_frequency(48)), NoteOff, NoteOff])
+            );
+        }
+
         #[test]
         fn preserves_the_state_of_voices() {
             let mut frequencies = NoteEventSource::new(
@@ -162,27 +343,253 @@ pub mod test {
                     TouchState::Touch {
                         tracking_id: 0,
                         position: Position { x: 798, y: 595 },
+                        contact: Contact::default(),
                     },
                     TouchState::Touch {
                         tracking_id: 1,
                         position: Position { x: 798, y: 595 },
+                        contact: Contact::default(),
                     },
                     TouchState::NoTouch { tracking_id: 0 },
                 ]
                 .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
             );
             frequencies.next();
             assert_eq!(
                 frequencies.next(),
                 Some(mk_test_voices(vec![
-                    (0, NoteOn(midi_to_frequency(48))),
-                    (1, NoteOn(midi_to_frequency(48)))
+                    (0, note_on(midi_to_frequency(48))),
+                    (1, note_on(midi_to_frequency(48)))
                 ]))
             );
             assert_eq!(
                 frequencies.next(),
-                Some(mk_test_voices(vec![(1, NoteOn(midi_to_frequency(48)))]))
+                Some(mk_test_voices(vec![(1, note_on(midi_to_frequency(48)))]))
+            );
+        }
+
+        #[test]
+        fn yields_the_position_inside_of_the_area() {
+            let mut frequencies = NoteEventSource::new(
+                areas(48),
+                vec![TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 797, y: 595 },
+                    contact: Contact::default(),
+                }]
+                .into_iter(),
+                voice_allocator(DEFAULT_POLYPHONY),
             );
+            match frequencies.next().unwrap()[0] {
+                NoteOn { u, v, .. } => {
+                    assert!((u - 0.2).abs() < 0.001, "u: {}", u);
+                    assert!((v - 0.5).abs() < 0.001, "v: {}", v);
+                }
+                NoteOff => panic!("expected a note on"),
+            }
+        }
+
+        mod pitch_mode {
+            use super::*;
+
+            fn frequencies(pitch_mode: PitchMode, positions: Vec<Position>) -> Vec<f32> {
+                let touches: Vec<TouchState> = positions
+                    .into_iter()
+                    .map(|position| TouchState::Touch {
+                        tracking_id: 0,
+                        position,
+                        contact: Contact::default(),
+                    })
+                    .collect();
+                NoteEventSource::new(
+                    areas(48),
+                    touches.into_iter(),
+                    voice_allocator(DEFAULT_POLYPHONY),
+                )
+                .with_pitch_mode(pitch_mode)
+                .map(|voices| match voices[0] {
+                    NoteOn { frequency, .. } => frequency,
+                    NoteOff => panic!("expected a note on"),
+                })
+                .collect()
+            }
+
+            // Positions in the first area (note 48), going up in the row.
+            fn positions() -> Vec<Position> {
+                vec![
+                    Position { x: 797, y: 595 },
+                    Position { x: 797, y: 592 },
+                    Position { x: 797, y: 587 },
+                ]
+            }
+
+            #[test]
+            fn snaps_to_the_notes_by_default() {
+                assert_eq!(
+                    frequencies(PitchMode::default(), positions()),
+                    vec![midi_to_frequency(48); 3]
+                );
+            }
+
+            #[test]
+            fn follows_the_position_continuously() {
+                let areas = areas(48);
+                assert_eq!(
+                    frequencies(PitchMode::Continuous, positions()),
+                    positions()
+                        .iter()
+                        .map(|position| areas.continuous_frequency(position).unwrap())
+                        .collect::<Vec<f32>>()
+                );
+            }
+
+            #[test]
+            fn starts_gliding_from_the_touched_note() {
+                let areas = areas(48);
+                let positions = positions();
+                let frequencies = frequencies(PitchMode::Glide, positions.clone());
+                assert_eq!(frequencies[0], midi_to_frequency(48));
+                for (frequency, position) in frequencies.iter().zip(positions.iter()).skip(1) {
+                    let expected = midi_to_frequency(48)
+                        * areas.continuous_frequency(position).unwrap()
+                        / areas.continuous_frequency(&positions[0]).unwrap();
+                    assert!((frequency - expected).abs() < 0.001);
+                }
+                assert!(frequencies[1] > frequencies[0]);
+                assert!(frequencies[2] > frequencies[1]);
+            }
+
+            #[test]
+            fn snaps_again_for_new_touches() {
+                let mut source = NoteEventSource::new(
+                    areas(48),
+                    vec![
+                        TouchState::Touch {
+                            tracking_id: 0,
+                            position: Position { x: 797, y: 595 },
+                            contact: Contact::default(),
+                        },
+                        TouchState::NoTouch { tracking_id: 0 },
+                        TouchState::Touch {
+                            tracking_id: 0,
+                            position: Position { x: 797, y: 587 },
+                            contact: Contact::default(),
+                        },
+                    ]
+                    .into_iter(),
+                    voice_allocator(DEFAULT_POLYPHONY),
+                )
+                .with_pitch_mode(PitchMode::Glide);
+                source.next();
+                source.next();
+                let frequencies: Vec<f32> = source
+                    .next()
+                    .unwrap()
+                    .into_iter()
+                    .filter_map(|voice| match voice {
+                        NoteOn { frequency, .. } => Some(frequency),
+                        NoteOff => None,
+                    })
+                    .collect();
+                assert_eq!(frequencies, vec![midi_to_frequency(48)]);
+            }
+        }
+
+        mod end_to_end {
+            use super::*;
+            use crate::evdev::uinput::VirtualTouchscreen;
+            use crate::evdev::{DeviceLocation, InputEventSource, TouchStateSource};
+            use crate::utils::shutdown::Shutdown;
+            use ::std::sync::mpsc::{channel, RecvTimeoutError};
+            use ::std::thread;
+            use ::std::time::Duration;
+
+            // Reads the given number of note event arrays from the device in
+            // a separate thread, since the device can't be moved across threads.
+            // The script is run once the device is opened.
+            fn note_events(
+                touchscreen: &VirtualTouchscreen,
+                count: usize,
+                script: impl FnOnce(),
+            ) -> Vec<Vec<NoteEvent>> {
+                let path = touchscreen.path();
+                let (ready_sender, ready) = channel();
+                let (result_sender, result) = channel();
+                thread::spawn(move || {
+                    let input_event_source =
+                        InputEventSource::new(DeviceLocation::Path(path), true, Shutdown::new())
+                            .unwrap();
+                    ready_sender.send(()).unwrap();
+                    let note_events: Vec<Vec<NoteEvent>> = NoteEventSource::new(
+                        areas(48),
+                        TouchStateSource::new(input_event_source),
+                        voice_allocator(DEFAULT_POLYPHONY),
+                    )
+                    .take(count)
+                    .collect();
+                    result_sender.send(note_events).unwrap();
+                });
+                ready.recv_timeout(Duration::from_secs(5)).unwrap();
+                script();
+                match result.recv_timeout(Duration::from_secs(5)) {
+                    Ok(note_events) => note_events,
+                    Err(RecvTimeoutError::Timeout) => {
+                        panic!("timeout while waiting for note events")
+                    }
+                    Err(RecvTimeoutError::Disconnected) => panic!("reading note events failed"),
+                }
+            }
+
+            // Needs /dev/uinput, run with `just uinput_test`.
+            #[test]
+            #[ignore]
+            fn plays_notes_for_touches_on_a_kernel_device() {
+                let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+                let note_events = note_events(&touchscreen, 3, || {
+                    touchscreen.touch(0, 10, Position { x: 798, y: 595 });
+                    touchscreen.move_to(0, Position { x: 798, y: 585 });
+                    touchscreen.release(0);
+                });
+                assert_eq!(
+                    note_events,
+                    vec![
+                        mk_test_voices(vec![(0, note_on(midi_to_frequency(48)))]),
+                        mk_test_voices(vec![(0, note_on(midi_to_frequency(49)))]),
+                        mk_test_voices(vec![]),
+                    ]
+                );
+            }
+
+            // Needs /dev/uinput, run with `just uinput_test`.
+            #[test]
+            #[ignore]
+            fn tracks_multiple_fingers_on_a_kernel_device() {
+                let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+                let note_events = note_events(&touchscreen, 5, || {
+                    touchscreen.touch(0, 10, Position { x: 798, y: 595 });
+                    touchscreen.touch(1, 11, Position { x: 798, y: 585 });
+                    touchscreen.move_to(0, Position { x: 798, y: 575 });
+                    touchscreen.release(0);
+                    touchscreen.release(1);
+                });
+                assert_eq!(
+                    note_events,
+                    vec![
+                        mk_test_voices(vec![(0, note_on(midi_to_frequency(48)))]),
+                        mk_test_voices(vec![
+                            (0, note_on(midi_to_frequency(48))),
+                            (1, note_on(midi_to_frequency(49))),
+                        ]),
+                        mk_test_voices(vec![
+                            (0, note_on(midi_to_frequency(50))),
+                            (1, note_on(midi_to_frequency(49))),
+                        ]),
+                        mk_test_voices(vec![(1, note_on(midi_to_frequency(49)))]),
+                        mk_test_voices(vec![]),
+                    ]
+                );
+            }
         }
     }
 }
@@ -1,6 +1,11 @@
+use crate::areas::dev_input::DevInput;
+use crate::areas::mounting::Mounting;
 use crate::areas::Areas;
 use crate::cli;
+use crate::evdev::TouchState;
 use crate::get_binary_name;
+use crate::sound::pitch::PitchControl;
+use crate::utils::shutdown::Shutdown;
 use crate::ErrorString;
 use ::sdl2::event::Event;
 use ::sdl2::gfx::primitives::DrawRenderer;
@@ -10,67 +15,107 @@ use ::sdl2::render::Canvas;
 use ::sdl2::video::Window;
 use ::sdl2::EventPump;
 use ::sdl2::VideoSubsystem;
+use ::std::sync::mpsc::Sender;
 
 impl Areas {
-    pub fn spawn_ui(self, cli_args: &cli::Args) {
+    pub fn spawn_ui(
+        self,
+        cli_args: &cli::Args,
+        mounting: Mounting,
+        dev_input: Option<Sender<TouchState>>,
+        pitch_control: PitchControl,
+        shutdown: Shutdown,
+    ) {
         let clone = cli_args.clone();
         ::std::thread::spawn(move || {
-            self.run_ui(&clone);
+            self.run_ui(&clone, mounting, dev_input, pitch_control, shutdown);
         });
     }
 
-    pub fn run_ui(self, cli_args: &cli::Args) {
-        if let Err(e) = Ui::run_ui(&cli_args, self) {
-            eprintln!("error in ui thread: {:?}", e);
+    pub fn run_ui(
+        self,
+        cli_args: &cli::Args,
+        mounting: Mounting,
+        dev_input: Option<Sender<TouchState>>,
+        pitch_control: PitchControl,
+        shutdown: Shutdown,
+    ) {
+        match Ui::run_ui(&cli_args, self, mounting, dev_input, pitch_control) {
+            // The window was closed.
+            Ok(()) => shutdown.request(),
+            Err(e) => eprintln!("error in ui thread: {:?}", e),
         }
     }
 }
 
+fn get_screen_rect(video_subsystem: &VideoSubsystem) -> Result<sdl2::rect::Rect, ErrorString> {
+    video_subsystem
+        .display_bounds(1)
+        .or_else(|_| video_subsystem.display_bounds(0))
+        .map_err(From::from)
+}
+
+pub fn open_window(cli_args: &cli::Args) -> Result<(Canvas<Window>, EventPump), ErrorString> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let screen_rect = get_screen_rect(&video_subsystem)?;
+    let initial_window_size = if cli_args.dev_mode {
+        (800, 600)
+    } else {
+        (screen_rect.width(), screen_rect.height())
+    };
+    let window = video_subsystem
+        .window(
+            &get_binary_name()?,
+            initial_window_size.0,
+            initial_window_size.1,
+        )
+        .position(screen_rect.x(), screen_rect.y())
+        .resizable()
+        .build()?;
+    let canvas = window.into_canvas().build()?;
+    let event_pump = sdl_context.event_pump()?;
+    Ok((canvas, event_pump))
+}
+
 struct Ui {
     canvas: Canvas<Window>,
     event_pump: EventPump,
     areas: Areas,
+    mounting: Mounting,
+    dev_input: Option<DevInput>,
+    pitch_control: PitchControl,
 }
 
 impl Ui {
-    fn run_ui(cli_args: &cli::Args, areas: Areas) -> Result<(), ErrorString> {
-        let mut ui = Ui::new(cli_args, areas)?;
-        ui.run_main_loop()?;
-        ui.quit();
-        Ok(())
-    }
-
-    fn get_screen_rect(video_subsystem: &VideoSubsystem) -> Result<sdl2::rect::Rect, ErrorString> {
-        video_subsystem
-            .display_bounds(1)
-            .or_else(|_| video_subsystem.display_bounds(0))
-            .map_err(From::from)
+    fn run_ui(
+        cli_args: &cli::Args,
+        areas: Areas,
+        mounting: Mounting,
+        dev_input: Option<Sender<TouchState>>,
+        pitch_control: PitchControl,
+    ) -> Result<(), ErrorString> {
+        let mut ui = Ui::new(cli_args, areas, mounting, dev_input, pitch_control)?;
+        ui.run_main_loop()
     }
 
-    fn new(cli_args: &cli::Args, areas: Areas) -> Result<Ui, ErrorString> {
-        let sdl_context = sdl2::init()?;
-        let video_subsystem = sdl_context.video()?;
-        let screen_rect = Ui::get_screen_rect(&video_subsystem)?;
-        let initial_window_size = if cli_args.dev_mode {
-            (800, 600)
-        } else {
-            (screen_rect.width(), screen_rect.height())
-        };
-        let window = video_subsystem
-            .window(
-                &get_binary_name()?,
-                initial_window_size.0,
-                initial_window_size.1,
-            )
-            .position(screen_rect.x(), screen_rect.y())
-            .resizable()
-            .build()?;
-        let canvas = window.into_canvas().build()?;
-        let event_pump = sdl_context.event_pump()?;
+    fn new(
+        cli_args: &cli::Args,
+        areas: Areas,
+        mounting: Mounting,
+        dev_input: Option<Sender<TouchState>>,
+        pitch_control: PitchControl,
+    ) -> Result<Ui, ErrorString> {
+        let (canvas, event_pump) = open_window(cli_args)?;
+        let dev_input = dev_input
+            .map(|sender| DevInput::new(sender, mounting.device_width, mounting.device_height));
         let mut ui = Ui {
             canvas,
             event_pump,
             areas,
+            mounting,
+            dev_input,
+            pitch_control,
         };
         ui.draw()?;
         Ok(ui)
@@ -78,7 +123,8 @@ impl Ui {
 
     fn run_main_loop(&mut self) -> Result<(), ErrorString> {
         'main: loop {
-            match self.event_pump.wait_event() {
+            let event = self.event_pump.wait_event();
+            match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
@@ -87,20 +133,51 @@ impl Ui {
                 Event::Window { .. } => {
                     self.draw()?;
                 }
-                _ => {}
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if !self.change_pitch(keycode) {
+                        self.handle_dev_input(&event);
+                    }
+                }
+                _ => self.handle_dev_input(&event),
             }
         }
         Ok(())
     }
 
-    fn quit(&self) {
-        ::std::process::exit(0);
+    fn handle_dev_input(&mut self, event: &Event) {
+        if let Some(dev_input) = &mut self.dev_input {
+            dev_input.handle(event, self.canvas.window().size());
+        }
     }
 
+    // Up/Down transpose by a semitone, PageUp/PageDown by an octave and
+    // Left/Right change the concert pitch by one Hz.
+    fn change_pitch(&self, keycode: Keycode) -> bool {
+        match keycode {
+            Keycode::Up => self.pitch_control.transpose(1),
+            Keycode::Down => self.pitch_control.transpose(-1),
+            Keycode::PageUp => self.pitch_control.transpose(12),
+            Keycode::PageDown => self.pitch_control.transpose(-12),
+            Keycode::Right => self.pitch_control.change_concert_pitch(1.0),
+            Keycode::Left => self.pitch_control.change_concert_pitch(-1.0),
+            _ => return false,
+        }
+        eprintln!(
+            "concert pitch: {} Hz, transposition: {} semitones",
+            self.pitch_control.concert_pitch(),
+            self.pitch_control.transposition()
+        );
+        true
+    }
+
+    // The window shows the screen as mounted, so it's in device coordinates.
     fn get_window_factors(&self) -> (f32, f32) {
         let window_size = self.canvas.window().size();
-        let x_factor = window_size.0 as f32 / self.areas.touch_width as f32;
-        let y_factor = window_size.1 as f32 / self.areas.touch_height as f32;
+        let x_factor = window_size.0 as f32 / self.mounting.device_width as f32;
+        let y_factor = window_size.1 as f32 / self.mounting.device_height as f32;
         (x_factor, y_factor)
     }
 
@@ -108,10 +185,15 @@ impl Ui {
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
         let (x_factor, y_factor) = self.get_window_factors();
-        for area in &self.areas.areas {
-            let (xs, ys) = &area.shape.to_polygon(x_factor, y_factor);
-            let color = area.color;
+        // Areas that come first win when looking up the area for a position,
+        // so they are drawn on top.
+        for area in self.areas.areas.iter().rev() {
+            let (xs, ys) = &area.shape.to_polygon(&self.mounting, x_factor, y_factor);
+            let color = self.areas.color(area);
             self.canvas.filled_polygon(&xs, &ys, color)?;
+            if area.outline {
+                self.canvas.polygon(&xs, &ys, Color::RGB(0, 0, 0))?;
+            }
         }
         self.canvas.present();
         Ok(())
@@ -1,4 +1,8 @@
-use crate::evdev::Position;
+use crate::areas::mounting::Mounting;
+use crate::evdev::{squared_distance, Position};
+
+// Number of corners used to render circles.
+const CIRCLE_CORNERS: usize = 32;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Shape {
@@ -7,53 +11,219 @@ pub enum Shape {
         u: Position,
         v: Position,
     },
+    // A regular hexagon with a corner at the top. The radius is the distance
+    // from the center to the corners.
+    Hexagon {
+        center: Position,
+        radius: i32,
+    },
+    Circle {
+        center: Position,
+        radius: i32,
+    },
+    // Polygons may be concave, but their edges must not cross each other.
+    Polygon {
+        corners: Vec<Position>,
+    },
 }
 
 impl Shape {
     pub fn contains(&self, position: &Position) -> bool {
         match self {
             Shape::Parallelogram { base, u, v } => {
-                let translated_position = Position {
-                    x: position.x - base.x,
-                    y: position.y - base.y,
-                };
-                let multiplication_factor = 1.0 / (u.x * v.y - v.x * u.y) as f32;
-                let u_component = (translated_position.x * v.y + translated_position.y * (-v.x))
-                    as f32
-                    * multiplication_factor;
-                let v_component = (translated_position.x * (-u.y) + translated_position.y * u.x)
-                    as f32
-                    * multiplication_factor;
+                let (u_component, v_component) = parallelogram_coordinates(base, u, v, position);
                 u_component >= 0.0 && u_component <= 1.0 && v_component >= 0.0 && v_component <= 1.0
             }
+            Shape::Circle { center, radius } => {
+                squared_distance(position, center) <= i64::from(*radius) * i64::from(*radius)
+            }
+            Shape::Hexagon { .. } | Shape::Polygon { .. } => {
+                polygon_contains(&self.corners(), position)
+            }
+        }
+    }
+
+    // Distance from the position to the border of the shape, 0 for positions inside.
+    pub fn distance(&self, position: &Position) -> f32 {
+        if self.contains(position) {
+            return 0.0;
         }
+        if let Shape::Circle { center, radius } = self {
+            return (squared_distance(position, center) as f32).sqrt() - *radius as f32;
+        }
+        let corners = self.corners();
+        corners
+            .iter()
+            .zip(corners.iter().cycle().skip(1))
+            .map(|(a, b)| segment_distance(position, a, b))
+            .fold(::std::f32::INFINITY, f32::min)
     }
 
-    pub fn to_polygon(&self, x_factor: f32, y_factor: f32) -> (Box<[i16]>, Box<[i16]>) {
-        let (mut xs, mut ys): (Box<[i16]>, Box<[i16]>) = match self {
-            Shape::Parallelogram { base, u, v } => (
-                Box::new([
-                    base.x as i16,
-                    (u.x + base.x) as i16,
-                    (u.x + v.x + base.x) as i16,
-                    (v.x + base.x) as i16,
-                ]),
-                Box::new([
-                    base.y as i16,
-                    (u.y + base.y) as i16,
-                    (u.y + v.y + base.y) as i16,
-                    (v.y + base.y) as i16,
-                ]),
-            ),
+    // The position inside the shape, from 0 to 1 on both axes: along u and v
+    // for parallelograms, and horizontally and upwards in the bounding box of
+    // other shapes.
+    pub fn normalized_position(&self, position: &Position) -> (f32, f32) {
+        let (u, v) = match self {
+            Shape::Parallelogram { base, u, v } => parallelogram_coordinates(base, u, v, position),
+            _ => {
+                let (min, max) = self.bounding_box();
+                (
+                    (position.x - min.x) as f32 / (max.x - min.x).max(1) as f32,
+                    (max.y - position.y) as f32 / (max.y - min.y).max(1) as f32,
+                )
+            }
         };
-        for x in xs.iter_mut() {
-            *x = (f32::from(*x) * x_factor) as i16;
+        (u.max(0.0).min(1.0), v.max(0.0).min(1.0))
+    }
+
+    // The smallest and largest corner of the axis-aligned bounding box.
+    pub fn bounding_box(&self) -> (Position, Position) {
+        if let Shape::Circle { center, radius } = self {
+            return (
+                Position {
+                    x: center.x - radius,
+                    y: center.y - radius,
+                },
+                Position {
+                    x: center.x + radius,
+                    y: center.y + radius,
+                },
+            );
+        }
+        let corners = self.corners();
+        let xs = corners.iter().map(|corner| corner.x);
+        let ys = corners.iter().map(|corner| corner.y);
+        (
+            Position {
+                x: xs.clone().min().unwrap_or(0),
+                y: ys.clone().min().unwrap_or(0),
+            },
+            Position {
+                x: xs.max().unwrap_or(0),
+                y: ys.max().unwrap_or(0),
+            },
+        )
+    }
+
+    fn corners(&self) -> Vec<Position> {
+        match self {
+            Shape::Parallelogram { base, u, v } => vec![
+                base.clone(),
+                Position {
+                    x: base.x + u.x,
+                    y: base.y + u.y,
+                },
+                Position {
+                    x: base.x + u.x + v.x,
+                    y: base.y + u.y + v.y,
+                },
+                Position {
+                    x: base.x + v.x,
+                    y: base.y + v.y,
+                },
+            ],
+            Shape::Hexagon { center, radius } => regular_polygon(center, *radius, 6),
+            Shape::Circle { center, radius } => regular_polygon(center, *radius, CIRCLE_CORNERS),
+            Shape::Polygon { corners } => corners.clone(),
+        }
+    }
+
+    // Corners of the shape in window coordinates, as used for rendering.
+    pub fn to_polygon(
+        &self,
+        mounting: &Mounting,
+        x_factor: f32,
+        y_factor: f32,
+    ) -> (Box<[i16]>, Box<[i16]>) {
+        let corners: Vec<Position> = self
+            .corners()
+            .iter()
+            .map(|corner| mounting.to_device(corner))
+            .collect();
+        (
+            corners
+                .iter()
+                .map(|corner| (corner.x as f32 * x_factor) as i16)
+                .collect(),
+            corners
+                .iter()
+                .map(|corner| (corner.y as f32 * y_factor) as i16)
+                .collect(),
+        )
+    }
+}
+
+// The position in multiples of u and v, relative to the base of a
+// parallelogram. Both are between 0 and 1 inside of it.
+pub fn parallelogram_coordinates(
+    base: &Position,
+    u: &Position,
+    v: &Position,
+    position: &Position,
+) -> (f32, f32) {
+    let translated_position = Position {
+        x: position.x - base.x,
+        y: position.y - base.y,
+    };
+    let multiplication_factor = 1.0 / (u.x * v.y - v.x * u.y) as f32;
+    let u_component = (translated_position.x * v.y + translated_position.y * (-v.x)) as f32
+        * multiplication_factor;
+    let v_component = (translated_position.x * (-u.y) + translated_position.y * u.x) as f32
+        * multiplication_factor;
+    (u_component, v_component)
+}
+
+// Corners of a regular polygon, starting at the top.
+fn regular_polygon(center: &Position, radius: i32, corners: usize) -> Vec<Position> {
+    (0..corners)
+        .map(|i| {
+            let angle = 2.0 * ::std::f32::consts::PI * i as f32 / corners as f32;
+            Position {
+                x: center.x + (radius as f32 * angle.sin()).round() as i32,
+                y: center.y - (radius as f32 * angle.cos()).round() as i32,
+            }
+        })
+        .collect()
+}
+
+fn on_segment(position: &Position, a: &Position, b: &Position) -> bool {
+    let cross = i64::from(b.x - a.x) * i64::from(position.y - a.y)
+        - i64::from(b.y - a.y) * i64::from(position.x - a.x);
+    cross == 0
+        && position.x >= a.x.min(b.x)
+        && position.x <= a.x.max(b.x)
+        && position.y >= a.y.min(b.y)
+        && position.y <= a.y.max(b.y)
+}
+
+// Even-odd rule, positions on the border count as inside.
+fn polygon_contains(corners: &[Position], position: &Position) -> bool {
+    let mut inside = false;
+    for (a, b) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+        if on_segment(position, a, b) {
+            return true;
         }
-        for y in ys.iter_mut() {
-            *y = (f32::from(*y) * y_factor) as i16;
+        if (a.y > position.y) != (b.y > position.y) {
+            let crossing_x = f64::from(a.x)
+                + f64::from(position.y - a.y) * f64::from(b.x - a.x) / f64::from(b.y - a.y);
+            if f64::from(position.x) < crossing_x {
+                inside = !inside;
+            }
         }
-        (xs, ys)
     }
+    inside
+}
+
+fn segment_distance(position: &Position, a: &Position, b: &Position) -> f32 {
+    let (px, py) = ((position.x - a.x) as f32, (position.y - a.y) as f32);
+    let (dx, dy) = ((b.x - a.x) as f32, (b.y - a.y) as f32);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((px * dx + py * dy) / length_squared).max(0.0).min(1.0)
+    };
+    (px - t * dx).hypot(py - t * dy)
 }
 
 #[cfg(test)]
@@ -95,6 +265,20 @@ mod test {
                 assert!(!PARALLELOGRAM.contains(&Position { x: 8, y: 2 }));
             }
 
+            #[test]
+            fn computes_coordinates_along_u_and_v() {
+                if let Shape::Parallelogram { base, u, v } = &PARALLELOGRAM {
+                    assert_eq!(
+                        parallelogram_coordinates(base, u, v, &Position { x: 10, y: 5 }),
+                        (1.0, 0.0)
+                    );
+                    assert_eq!(
+                        parallelogram_coordinates(base, u, v, &Position { x: 10, y: 10 }),
+                        (2.0 / 3.0, 2.0 / 3.0)
+                    );
+                }
+            }
+
             #[test]
             fn works_for_translated_parallelograms() {
                 let parallelogram = Shape::Parallelogram {
@@ -110,8 +294,254 @@ mod test {
         }
     }
 
+    mod distance {
+        use super::*;
+
+        const SQUARE: Shape = Shape::Parallelogram {
+            base: Position { x: 0, y: 0 },
+            u: Position { x: 10, y: 0 },
+            v: Position { x: 0, y: 10 },
+        };
+
+        #[test]
+        fn is_zero_inside() {
+            assert_eq!(SQUARE.distance(&Position { x: 5, y: 5 }), 0.0);
+        }
+
+        #[test]
+        fn measures_the_distance_to_the_nearest_edge() {
+            assert_eq!(SQUARE.distance(&Position { x: 13, y: 5 }), 3.0);
+            assert_eq!(SQUARE.distance(&Position { x: 5, y: -2 }), 2.0);
+        }
+
+        #[test]
+        fn measures_the_distance_to_the_nearest_corner() {
+            assert_eq!(SQUARE.distance(&Position { x: 13, y: 14 }), 5.0);
+        }
+    }
+
+    mod bounding_box {
+        use super::*;
+
+        #[test]
+        fn contains_all_corners() {
+            let parallelogram = Shape::Parallelogram {
+                base: Position { x: 10, y: 10 },
+                u: Position { x: 0, y: -10 },
+                v: Position { x: -6, y: -6 },
+            };
+            assert_eq!(
+                parallelogram.bounding_box(),
+                (Position { x: 4, y: -6 }, Position { x: 10, y: 10 })
+            );
+        }
+
+        #[test]
+        fn contains_whole_circles() {
+            let circle = Shape::Circle {
+                center: Position { x: 100, y: 100 },
+                radius: 10,
+            };
+            assert_eq!(
+                circle.bounding_box(),
+                (Position { x: 90, y: 90 }, Position { x: 110, y: 110 })
+            );
+        }
+    }
+
+    mod normalized_position {
+        use super::*;
+
+        const SQUARE: Shape = Shape::Parallelogram {
+            base: Position { x: 0, y: 0 },
+            u: Position { x: 10, y: 0 },
+            v: Position { x: 0, y: 10 },
+        };
+
+        #[test]
+        fn uses_the_parallelogram_coordinates() {
+            let parallelogram = Shape::Parallelogram {
+                base: Position { x: 100, y: 100 },
+                u: Position { x: 0, y: -8 },
+                v: Position { x: -16, y: 0 },
+            };
+            assert_eq!(
+                parallelogram.normalized_position(&Position { x: 96, y: 94 }),
+                (0.75, 0.25)
+            );
+        }
+
+        #[test]
+        fn uses_the_bounding_box_for_other_shapes() {
+            let circle = Shape::Circle {
+                center: Position { x: 100, y: 100 },
+                radius: 10,
+            };
+            assert_eq!(
+                circle.normalized_position(&Position { x: 105, y: 100 }),
+                (0.75, 0.5)
+            );
+            assert_eq!(
+                circle.normalized_position(&Position { x: 100, y: 92 }),
+                (0.5, 0.9)
+            );
+        }
+
+        #[test]
+        fn clamps_positions_outside() {
+            assert_eq!(
+                SQUARE.normalized_position(&Position { x: 15, y: -5 }),
+                (1.0, 0.0)
+            );
+        }
+    }
+
+    mod hexagon {
+        use super::*;
+
+        const HEXAGON: Shape = Shape::Hexagon {
+            center: Position { x: 0, y: 0 },
+            radius: 10,
+        };
+
+        #[test]
+        fn has_a_corner_at_the_top() {
+            assert_eq!(
+                HEXAGON.corners(),
+                vec![
+                    Position { x: 0, y: -10 },
+                    Position { x: 9, y: -5 },
+                    Position { x: 9, y: 5 },
+                    Position { x: 0, y: 10 },
+                    Position { x: -9, y: 5 },
+                    Position { x: -9, y: -5 },
+                ]
+            );
+        }
+
+        #[test]
+        fn detects_positions_inside() {
+            assert!(HEXAGON.contains(&Position { x: 0, y: 0 }));
+            assert!(HEXAGON.contains(&Position { x: 8, y: 0 }));
+            assert!(HEXAGON.contains(&Position { x: 0, y: -9 }));
+        }
+
+        #[test]
+        fn returns_true_for_corners_and_edges() {
+            assert!(HEXAGON.contains(&Position { x: 0, y: -10 }));
+            assert!(HEXAGON.contains(&Position { x: 9, y: 0 }));
+        }
+
+        #[test]
+        fn detects_positions_outside() {
+            assert!(!HEXAGON.contains(&Position { x: 10, y: 0 }));
+            assert!(!HEXAGON.contains(&Position { x: 8, y: -8 }));
+            assert!(!HEXAGON.contains(&Position { x: 0, y: 11 }));
+        }
+
+        #[test]
+        fn measures_the_distance_to_the_nearest_edge() {
+            assert_eq!(HEXAGON.distance(&Position { x: 12, y: 0 }), 3.0);
+        }
+    }
+
+    mod circle {
+        use super::*;
+
+        const CIRCLE: Shape = Shape::Circle {
+            center: Position { x: 100, y: 100 },
+            radius: 10,
+        };
+
+        #[test]
+        fn detects_positions_inside() {
+            assert!(CIRCLE.contains(&Position { x: 100, y: 100 }));
+            assert!(CIRCLE.contains(&Position { x: 106, y: 108 }));
+        }
+
+        #[test]
+        fn detects_positions_outside() {
+            assert!(!CIRCLE.contains(&Position { x: 108, y: 108 }));
+            assert!(!CIRCLE.contains(&Position { x: 111, y: 100 }));
+        }
+
+        #[test]
+        fn measures_the_distance_to_the_border() {
+            assert_eq!(CIRCLE.distance(&Position { x: 100, y: 125 }), 15.0);
+        }
+
+        #[test]
+        fn is_rendered_as_a_regular_polygon() {
+            let (xs, ys) = CIRCLE.to_polygon(&crate::areas::mounting::test::unrotated(), 1.0, 1.0);
+            assert_eq!(xs.len(), CIRCLE_CORNERS);
+            assert_eq!((xs[0], ys[0]), (100, 90));
+        }
+    }
+
+    mod polygon {
+        use super::*;
+
+        // An L-shape.
+        fn polygon() -> Shape {
+            Shape::Polygon {
+                corners: vec![
+                    Position { x: 0, y: 0 },
+                    Position { x: 10, y: 0 },
+                    Position { x: 10, y: 5 },
+                    Position { x: 5, y: 5 },
+                    Position { x: 5, y: 10 },
+                    Position { x: 0, y: 10 },
+                ],
+            }
+        }
+
+        #[test]
+        fn detects_positions_inside() {
+            assert!(polygon().contains(&Position { x: 2, y: 2 }));
+            assert!(polygon().contains(&Position { x: 8, y: 2 }));
+            assert!(polygon().contains(&Position { x: 2, y: 8 }));
+        }
+
+        #[test]
+        fn detects_positions_in_concave_parts() {
+            assert!(!polygon().contains(&Position { x: 8, y: 8 }));
+        }
+
+        #[test]
+        fn returns_true_for_corners_and_edges() {
+            assert!(polygon().contains(&Position { x: 5, y: 5 }));
+            assert!(polygon().contains(&Position { x: 7, y: 5 }));
+            assert!(polygon().contains(&Position { x: 0, y: 10 }));
+        }
+
+        #[test]
+        fn detects_positions_outside() {
+            assert!(!polygon().contains(&Position { x: -1, y: 5 }));
+            assert!(!polygon().contains(&Position { x: 11, y: 2 }));
+        }
+
+        #[test]
+        fn measures_the_distance_to_the_nearest_edge() {
+            assert_eq!(polygon().distance(&Position { x: 8, y: 8 }), 3.0);
+        }
+
+        #[test]
+        fn is_rendered_with_its_corners() {
+            let expected: (Box<[i16]>, Box<[i16]>) = (
+                Box::new([0, 10, 10, 5, 5, 0]),
+                Box::new([0, 0, 5, 5, 10, 10]),
+            );
+            assert_eq!(
+                polygon().to_polygon(&crate::areas::mounting::test::unrotated(), 1.0, 1.0),
+                expected
+            );
+        }
+    }
+
     mod to_polygon {
         use super::*;
+        use crate::areas::mounting::test::{mounting, unrotated};
+        use crate::areas::mounting::Rotation;
 
         mod parallelogram {
             use super::*;
@@ -126,7 +556,7 @@ mod test {
 
                 let expected: (Box<[i16]>, Box<[i16]>) =
                     (Box::new([0, 10, 15, 5]), Box::new([0, 5, 15, 10]));
-                assert_eq!(parallelogram.to_polygon(1.0, 1.0), expected);
+                assert_eq!(parallelogram.to_polygon(&unrotated(), 1.0, 1.0), expected);
             }
 
             #[test]
@@ -139,7 +569,7 @@ mod test {
 
                 let expected: (Box<[i16]>, Box<[i16]>) =
                     (Box::new([33, 43, 48, 38]), Box::new([77, 82, 92, 87]));
-                assert_eq!(parallelogram.to_polygon(1.0, 1.0), expected);
+                assert_eq!(parallelogram.to_polygon(&unrotated(), 1.0, 1.0), expected);
             }
 
             #[test]
@@ -151,7 +581,28 @@ mod test {
                 };
                 let expected: (Box<[i16]>, Box<[i16]>) =
                     (Box::new([0, 2, 2, 0]), Box::new([0, 0, 3, 3]));
-                assert_eq!(parallelogram.to_polygon(2.0, 3.0), expected);
+                assert_eq!(parallelogram.to_polygon(&unrotated(), 2.0, 3.0), expected);
+            }
+
+            #[test]
+            fn converts_instrument_coordinates_to_device_coordinates() {
+                let parallelogram = Shape::Parallelogram {
+                    base: Position { x: 0, y: 0 },
+                    u: Position { x: 10, y: 0 },
+                    v: Position { x: 0, y: 20 },
+                };
+                let expected: (Box<[i16]>, Box<[i16]>) = (
+                    Box::new([400, 390, 390, 400]),
+                    Box::new([300, 300, 280, 280]),
+                );
+                assert_eq!(
+                    parallelogram.to_polygon(
+                        &mounting(Rotation::Degrees180, false, false),
+                        1.0,
+                        1.0
+                    ),
+                    expected
+                );
             }
         }
 
@@ -176,7 +627,7 @@ mod test {
                 .get(1)
                 .unwrap()
                 .shape
-                .to_polygon(700.0 / 1000.0, 500.0 / 1000.0);
+                .to_polygon(&unrotated(), 700.0 / 1000.0, 500.0 / 1000.0);
                 let expected: (Box<[i16]>, Box<[i16]>) = (
                     Box::new([700, 700, 693, 693]),
                     Box::new([500, 495, 495, 500]),
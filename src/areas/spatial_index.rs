@@ -0,0 +1,121 @@
+use crate::evdev::Position;
+
+// Upper bound for the number of cells in each direction.
+const MAX_CELLS: i32 = 256;
+
+// A uniform grid of cells over the touch area. Every cell holds the indices
+// of the areas whose bounding boxes overlap it, in their original order, so
+// that looking up the first area containing a position only has to check the
+// areas of one cell.
+#[derive(Clone, Debug)]
+pub struct SpatialIndex {
+    cell_width: i32,
+    cell_height: i32,
+    columns: i32,
+    rows: i32,
+    cells: Vec<Vec<usize>>,
+}
+
+impl SpatialIndex {
+    pub fn new(
+        bounding_boxes: &[(Position, Position)],
+        touch_width: i32,
+        touch_height: i32,
+    ) -> SpatialIndex {
+        let (touch_width, touch_height) = (touch_width.max(1), touch_height.max(1));
+        // about one area per cell
+        let cell_size = (f64::from(touch_width) * f64::from(touch_height)
+            / bounding_boxes.len().max(1) as f64)
+            .sqrt()
+            .max(1.0) as i32;
+        let columns = (touch_width / cell_size).max(1).min(MAX_CELLS);
+        let rows = (touch_height / cell_size).max(1).min(MAX_CELLS);
+        let mut index = SpatialIndex {
+            // rounded up, so that the cells cover the whole touch area
+            cell_width: (touch_width + columns - 1) / columns,
+            cell_height: (touch_height + rows - 1) / rows,
+            columns,
+            rows,
+            cells: vec![vec![]; (columns * rows) as usize],
+        };
+        for (area_index, (min, max)) in bounding_boxes.iter().enumerate() {
+            let first_column = (min.x.div_euclid(index.cell_width)).max(0);
+            let last_column = (max.x.div_euclid(index.cell_width)).min(columns - 1);
+            let first_row = (min.y.div_euclid(index.cell_height)).max(0);
+            let last_row = (max.y.div_euclid(index.cell_height)).min(rows - 1);
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    index.cells[(row * columns + column) as usize].push(area_index);
+                }
+            }
+        }
+        index
+    }
+
+    // The indices of the areas that may contain the position, or None for
+    // positions outside of the touch area.
+    pub fn candidates(&self, position: &Position) -> Option<&[usize]> {
+        let column = position.x.div_euclid(self.cell_width);
+        let row = position.y.div_euclid(self.cell_height);
+        if (0..self.columns).contains(&column) && (0..self.rows).contains(&row) {
+            Some(&self.cells[(row * self.columns + column) as usize])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bounding_box(min_x: i32, min_y: i32, max_x: i32, max_y: i32) -> (Position, Position) {
+        (
+            Position { x: min_x, y: min_y },
+            Position { x: max_x, y: max_y },
+        )
+    }
+
+    #[test]
+    fn returns_the_areas_overlapping_the_cell() {
+        // a 4 by 4 grid of squares
+        let bounding_boxes: Vec<(Position, Position)> = (0..16)
+            .map(|i| {
+                let (x, y) = (i % 4 * 50, i / 4 * 50);
+                bounding_box(x, y, x + 50, y + 50)
+            })
+            .collect();
+        let index = SpatialIndex::new(&bounding_boxes, 200, 200);
+        assert_eq!(index.candidates(&Position { x: 10, y: 10 }), Some(&[0][..]));
+        assert_eq!(
+            index.candidates(&Position { x: 160, y: 160 }),
+            Some(&[10, 11, 14, 15][..])
+        );
+    }
+
+    #[test]
+    fn keeps_the_order_of_overlapping_areas() {
+        let index = SpatialIndex::new(
+            &[bounding_box(0, 0, 200, 200), bounding_box(50, 50, 60, 60)],
+            200,
+            200,
+        );
+        assert_eq!(
+            index.candidates(&Position { x: 55, y: 55 }),
+            Some(&[0, 1][..])
+        );
+    }
+
+    #[test]
+    fn includes_areas_reaching_beyond_the_touch_area() {
+        let index = SpatialIndex::new(&[bounding_box(-50, -50, 10, 10)], 200, 200);
+        assert_eq!(index.candidates(&Position { x: 0, y: 0 }), Some(&[0][..]));
+    }
+
+    #[test]
+    fn returns_none_outside_of_the_touch_area() {
+        let index = SpatialIndex::new(&[bounding_box(0, 0, 200, 200)], 200, 200);
+        assert_eq!(index.candidates(&Position { x: -1, y: 0 }), None);
+        assert_eq!(index.candidates(&Position { x: 0, y: 200 }), None);
+    }
+}
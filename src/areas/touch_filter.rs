@@ -0,0 +1,471 @@
+use crate::areas::Areas;
+use crate::cli;
+use crate::evdev::{Position, TouchState};
+use ::std::sync::mpsc::{Receiver, RecvTimeoutError};
+use ::std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchFilterConfig {
+    // How far a touch has to move past the border of its area before it
+    // switches to another area, in touch coordinates.
+    pub hysteresis: i32,
+    // Weight of the previous position when smoothing, from 0 (no smoothing)
+    // to just below 1.
+    pub smoothing: f32,
+    // Touches that are released before this duration don't trigger notes.
+    pub min_touch_duration: Duration,
+}
+
+impl TouchFilterConfig {
+    pub fn new(cli_args: &cli::Args) -> TouchFilterConfig {
+        TouchFilterConfig {
+            hysteresis: cli_args.hysteresis,
+            smoothing: cli_args.smoothing,
+            min_touch_duration: cli_args.min_touch_duration,
+        }
+    }
+}
+
+struct FilteredTouch {
+    tracking_id: i32,
+    started: Instant,
+    active: bool,
+    // The last update of a touch that is still held back by the minimum
+    // touch duration.
+    held_back: Option<TouchState>,
+    smoothed: (f32, f32),
+    // The area the touch is currently in, together with the last position
+    // inside of it.
+    area: Option<(usize, Position)>,
+}
+
+pub struct TouchFilter {
+    areas: Areas,
+    config: TouchFilterConfig,
+    // A channel (instead of an iterator), so that touches that are held back
+    // can be passed on when their minimum duration is over, even if no
+    // further updates arrive for them.
+    touches: Receiver<TouchState>,
+    state: Vec<FilteredTouch>,
+}
+
+impl TouchFilter {
+    pub fn new(
+        areas: Areas,
+        config: TouchFilterConfig,
+        touches: Receiver<TouchState>,
+    ) -> TouchFilter {
+        TouchFilter {
+            areas,
+            config,
+            touches,
+            state: vec![],
+        }
+    }
+
+    fn filter_touch(&mut self, now: Instant, touch_state: TouchState) -> Option<TouchState> {
+        match touch_state {
+            TouchState::NoTouch { tracking_id } => {
+                let index = self
+                    .state
+                    .iter()
+                    .position(|touch| touch.tracking_id == tracking_id)?;
+                if self.state.remove(index).active {
+                    Some(touch_state)
+                } else {
+                    None
+                }
+            }
+            TouchState::Touch {
+                tracking_id,
+                position,
+                contact,
+            } => {
+                let index = match self
+                    .state
+                    .iter()
+                    .position(|touch| touch.tracking_id == tracking_id)
+                {
+                    Some(index) => index,
+                    None => {
+                        self.state.push(FilteredTouch {
+                            tracking_id,
+                            started: now,
+                            active: false,
+                            held_back: None,
+                            smoothed: (position.x as f32, position.y as f32),
+                            area: None,
+                        });
+                        self.state.len() - 1
+                    }
+                };
+                let position = self.smooth(index, &position);
+                let position = self.apply_hysteresis(index, position);
+                let touch = &mut self.state[index];
+                let touch_state = TouchState::Touch {
+                    tracking_id,
+                    position,
+                    contact,
+                };
+                if !touch.active
+                    && now.duration_since(touch.started) < self.config.min_touch_duration
+                {
+                    touch.held_back = Some(touch_state);
+                    return None;
+                }
+                touch.active = true;
+                touch.held_back = None;
+                Some(touch_state)
+            }
+        }
+    }
+
+    // Passes on a touch whose minimum duration is over, with its last
+    // update.
+    fn release_held_back(&mut self, now: Instant) -> Option<TouchState> {
+        let min_touch_duration = self.config.min_touch_duration;
+        let touch = self.state.iter_mut().find(|touch| {
+            !touch.active && now.duration_since(touch.started) >= min_touch_duration
+        })?;
+        touch.active = true;
+        touch.held_back.take()
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.state
+            .iter()
+            .filter(|touch| !touch.active)
+            .map(|touch| touch.started + self.config.min_touch_duration)
+            .min()
+    }
+
+    fn smooth(&mut self, index: usize, position: &Position) -> Position {
+        let smoothing = self.config.smoothing;
+        let smoothed = &mut self.state[index].smoothed;
+        smoothed.0 = smoothed.0 * smoothing + position.x as f32 * (1.0 - smoothing);
+        smoothed.1 = smoothed.1 * smoothing + position.y as f32 * (1.0 - smoothing);
+        Position {
+            x: smoothed.0.round() as i32,
+            y: smoothed.1.round() as i32,
+        }
+    }
+
+    // Touches close to the border of their current area are held at the last
+    // position inside of it.
+    fn apply_hysteresis(&mut self, index: usize, position: Position) -> Position {
+        let touch = &mut self.state[index];
+        if let Some((area_index, last_position)) = touch.area.clone() {
+            let distance = self.areas.distance(area_index, &position);
+            if distance == 0.0 {
+                touch.area = Some((area_index, position.clone()));
+                return position;
+            } else if distance <= self.config.hysteresis as f32 {
+                return last_position;
+            }
+        }
+        touch.area = self
+            .areas
+            .area_index(&position)
+            .map(|area_index| (area_index, position.clone()));
+        position
+    }
+}
+
+impl Iterator for TouchFilter {
+    type Item = TouchState;
+
+    // Touches that are still held back by the minimum touch duration are
+    // passed on as soon as that duration is over.
+    fn next(&mut self) -> Option<TouchState> {
+        loop {
+            if let Some(touch_state) = self.release_held_back(Instant::now()) {
+                return Some(touch_state);
+            }
+            let touch_state = match self.next_deadline() {
+                None => self.touches.recv().ok()?,
+                Some(deadline) => match self
+                    .touches
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                {
+                    Ok(touch_state) => touch_state,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                },
+            };
+            if let Some(touch_state) = self.filter_touch(Instant::now(), touch_state) {
+                return Some(touch_state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::areas::shape::Shape;
+    use crate::areas::Area;
+    use crate::evdev::Contact;
+    use ::std::sync::mpsc::channel;
+
+    // Two areas next to each other, the left one from x = 0 to 100,
+    // the right one from x = 100 to 200.
+    fn areas() -> Areas {
+        let square = |x| {
+            Area::new(
+                Shape::Parallelogram {
+                    base: Position { x, y: 0 },
+                    u: Position { x: 100, y: 0 },
+                    v: Position { x: 0, y: 100 },
+                },
+                60,
+            )
+        };
+        Areas::from_areas(vec![square(0), square(100)], 200, 100)
+    }
+
+    fn config() -> TouchFilterConfig {
+        TouchFilterConfig {
+            hysteresis: 0,
+            smoothing: 0.0,
+            min_touch_duration: Duration::from_millis(0),
+        }
+    }
+
+    fn touch(tracking_id: i32, x: i32, y: i32) -> TouchState {
+        TouchState::Touch {
+            tracking_id,
+            position: Position { x, y },
+            contact: Contact::default(),
+        }
+    }
+
+    fn filter(config: TouchFilterConfig, touches: Vec<TouchState>) -> Vec<TouchState> {
+        let (sender, receiver) = channel();
+        for touch_state in touches {
+            sender.send(touch_state).unwrap();
+        }
+        drop(sender);
+        TouchFilter::new(areas(), config, receiver).collect()
+    }
+
+    #[test]
+    fn passes_touches_through_by_default() {
+        let touches = vec![
+            touch(0, 10, 20),
+            touch(0, 110, 20),
+            TouchState::NoTouch { tracking_id: 0 },
+        ];
+        assert_eq!(filter(config(), touches.clone()), touches);
+    }
+
+    mod hysteresis {
+        use super::*;
+
+        fn hysteresis_config() -> TouchFilterConfig {
+            TouchFilterConfig {
+                hysteresis: 10,
+                ..config()
+            }
+        }
+
+        #[test]
+        fn holds_touches_slightly_past_the_border() {
+            assert_eq!(
+                filter(
+                    hysteresis_config(),
+                    vec![touch(0, 95, 50), touch(0, 105, 50)]
+                ),
+                vec![touch(0, 95, 50), touch(0, 95, 50)]
+            );
+        }
+
+        #[test]
+        fn switches_areas_when_moving_far_enough() {
+            assert_eq!(
+                filter(
+                    hysteresis_config(),
+                    vec![touch(0, 95, 50), touch(0, 105, 50), touch(0, 115, 50)]
+                ),
+                vec![touch(0, 95, 50), touch(0, 95, 50), touch(0, 115, 50)]
+            );
+        }
+
+        #[test]
+        fn keeps_the_new_area_after_switching() {
+            assert_eq!(
+                filter(
+                    hysteresis_config(),
+                    vec![touch(0, 95, 50), touch(0, 115, 50), touch(0, 95, 50)]
+                ),
+                vec![touch(0, 95, 50), touch(0, 115, 50), touch(0, 115, 50)]
+            );
+        }
+
+        #[test]
+        fn tracks_touches_independently() {
+            assert_eq!(
+                filter(
+                    hysteresis_config(),
+                    vec![touch(0, 95, 50), touch(1, 105, 50)]
+                ),
+                vec![touch(0, 95, 50), touch(1, 105, 50)]
+            );
+        }
+    }
+
+    mod smoothing {
+        use super::*;
+
+        #[test]
+        fn averages_positions() {
+            let config = TouchFilterConfig {
+                smoothing: 0.5,
+                ..config()
+            };
+            assert_eq!(
+                filter(config, vec![touch(0, 10, 10), touch(0, 30, 50)]),
+                vec![touch(0, 10, 10), touch(0, 20, 30)]
+            );
+        }
+
+        #[test]
+        fn starts_new_touches_at_their_position() {
+            let config = TouchFilterConfig {
+                smoothing: 0.5,
+                ..config()
+            };
+            assert_eq!(
+                filter(
+                    config,
+                    vec![
+                        touch(0, 10, 10),
+                        TouchState::NoTouch { tracking_id: 0 },
+                        touch(1, 30, 50)
+                    ]
+                ),
+                vec![
+                    touch(0, 10, 10),
+                    TouchState::NoTouch { tracking_id: 0 },
+                    touch(1, 30, 50)
+                ]
+            );
+        }
+    }
+
+    mod min_touch_duration {
+        use super::*;
+
+        fn debounce_config() -> TouchFilterConfig {
+            TouchFilterConfig {
+                min_touch_duration: Duration::from_millis(50),
+                ..config()
+            }
+        }
+
+        fn debounce_filter() -> TouchFilter {
+            TouchFilter::new(areas(), debounce_config(), channel().1)
+        }
+
+        #[test]
+        fn holds_back_new_touches() {
+            let mut filter = debounce_filter();
+            assert_eq!(filter.filter_touch(Instant::now(), touch(0, 10, 10)), None);
+        }
+
+        #[test]
+        fn passes_touches_on_after_the_minimum_duration() {
+            let mut filter = debounce_filter();
+            let start = Instant::now();
+            filter.filter_touch(start, touch(0, 10, 10));
+            assert_eq!(
+                filter.filter_touch(start + Duration::from_millis(50), touch(0, 12, 10)),
+                Some(touch(0, 12, 10))
+            );
+        }
+
+        #[test]
+        fn passes_held_back_touches_on_after_the_minimum_duration() {
+            let mut filter = debounce_filter();
+            let start = Instant::now();
+            filter.filter_touch(start, touch(0, 10, 10));
+            filter.filter_touch(start + Duration::from_millis(10), touch(0, 12, 10));
+            assert_eq!(
+                filter.release_held_back(start + Duration::from_millis(40)),
+                None
+            );
+            assert_eq!(
+                filter.release_held_back(start + Duration::from_millis(50)),
+                Some(touch(0, 12, 10))
+            );
+            assert_eq!(
+                filter.release_held_back(start + Duration::from_millis(60)),
+                None
+            );
+        }
+
+        #[test]
+        fn waits_for_the_earliest_held_back_touch() {
+            let mut filter = debounce_filter();
+            let start = Instant::now();
+            assert_eq!(filter.next_deadline(), None);
+            filter.filter_touch(start, touch(0, 10, 10));
+            filter.filter_touch(start + Duration::from_millis(20), touch(1, 110, 10));
+            assert_eq!(
+                filter.next_deadline(),
+                Some(start + Duration::from_millis(50))
+            );
+            filter.release_held_back(start + Duration::from_millis(50));
+            assert_eq!(
+                filter.next_deadline(),
+                Some(start + Duration::from_millis(70))
+            );
+            filter.release_held_back(start + Duration::from_millis(70));
+            assert_eq!(filter.next_deadline(), None);
+        }
+
+        #[test]
+        fn passes_on_touches_that_stay_still_and_their_release() {
+            let (sender, receiver) = channel();
+            let mut filter = TouchFilter::new(areas(), debounce_config(), receiver);
+            let start = Instant::now();
+            // the finger doesn't move after its first event, so there are no
+            // further updates (and the sender stays open)
+            sender.send(touch(0, 10, 10)).unwrap();
+            assert_eq!(filter.next(), Some(touch(0, 10, 10)));
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            sender.send(TouchState::NoTouch { tracking_id: 0 }).unwrap();
+            assert_eq!(filter.next(), Some(TouchState::NoTouch { tracking_id: 0 }));
+            drop(sender);
+            assert_eq!(filter.next(), None);
+        }
+
+        #[test]
+        fn drops_short_touches_entirely() {
+            let mut filter = debounce_filter();
+            let start = Instant::now();
+            filter.filter_touch(start, touch(0, 10, 10));
+            assert_eq!(
+                filter.filter_touch(
+                    start + Duration::from_millis(10),
+                    TouchState::NoTouch { tracking_id: 0 }
+                ),
+                None
+            );
+        }
+
+        #[test]
+        fn releases_touches_that_were_passed_on() {
+            let mut filter = debounce_filter();
+            let start = Instant::now();
+            filter.filter_touch(start, touch(0, 10, 10));
+            filter.filter_touch(start + Duration::from_millis(60), touch(0, 10, 10));
+            assert_eq!(
+                filter.filter_touch(
+                    start + Duration::from_millis(70),
+                    TouchState::NoTouch { tracking_id: 0 }
+                ),
+                Some(TouchState::NoTouch { tracking_id: 0 })
+            );
+        }
+    }
+}
@@ -0,0 +1,531 @@
+pub mod ui;
+
+use crate::areas::mounting::Mounting;
+use crate::cli;
+use crate::evdev::{DeviceInfo, Position, TouchState};
+use crate::utils::shutdown::Shutdown;
+use crate::AddMessage;
+use crate::ErrorString;
+use std::fs;
+use std::sync::mpsc::channel;
+
+const DEGENERATE_POINTS: &str =
+    "calibration points are degenerate, please touch the targets precisely";
+
+// Taps whose spread across their main direction is less than about 1% of the
+// spread along it are rejected, since the fitted transform would mostly
+// amplify the inaccuracy of the taps.
+const MIN_SPREAD_PRODUCT: f64 = 1e-4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    x: [f64; 3],
+    y: [f64; 3],
+}
+
+impl Calibration {
+    #[cfg(test)]
+    fn identity() -> Calibration {
+        Calibration {
+            x: [1.0, 0.0, 0.0],
+            y: [0.0, 1.0, 0.0],
+        }
+    }
+
+    fn apply(&self, position: &Position) -> Position {
+        let (x, y) = (f64::from(position.x), f64::from(position.y));
+        Position {
+            x: (self.x[0] * x + self.x[1] * y + self.x[2]).round() as i32,
+            y: (self.y[0] * x + self.y[1] * y + self.y[2]).round() as i32,
+        }
+    }
+
+    pub fn calibrate(&self, touch_state: TouchState) -> TouchState {
+        match touch_state {
+            TouchState::Touch {
+                tracking_id,
+                position,
+                contact,
+            } => TouchState::Touch {
+                tracking_id,
+                position: self.apply(&position),
+                contact,
+            },
+            TouchState::NoTouch { .. } => touch_state,
+        }
+    }
+
+    fn from_points(points: &[(Position, Position)]) -> Result<Calibration, ErrorString> {
+        // The measured positions are centered and scaled to unit spread before
+        // fitting, so that the degeneracy check below doesn't depend on the
+        // resolution of the touch screen.
+        let n = points.len() as f64;
+        let mean = |coordinate: fn(&Position) -> i32| {
+            points
+                .iter()
+                .map(|(measured, _)| f64::from(coordinate(measured)))
+                .sum::<f64>()
+                / n
+        };
+        let (mean_x, mean_y) = (mean(|position| position.x), mean(|position| position.y));
+        let spread = (points
+            .iter()
+            .map(|(measured, _)| {
+                (f64::from(measured.x) - mean_x).powi(2) + (f64::from(measured.y) - mean_y).powi(2)
+            })
+            .sum::<f64>()
+            / n)
+            .sqrt();
+        if spread == 0.0 {
+            return Err(ErrorString::from(DEGENERATE_POINTS));
+        }
+        let mut matrix = [[0.0; 3]; 3];
+        let mut x_sums = [0.0; 3];
+        let mut y_sums = [0.0; 3];
+        for (measured, target) in points {
+            let row = [
+                (f64::from(measured.x) - mean_x) / spread,
+                (f64::from(measured.y) - mean_y) / spread,
+                1.0,
+            ];
+            for (i, matrix_row) in matrix.iter_mut().enumerate() {
+                for (j, cell) in matrix_row.iter_mut().enumerate() {
+                    *cell += row[i] * row[j];
+                }
+                x_sums[i] += row[i] * f64::from(target.x);
+                y_sums[i] += row[i] * f64::from(target.y);
+            }
+        }
+        // For normalized points this is the product of the variances along the
+        // two principal axes, which is close to zero for nearly collinear
+        // points.
+        if determinant(matrix) / n.powi(3) < MIN_SPREAD_PRODUCT {
+            return Err(ErrorString::from(DEGENERATE_POINTS));
+        }
+        let denormalize = |[a, b, c]: [f64; 3]| {
+            [
+                a / spread,
+                b / spread,
+                c - (a * mean_x + b * mean_y) / spread,
+            ]
+        };
+        Ok(Calibration {
+            x: denormalize(solve(matrix, x_sums)),
+            y: denormalize(solve(matrix, y_sums)),
+        })
+    }
+
+    pub fn load(file: &str) -> Result<Calibration, ErrorString> {
+        let contents =
+            fs::read_to_string(file).add_message(format!("can't read calibration {}", file))?;
+        Calibration::parse(&contents).add_message(format!("invalid calibration {}", file))
+    }
+
+    fn parse(contents: &str) -> Result<Calibration, ErrorString> {
+        let mut rows = vec![];
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut row = [0.0; 3];
+            let cells: Vec<&str> = line.split_whitespace().collect();
+            if cells.len() != row.len() {
+                return Err(ErrorString(format!("expected three numbers: {}", line)));
+            }
+            for (cell, string) in row.iter_mut().zip(cells) {
+                *cell = string.parse()?;
+            }
+            rows.push(row);
+        }
+        match rows.as_slice() {
+            [x, y] => Ok(Calibration { x: *x, y: *y }),
+            _ => Err(ErrorString(format!(
+                "expected two rows, got {}",
+                rows.len()
+            ))),
+        }
+    }
+
+    fn serialize(&self) -> String {
+        format!(
+            "# touch calibration: x' = a * x + b * y + c\n{} {} {}\n{} {} {}\n",
+            self.x[0], self.x[1], self.x[2], self.y[0], self.y[1], self.y[2]
+        )
+    }
+
+    fn save(&self, file: &str) -> Result<(), ErrorString> {
+        fs::write(file, self.serialize()).add_message(format!("can't write calibration {}", file))
+    }
+}
+
+fn determinant(m: [[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+fn solve(matrix: [[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    let determinant_of_matrix = determinant(matrix);
+    let mut result = [0.0; 3];
+    for (column, cell) in result.iter_mut().enumerate() {
+        let mut replaced = matrix;
+        for (row, value) in vector.iter().enumerate() {
+            replaced[row][column] = *value;
+        }
+        *cell = determinant(replaced) / determinant_of_matrix;
+    }
+    result
+}
+
+// The targets are spread over the instrument as the player sees it, and
+// converted to device coordinates, in which the taps are measured and the
+// calibration ui draws.
+fn targets(mounting: &Mounting) -> Vec<Position> {
+    let (width, height) = (mounting.width(), mounting.height());
+    vec![(1, 1), (9, 1), (9, 9), (1, 9), (5, 5)]
+        .into_iter()
+        .map(|(x, y)| {
+            mounting.to_device(&Position {
+                x: width * x / 10,
+                y: height * y / 10,
+            })
+        })
+        .collect()
+}
+
+fn next_tap(touches: &mut impl Iterator<Item = TouchState>) -> Result<Position, ErrorString> {
+    let mut current = None;
+    let mut positions: Vec<Position> = vec![];
+    for touch_state in touches {
+        match touch_state {
+            TouchState::Touch {
+                tracking_id,
+                position,
+                ..
+            } => {
+                if current.is_none() || current == Some(tracking_id) {
+                    current = Some(tracking_id);
+                    positions.push(position);
+                }
+            }
+            TouchState::NoTouch { tracking_id } => {
+                if current == Some(tracking_id) {
+                    // summed as i64, so that long taps can't overflow
+                    let n = positions.len() as i64;
+                    let mean = |coordinate: fn(&Position) -> i32| {
+                        (positions
+                            .iter()
+                            .map(|position| i64::from(coordinate(position)))
+                            .sum::<i64>()
+                            / n) as i32
+                    };
+                    return Ok(Position {
+                        x: mean(|position| position.x),
+                        y: mean(|position| position.y),
+                    });
+                }
+            }
+        }
+    }
+    Err(ErrorString::from("calibration: touch input ended"))
+}
+
+pub fn run(
+    cli_args: &cli::Args,
+    device_info: &DeviceInfo,
+    mut touches: impl Iterator<Item = TouchState>,
+    shutdown: Shutdown,
+) -> Result<(), ErrorString> {
+    let file = cli_args
+        .calibration_file
+        .as_ref()
+        .ok_or("--calibrate needs a file to write to, please use --calibration")?;
+    let (sender, receiver) = channel();
+    ui::spawn(cli_args, device_info, receiver, shutdown.clone());
+    let mut points = vec![];
+    for target in targets(&Mounting::new(cli_args, device_info)) {
+        sender
+            .send(target.clone())
+            .map_err(|_| "calibration: ui thread stopped")?;
+        let measured = match next_tap(&mut touches) {
+            // The touch input stops when the window gets closed.
+            Err(_) if shutdown.is_requested() => {
+                eprintln!("calibration aborted");
+                return Ok(());
+            }
+            result => result?,
+        };
+        points.push((measured, target));
+    }
+    Calibration::from_points(&points)?.save(file)?;
+    eprintln!("calibration written to {}", file);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evdev::Contact;
+
+    fn assert_close(a: &Calibration, b: &Calibration) {
+        let a_values = a.x.iter().chain(a.y.iter());
+        let b_values = b.x.iter().chain(b.y.iter());
+        for (x, y) in a_values.zip(b_values) {
+            assert!((x - y).abs() < 0.0001, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn points(f: impl Fn(&Position) -> Position) -> Vec<(Position, Position)> {
+        vec![
+            Position { x: 100, y: 100 },
+            Position { x: 900, y: 100 },
+            Position { x: 900, y: 700 },
+            Position { x: 100, y: 700 },
+            Position { x: 500, y: 400 },
+        ]
+        .into_iter()
+        .map(|position| (position.clone(), f(&position)))
+        .collect()
+    }
+
+    mod from_points {
+        use super::*;
+
+        #[test]
+        fn returns_the_identity_for_exact_touches() {
+            assert_close(
+                &Calibration::from_points(&points(|position| position.clone())).unwrap(),
+                &Calibration::identity(),
+            );
+        }
+
+        #[test]
+        fn compensates_for_offsets() {
+            let calibration = Calibration::from_points(&points(|position| Position {
+                x: position.x + 30,
+                y: position.y - 20,
+            }))
+            .unwrap();
+            assert_eq!(
+                calibration.apply(&Position { x: 300, y: 300 }),
+                Position { x: 330, y: 280 }
+            );
+        }
+
+        #[test]
+        fn compensates_for_scaling() {
+            let calibration = Calibration::from_points(&points(|position| Position {
+                x: position.x * 2,
+                y: position.y / 2,
+            }))
+            .unwrap();
+            assert_eq!(
+                calibration.apply(&Position { x: 300, y: 300 }),
+                Position { x: 600, y: 150 }
+            );
+        }
+
+        #[test]
+        fn compensates_for_skewed_touch_grids() {
+            let calibration = Calibration::from_points(&points(|position| Position {
+                x: position.x + position.y / 10,
+                y: position.y,
+            }))
+            .unwrap();
+            assert_eq!(
+                calibration.apply(&Position { x: 300, y: 500 }),
+                Position { x: 350, y: 500 }
+            );
+        }
+
+        #[test]
+        fn rejects_collinear_points() {
+            let points: Vec<(Position, Position)> = (0..4)
+                .map(|i| (Position { x: i, y: i }, Position { x: i, y: i }))
+                .collect();
+            assert!(Calibration::from_points(&points).is_err());
+        }
+
+        #[test]
+        fn rejects_nearly_collinear_points() {
+            let points: Vec<(Position, Position)> =
+                vec![(0, 0), (1000, 1001), (2000, 2000), (3000, 3001)]
+                    .into_iter()
+                    .map(|(x, y)| (Position { x, y }, Position { x, y }))
+                    .collect();
+            assert!(Calibration::from_points(&points).is_err());
+        }
+
+        #[test]
+        fn rejects_points_at_the_same_position() {
+            let points: Vec<(Position, Position)> = (0..4)
+                .map(|_| (Position { x: 5, y: 5 }, Position { x: 5, y: 5 }))
+                .collect();
+            assert!(Calibration::from_points(&points).is_err());
+        }
+    }
+
+    mod calibrate {
+        use super::*;
+
+        #[test]
+        fn transforms_touch_positions() {
+            let calibration = Calibration {
+                x: [1.0, 0.0, 10.0],
+                y: [0.0, 1.0, 20.0],
+            };
+            assert_eq!(
+                calibration.calibrate(TouchState::Touch {
+                    tracking_id: 3,
+                    position: Position { x: 1, y: 2 },
+                    contact: Contact::default(),
+                }),
+                TouchState::Touch {
+                    tracking_id: 3,
+                    position: Position { x: 11, y: 22 },
+                    contact: Contact::default(),
+                }
+            );
+        }
+
+        #[test]
+        fn leaves_no_touch_states_alone() {
+            let calibration = Calibration {
+                x: [1.0, 0.0, 10.0],
+                y: [0.0, 1.0, 20.0],
+            };
+            assert_eq!(
+                calibration.calibrate(TouchState::NoTouch { tracking_id: 3 }),
+                TouchState::NoTouch { tracking_id: 3 }
+            );
+        }
+    }
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn parses_serialized_calibrations() {
+            let calibration = Calibration {
+                x: [1.5, -0.25, 10.0],
+                y: [0.125, 0.75, -20.5],
+            };
+            assert_eq!(
+                Calibration::parse(&calibration.serialize()).unwrap(),
+                calibration
+            );
+        }
+
+        #[test]
+        fn rejects_incomplete_calibrations() {
+            assert!(Calibration::parse("1 0 0\n").is_err());
+            assert!(Calibration::parse("1 0 0\n0 1\n").is_err());
+        }
+
+        #[test]
+        fn rejects_invalid_numbers() {
+            assert!(Calibration::parse("1 0 0\n0 1 foo\n").is_err());
+        }
+    }
+
+    mod targets {
+        use super::*;
+        use crate::areas::mounting::test::{mounting, unrotated};
+        use crate::areas::mounting::Rotation;
+
+        #[test]
+        fn starts_in_the_upper_left_corner() {
+            assert_eq!(targets(&unrotated())[0], Position { x: 40, y: 30 });
+        }
+
+        #[test]
+        fn follows_the_rotation_of_the_mounted_screen() {
+            assert_eq!(
+                targets(&mounting(Rotation::Degrees180, false, false))[0],
+                Position { x: 360, y: 270 }
+            );
+            assert_eq!(
+                targets(&mounting(Rotation::Degrees90, false, false))[0],
+                Position { x: 40, y: 270 }
+            );
+        }
+
+        #[test]
+        fn follows_mirroring() {
+            assert_eq!(
+                targets(&mounting(Rotation::Degrees0, true, false))[0],
+                Position { x: 360, y: 30 }
+            );
+        }
+    }
+
+    mod next_tap {
+        use super::*;
+
+        #[test]
+        fn averages_long_taps_without_overflowing() {
+            let mut touches = (0..200_000)
+                .map(|_| TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 16383, y: 9570 },
+                    contact: Contact::default(),
+                })
+                .chain(vec![TouchState::NoTouch { tracking_id: 0 }]);
+            assert_eq!(
+                next_tap(&mut touches).unwrap(),
+                Position { x: 16383, y: 9570 }
+            );
+        }
+
+        #[test]
+        fn averages_the_positions_of_a_tap() {
+            let mut touches = vec![
+                TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 10, y: 20 },
+                    contact: Contact::default(),
+                },
+                TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 20, y: 40 },
+                    contact: Contact::default(),
+                },
+                TouchState::NoTouch { tracking_id: 0 },
+            ]
+            .into_iter();
+            assert_eq!(next_tap(&mut touches).unwrap(), Position { x: 15, y: 30 });
+        }
+
+        #[test]
+        fn ignores_other_fingers() {
+            let mut touches = vec![
+                TouchState::Touch {
+                    tracking_id: 0,
+                    position: Position { x: 10, y: 20 },
+                    contact: Contact::default(),
+                },
+                TouchState::Touch {
+                    tracking_id: 1,
+                    position: Position { x: 1000, y: 1000 },
+                    contact: Contact::default(),
+                },
+                TouchState::NoTouch { tracking_id: 1 },
+                TouchState::NoTouch { tracking_id: 0 },
+            ]
+            .into_iter();
+            assert_eq!(next_tap(&mut touches).unwrap(), Position { x: 10, y: 20 });
+        }
+
+        #[test]
+        fn fails_when_the_touch_input_ends() {
+            let mut touches = vec![TouchState::Touch {
+                tracking_id: 0,
+                position: Position { x: 10, y: 20 },
+                contact: Contact::default(),
+            }]
+            .into_iter();
+            assert!(next_tap(&mut touches).is_err());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::areas::render::open_window;
+use crate::cli;
+use crate::evdev::{DeviceInfo, Position};
+use crate::utils::shutdown::Shutdown;
+use crate::ErrorString;
+use ::sdl2::event::Event;
+use ::sdl2::gfx::primitives::DrawRenderer;
+use ::sdl2::keyboard::Keycode;
+use ::sdl2::pixels::Color;
+use ::sdl2::render::Canvas;
+use ::sdl2::video::Window;
+use ::std::sync::mpsc::{Receiver, TryRecvError};
+
+const CROSSHAIR_SIZE: i16 = 30;
+
+pub fn spawn(
+    cli_args: &cli::Args,
+    device_info: &DeviceInfo,
+    targets: Receiver<Position>,
+    shutdown: Shutdown,
+) {
+    let cli_args = cli_args.clone();
+    let device_info = device_info.clone();
+    ::std::thread::spawn(move || match run(&cli_args, &device_info, targets) {
+        // The window was closed or the calibration is done.
+        Ok(()) => shutdown.request(),
+        Err(e) => eprintln!("error in calibration ui thread: {:?}", e),
+    });
+}
+
+fn run(
+    cli_args: &cli::Args,
+    device_info: &DeviceInfo,
+    targets: Receiver<Position>,
+) -> Result<(), ErrorString> {
+    let (mut canvas, mut event_pump) = open_window(cli_args)?;
+    let mut target = None;
+    'main: loop {
+        match targets.try_recv() {
+            Ok(new_target) => {
+                target = Some(new_target);
+                draw(&mut canvas, device_info, &target)?;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => break 'main,
+        }
+        match event_pump.wait_event_timeout(50) {
+            Some(Event::Quit { .. })
+            | Some(Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            }) => break 'main,
+            Some(Event::Window { .. }) => draw(&mut canvas, device_info, &target)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn draw(
+    canvas: &mut Canvas<Window>,
+    device_info: &DeviceInfo,
+    target: &Option<Position>,
+) -> Result<(), ErrorString> {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    if let Some(target) = target {
+        let window_size = canvas.window().size();
+        let x = (target.x as f32 * window_size.0 as f32 / device_info.width() as f32) as i16;
+        let y = (target.y as f32 * window_size.1 as f32 / device_info.height() as f32) as i16;
+        let color = Color::RGB(255, 255, 255);
+        canvas.thick_line(x - CROSSHAIR_SIZE, y, x + CROSSHAIR_SIZE, y, 3, color)?;
+        canvas.thick_line(x, y - CROSSHAIR_SIZE, x, y + CROSSHAIR_SIZE, 3, color)?;
+        canvas.circle(x, y, CROSSHAIR_SIZE / 2, color)?;
+    }
+    canvas.present();
+    Ok(())
+}
@@ -1,16 +1,58 @@
+use crate::areas::mounting::Rotation;
+use crate::areas::note_event_source::PitchMode;
+use crate::sound::generator::SecondaryAxis;
+use crate::sound::midi::MPE_CHANNELS;
+use crate::sound::pitch::{
+    DEFAULT_CONCERT_PITCH, MAX_CONCERT_PITCH, MAX_TRANSPOSITION, MIN_CONCERT_PITCH,
+};
+use crate::sound::scala;
+use crate::sound::tuning::Tuning;
+use crate::sound::voice_allocator::VoiceStealing;
 use crate::sound::wave_form::WaveFormConfig;
+use crate::sound::DEFAULT_POLYPHONY;
 use crate::ErrorString;
 use crate::LayoutType;
 use clap::{App, Arg};
 use std::ffi::OsString;
+use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Args {
     pub volume: f32,
+    pub polyphony: usize,
+    pub voice_stealing: VoiceStealing,
     pub layout_type: LayoutType,
+    pub layout_file: Option<String>,
+    pub layout_name: Option<String>,
+    pub start_note: Option<i32>,
+    pub columns: Option<i32>,
+    pub octaves: Option<i32>,
+    pub rows: Option<i32>,
+    pub tuning: Tuning,
+    pub concert_pitch: f32,
+    // in semitones
+    pub transposition: i32,
+    pub pitch_mode: PitchMode,
+    pub secondary_axis: SecondaryAxis,
+    pub secondary_axis_cc: Option<u8>,
+    pub mpe: bool,
     pub midi: bool,
     pub wave_form_config: WaveFormConfig,
     pub dev_mode: bool,
+    pub dev_audio: bool,
+    pub device: Option<String>,
+    pub shared_input: bool,
+    pub list_devices: bool,
+    pub calibration_file: Option<String>,
+    pub calibrate: bool,
+    pub record_touches: Option<String>,
+    pub replay_touches: Option<String>,
+    pub hysteresis: i32,
+    pub smoothing: f32,
+    pub min_touch_duration: Duration,
+    pub rotation: Rotation,
+    pub mirror_x: bool,
+    pub mirror_y: bool,
 }
 
 pub fn parse<S, T>(binary_name: String, args: T) -> Result<Args, ErrorString>
@@ -23,6 +65,29 @@ where
         LayoutType::iter_variants().collect::<Vec<LayoutType>>(),
         LayoutType::default()
     );
+    let polyphony_help = format!(
+        "number of simultaneously sounding voices (default: {})",
+        DEFAULT_POLYPHONY
+    );
+    let concert_pitch_help = format!(
+        "sets the frequency of A4, e.g. 415 or 442, from {} to {} (default: {})",
+        MIN_CONCERT_PITCH, MAX_CONCERT_PITCH, DEFAULT_CONCERT_PITCH
+    );
+    let pitch_mode_help = format!(
+        "whether the pitch snaps to the touched note (Discrete), follows the finger continuously along the rows or keys (Continuous) or starts on the touched note and glides when moving (Glide), the midi backend needs --mpe for the latter two, possible values: {:?}, (default: {:?})",
+        PitchMode::iter_variants().collect::<Vec<PitchMode>>(),
+        PitchMode::default()
+    );
+    let secondary_axis_help = format!(
+        "what the position of a touch across its area controls in the synthesizer, possible values: {:?}, (default: {:?})",
+        SecondaryAxis::iter_variants().collect::<Vec<SecondaryAxis>>(),
+        SecondaryAxis::default()
+    );
+    let voice_stealing_help = format!(
+        "which voice to reuse when all voices are in use, possible values: {:?}, (default: {:?})",
+        VoiceStealing::iter_variants().collect::<Vec<VoiceStealing>>(),
+        VoiceStealing::default()
+    );
     let app = App::new(binary_name)
         .version("0.1.0")
         .author("Sönke Hahn <soenkehahn@gmail.com>")
@@ -33,12 +98,103 @@ where
                 .value_name("VOLUME")
                 .help("Sets a custom sound volume (default: 1.0)")
                 .takes_value(true),
+        ).arg(
+            Arg::with_name("polyphony")
+                .long("polyphony")
+                .value_name("VOICES")
+                .help(&polyphony_help)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("voice-stealing")
+                .long("voice-stealing")
+                .value_name("POLICY")
+                .help(&voice_stealing_help)
+                .takes_value(true),
         ).arg(
             Arg::with_name("layout")
                 .long("layout")
                 .value_name("LAYOUT_TYPE")
                 .help(&layout_help)
                 .takes_value(true),
+        ).arg(
+            Arg::with_name("layout-file")
+                .long("layout-file")
+                .value_name("FILE")
+                .help("loads the layout from a TOML file instead of using one of the built-in layouts")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("layout-name")
+                .long("layout-name")
+                .value_name("NAME")
+                .help("selects a layout from the file given with --layout-file (default: the first one)")
+                .requires("layout-file")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("start-note")
+                .long("start-note")
+                .value_name("MIDI_NOTE")
+                .help("sets the note in the lower left corner of the hexagonal and piano layouts (default: depends on the layout)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("columns")
+                .long("columns")
+                .value_name("NUMBER")
+                .help("sets the number of keys in a row of the hexagonal layouts (default: depends on the layout)")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("octaves")
+                .long("octaves")
+                .value_name("NUMBER")
+                .help("sets the number of octaves in a row of the piano layout (default: 2)")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("rows")
+                .long("rows")
+                .value_name("NUMBER")
+                .help("sets the number of rows of the piano layout (default: 2)")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("tuning")
+                .long("tuning")
+                .value_name("TUNING")
+                .help("sets the tuning, either a Scala file (FILE.scl), an equal division of the octave, e.g. 31edo, or comma separated scale degrees as ratios or cents, the last one being the period, e.g. 9/8,5/4,4/3,3/2,5/3,15/8,2/1 (default: 12edo)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("keyboard-mapping")
+                .long("keyboard-mapping")
+                .value_name("FILE")
+                .help("maps notes to the scale degrees of the tuning as given in a Scala keyboard mapping file (FILE.kbm)")
+                .requires("tuning")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("concert-pitch")
+                .long("concert-pitch")
+                .value_name("HZ")
+                .help(&concert_pitch_help)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("transpose")
+                .long("transpose")
+                .value_name("SEMITONES")
+                .help("transposes all notes by the given number of semitones, e.g. -2 (default: 0)")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("transpose-octaves")
+                .long("transpose-octaves")
+                .value_name("OCTAVES")
+                .help("transposes all notes by the given number of octaves, adding to --transpose (default: 0)")
+                .allow_hyphen_values(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("pitch-mode")
+                .long("pitch-mode")
+                .value_name("PITCH_MODE")
+                .help(&pitch_mode_help)
+                .takes_value(true),
         ).arg(
             Arg::with_name("harmonics")
                 .long("harmonics")
@@ -49,19 +205,154 @@ where
                 .long("midi")
                 .help("switches to the midi backend (default: false)")
                 .takes_value(false),
+        ).arg(
+            Arg::with_name("secondary-axis")
+                .long("secondary-axis")
+                .value_name("TARGET")
+                .help(&secondary_axis_help)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("secondary-axis-cc")
+                .long("secondary-axis-cc")
+                .value_name("CONTROLLER")
+                .help("sends the position of a touch across its area as the given midi controller, e.g. 74 (default: not sent)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("mpe")
+                .long("mpe")
+                .help("sends every voice on its own midi channel (2 to 16), as in MIDI Polyphonic Expression, which limits the polyphony to 15 (default: false)")
+                .takes_value(false),
         ).arg(
             Arg::with_name("dev-mode")
                 .long("dev-mode")
-                .help("disables touch input and audio output (default: false)")
+                .help("replaces touch input with mouse and keyboard input and disables audio output (default: false)")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("dev-audio")
+                .long("dev-audio")
+                .help("enables audio output in dev mode (default: false)")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("device")
+                .long("device")
+                .value_name("PATH")
+                .help("sets the touch input device, e.g. /dev/input/event5 (default: the first touch device found)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("shared-input")
+                .long("shared-input")
+                .help("reads the touch device without grabbing it exclusively, so that other programs still receive its input (default: false)")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("list-devices")
+                .long("list-devices")
+                .help("lists the touch devices found and exits")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("calibration")
+                .long("calibration")
+                .value_name("FILE")
+                .help("applies the touch calibration stored in FILE")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("calibrate")
+                .long("calibrate")
+                .help("shows calibration targets and writes the resulting calibration to the file given with --calibration")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("record-touches")
+                .long("record-touches")
+                .value_name("FILE")
+                .help("records the touch input to FILE")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("replay-touches")
+                .long("replay-touches")
+                .value_name("FILE")
+                .help("replays touch input recorded with --record-touches instead of reading the touch device")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("hysteresis")
+                .long("hysteresis")
+                .value_name("DISTANCE")
+                .help("how far (in touch coordinates) a finger has to move past the border of a note before switching to the neighbouring note (default: 0)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("smoothing")
+                .long("smoothing")
+                .value_name("FACTOR")
+                .help("smooths touch positions, from 0 (no smoothing) to just below 1 (heavy smoothing) (default: 0)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("min-touch-duration")
+                .long("min-touch-duration")
+                .value_name("MILLISECONDS")
+                .help("ignores touches that are shorter than this (default: 0)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("rotation")
+                .long("rotation")
+                .value_name("DEGREES")
+                .help("clockwise rotation of the mounted screen, possible values: 0, 90, 180, 270 (default: 0)")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("mirror-x")
+                .long("mirror-x")
+                .help("mirrors the instrument horizontally (default: false)")
+                .takes_value(false),
+        ).arg(
+            Arg::with_name("mirror-y")
+                .long("mirror-y")
+                .help("mirrors the instrument vertically (default: false)")
                 .takes_value(false),
         );
     let matches = app.get_matches_from(args);
+    let mpe = matches.is_present("mpe");
     Ok(Args {
         volume: parse_volume(matches.value_of("volume"))?,
+        polyphony: parse_polyphony(matches.value_of("polyphony"), mpe)?,
+        voice_stealing: parse_voice_stealing(matches.value_of("voice-stealing"))?,
         layout_type: parse_layout_type(matches.value_of("layout"))?,
+        layout_file: matches.value_of("layout-file").map(String::from),
+        layout_name: matches.value_of("layout-name").map(String::from),
+        start_note: parse_start_note(matches.value_of("start-note"))?,
+        columns: parse_count(matches.value_of("columns"), "number of columns")?,
+        octaves: parse_count(matches.value_of("octaves"), "number of octaves")?,
+        rows: parse_count(matches.value_of("rows"), "number of rows")?,
+        tuning: parse_tuning(
+            matches.value_of("tuning"),
+            matches.value_of("keyboard-mapping"),
+        )?,
+        concert_pitch: parse_concert_pitch(matches.value_of("concert-pitch"))?,
+        transposition: parse_transpositions(
+            matches.value_of("transpose"),
+            matches.value_of("transpose-octaves"),
+        )?,
+        pitch_mode: parse_pitch_mode(
+            matches.value_of("pitch-mode"),
+            matches.is_present("midi"),
+            mpe,
+        )?,
+        secondary_axis: parse_secondary_axis(matches.value_of("secondary-axis"))?,
+        secondary_axis_cc: parse_controller(matches.value_of("secondary-axis-cc"))?,
+        mpe,
         wave_form_config: parse_wave_form_config(matches.value_of("harmonics"))?,
         midi: matches.is_present("midi"),
         dev_mode: matches.is_present("dev-mode"),
+        dev_audio: matches.is_present("dev-audio"),
+        device: matches.value_of("device").map(String::from),
+        shared_input: matches.is_present("shared-input"),
+        list_devices: matches.is_present("list-devices"),
+        calibration_file: matches.value_of("calibration").map(String::from),
+        calibrate: matches.is_present("calibrate"),
+        record_touches: matches.value_of("record-touches").map(String::from),
+        replay_touches: matches.value_of("replay-touches").map(String::from),
+        hysteresis: parse_hysteresis(matches.value_of("hysteresis"))?,
+        smoothing: parse_smoothing(matches.value_of("smoothing"))?,
+        min_touch_duration: parse_min_touch_duration(matches.value_of("min-touch-duration"))?,
+        rotation: parse_rotation(matches.value_of("rotation"))?,
+        mirror_x: matches.is_present("mirror-x"),
+        mirror_y: matches.is_present("mirror-y"),
     })
 }
 
@@ -74,12 +365,247 @@ fn parse_volume(input: Option<&str>) -> Result<f32, ErrorString> {
     }
 }
 
+// With --mpe every voice needs a channel of its own.
+fn parse_polyphony(input: Option<&str>, mpe: bool) -> Result<usize, ErrorString> {
+    let max_polyphony = if mpe { MPE_CHANNELS } else { usize::MAX };
+    match input {
+        None => Ok(DEFAULT_POLYPHONY.min(max_polyphony)),
+        Some(string) => match string.parse() {
+            Ok(polyphony) if polyphony > 0 && polyphony <= max_polyphony => Ok(polyphony),
+            Ok(polyphony) if polyphony > 0 => Err(ErrorString(format!(
+                "invalid polyphony: {}, --mpe allows at most {} voices",
+                string, MPE_CHANNELS
+            ))),
+            _ => Err(ErrorString(format!(
+                "invalid polyphony: {}, expected a positive number",
+                string
+            ))),
+        },
+    }
+}
+
+fn parse_start_note(input: Option<&str>) -> Result<Option<i32>, ErrorString> {
+    match input {
+        None => Ok(None),
+        Some(string) => match string.parse() {
+            Ok(start_note) if (0..128).contains(&start_note) => Ok(Some(start_note)),
+            _ => Err(ErrorString(format!(
+                "invalid start note: {}, expected a midi note from 0 to 127",
+                string
+            ))),
+        },
+    }
+}
+
+fn parse_count(input: Option<&str>, description: &str) -> Result<Option<i32>, ErrorString> {
+    match input {
+        None => Ok(None),
+        Some(string) => match string.parse() {
+            Ok(count) if count > 0 => Ok(Some(count)),
+            _ => Err(ErrorString(format!(
+                "invalid {}: {}, expected a positive number",
+                description, string
+            ))),
+        },
+    }
+}
+
+fn parse_tuning(
+    input: Option<&str>,
+    keyboard_mapping: Option<&str>,
+) -> Result<Tuning, ErrorString> {
+    let tuning = match input {
+        None => Tuning::default(),
+        Some(string) => Tuning::parse(string)?,
+    };
+    match keyboard_mapping {
+        None => Ok(tuning),
+        Some(file) => tuning.with_keyboard_mapping(scala::load_kbm(file)?),
+    }
+}
+
+fn parse_concert_pitch(input: Option<&str>) -> Result<f32, ErrorString> {
+    match input {
+        None => Ok(DEFAULT_CONCERT_PITCH),
+        Some(string) => match string.parse::<f32>() {
+            Ok(concert_pitch)
+                if (MIN_CONCERT_PITCH..=MAX_CONCERT_PITCH).contains(&concert_pitch) =>
+            {
+                Ok(concert_pitch)
+            }
+            _ => Err(ErrorString(format!(
+                "invalid concert pitch: {}, expected a frequency from {} to {} Hz",
+                string, MIN_CONCERT_PITCH, MAX_CONCERT_PITCH
+            ))),
+        },
+    }
+}
+
+// Parses a transposition in units of the given number of semitones.
+fn parse_transposition(input: Option<&str>, semitones: i32) -> Result<i32, ErrorString> {
+    match input {
+        None => Ok(0),
+        Some(string) => match string.parse::<i32>() {
+            Ok(transposition) if transposition.abs() <= MAX_TRANSPOSITION / semitones => {
+                Ok(transposition * semitones)
+            }
+            _ => Err(ErrorString(format!(
+                "invalid transposition: {}, expected a whole number from -{} to {}",
+                string,
+                MAX_TRANSPOSITION / semitones,
+                MAX_TRANSPOSITION / semitones
+            ))),
+        },
+    }
+}
+
+// Semitones and octaves add up, but the sum has to stay in range as well.
+fn parse_transpositions(
+    semitones: Option<&str>,
+    octaves: Option<&str>,
+) -> Result<i32, ErrorString> {
+    let transposition = parse_transposition(semitones, 1)? + parse_transposition(octaves, 12)?;
+    if transposition.abs() <= MAX_TRANSPOSITION {
+        Ok(transposition)
+    } else {
+        Err(ErrorString(format!(
+            "invalid transposition: {} semitones in total, expected from -{} to {}",
+            transposition, MAX_TRANSPOSITION, MAX_TRANSPOSITION
+        )))
+    }
+}
+
+fn parse_hysteresis(input: Option<&str>) -> Result<i32, ErrorString> {
+    match input {
+        None => Ok(0),
+        Some(string) => match string.parse() {
+            Ok(hysteresis) if hysteresis >= 0 => Ok(hysteresis),
+            _ => Err(ErrorString(format!(
+                "invalid hysteresis: {}, expected a non-negative number",
+                string
+            ))),
+        },
+    }
+}
+
+fn parse_smoothing(input: Option<&str>) -> Result<f32, ErrorString> {
+    match input {
+        None => Ok(0.0),
+        Some(string) => match string.parse() {
+            Ok(smoothing) if smoothing >= 0.0 && smoothing < 1.0 => Ok(smoothing),
+            _ => Err(ErrorString(format!(
+                "invalid smoothing: {}, expected a number from 0 up to (but excluding) 1",
+                string
+            ))),
+        },
+    }
+}
+
+fn parse_min_touch_duration(input: Option<&str>) -> Result<Duration, ErrorString> {
+    match input {
+        None => Ok(Duration::from_millis(0)),
+        Some(string) => match string.parse() {
+            Ok(milliseconds) => Ok(Duration::from_millis(milliseconds)),
+            _ => Err(ErrorString(format!(
+                "invalid minimum touch duration: {}, expected milliseconds",
+                string
+            ))),
+        },
+    }
+}
+
+fn parse_rotation(input: Option<&str>) -> Result<Rotation, ErrorString> {
+    match input {
+        None => Ok(Rotation::default()),
+        Some("0") => Ok(Rotation::Degrees0),
+        Some("90") => Ok(Rotation::Degrees90),
+        Some("180") => Ok(Rotation::Degrees180),
+        Some("270") => Ok(Rotation::Degrees270),
+        Some(rotation) => Err(ErrorString(format!(
+            "invalid rotation: {}, possible values: 0, 90, 180, 270",
+            rotation
+        ))),
+    }
+}
+
+fn parse_voice_stealing(input: Option<&str>) -> Result<VoiceStealing, ErrorString> {
+    match input {
+        None => Ok(VoiceStealing::default()),
+        Some("Oldest") => Ok(VoiceStealing::Oldest),
+        Some("Quietest") => Ok(VoiceStealing::Quietest),
+        Some(voice_stealing) => Err(ErrorString(format!(
+            "unknown voice stealing policy: {}, possible values: {:?}",
+            voice_stealing,
+            VoiceStealing::iter_variants().collect::<Vec<VoiceStealing>>()
+        ))),
+    }
+}
+
+// Without --mpe the midi backend can't bend single notes, so it would have to
+// play a new note for every change in pitch.
+fn parse_pitch_mode(input: Option<&str>, midi: bool, mpe: bool) -> Result<PitchMode, ErrorString> {
+    let pitch_mode = match input {
+        None => PitchMode::default(),
+        Some("Discrete") => PitchMode::Discrete,
+        Some("Continuous") => PitchMode::Continuous,
+        Some("Glide") => PitchMode::Glide,
+        Some(pitch_mode) => {
+            return Err(ErrorString(format!(
+                "unknown pitch mode: {}, possible values: {:?}",
+                pitch_mode,
+                PitchMode::iter_variants().collect::<Vec<PitchMode>>()
+            )))
+        }
+    };
+    if midi && !mpe && pitch_mode != PitchMode::Discrete {
+        return Err(ErrorString(format!(
+            "pitch mode {:?} needs --mpe with --midi",
+            pitch_mode
+        )));
+    }
+    Ok(pitch_mode)
+}
+
+fn parse_secondary_axis(input: Option<&str>) -> Result<SecondaryAxis, ErrorString> {
+    match input {
+        None => Ok(SecondaryAxis::default()),
+        Some("Off") => Ok(SecondaryAxis::Off),
+        Some("Volume") => Ok(SecondaryAxis::Volume),
+        Some("Cutoff") => Ok(SecondaryAxis::Cutoff),
+        Some("Harmonics") => Ok(SecondaryAxis::Harmonics),
+        Some(secondary_axis) => Err(ErrorString(format!(
+            "unknown secondary axis target: {}, possible values: {:?}",
+            secondary_axis,
+            SecondaryAxis::iter_variants().collect::<Vec<SecondaryAxis>>()
+        ))),
+    }
+}
+
+fn parse_controller(input: Option<&str>) -> Result<Option<u8>, ErrorString> {
+    match input {
+        None => Ok(None),
+        Some(string) => match string.parse() {
+            Ok(controller) if controller < 120 => Ok(Some(controller)),
+            _ => Err(ErrorString(format!(
+                "invalid midi controller: {}, expected a number from 0 to 119",
+                string
+            ))),
+        },
+    }
+}
+
 fn parse_layout_type(input: Option<&str>) -> Result<LayoutType, ErrorString> {
     match input {
         None => Ok(LayoutType::default()),
         Some("Parallelograms") => Ok(LayoutType::Parallelograms),
         Some("Grid") => Ok(LayoutType::Grid),
         Some("Grid2") => Ok(LayoutType::Grid2),
+        Some("WickiHayden") => Ok(LayoutType::WickiHayden),
+        Some("HarmonicTable") => Ok(LayoutType::HarmonicTable),
+        Some("Janko") => Ok(LayoutType::Janko),
+        Some("BosanquetWilson") => Ok(LayoutType::BosanquetWilson),
+        Some("Fokker") => Ok(LayoutType::Fokker),
+        Some("Piano") => Ok(LayoutType::Piano),
         Some(layout) => Err(ErrorString(format!(
             "unknown layout: {}, possible values: {:?}",
             layout,
@@ -120,12 +646,41 @@ pub mod test {
     fn has_default_values_when_no_arguments_given() {
         let expected = Args {
             volume: 1.0,
+            polyphony: DEFAULT_POLYPHONY,
+            voice_stealing: VoiceStealing::Oldest,
             layout_type: LayoutType::default(),
+            layout_file: None,
+            layout_name: None,
+            start_note: None,
+            columns: None,
+            octaves: None,
+            rows: None,
+            tuning: Tuning::default(),
+            concert_pitch: 440.0,
+            transposition: 0,
+            pitch_mode: PitchMode::Discrete,
+            secondary_axis: SecondaryAxis::Off,
+            secondary_axis_cc: None,
+            mpe: false,
             midi: false,
             wave_form_config: WaveFormConfig {
                 harmonics: vec![1.0],
             },
             dev_mode: false,
+            dev_audio: false,
+            device: None,
+            shared_input: false,
+            list_devices: false,
+            calibration_file: None,
+            calibrate: false,
+            record_touches: None,
+            replay_touches: None,
+            hysteresis: 0,
+            smoothing: 0.0,
+            min_touch_duration: Duration::from_millis(0),
+            rotation: Rotation::Degrees0,
+            mirror_x: false,
+            mirror_y: false,
         };
         assert_eq!(args(vec![]), expected)
     }
@@ -135,9 +690,198 @@ pub mod test {
         assert_eq!(args(vec!["--volume", "2"]).volume, 2.0);
     }
 
+    #[test]
+    fn allows_to_change_the_polyphony() {
+        assert_eq!(args(vec!["--polyphony", "32"]).polyphony, 32);
+    }
+
+    #[test]
+    fn rejects_zero_polyphony() {
+        assert!(parse(
+            "test-binary-name".to_string(),
+            vec!["test-binary-name", "--polyphony", "0"].into_iter()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn allows_to_change_the_voice_stealing_policy() {
+        assert_eq!(
+            args(vec!["--voice-stealing", "Quietest"]).voice_stealing,
+            VoiceStealing::Quietest
+        );
+    }
+
     #[test]
     fn allows_to_change_the_layout_type() {
         assert_eq!(args(vec!["--layout", "Grid"]).layout_type, LayoutType::Grid);
+        assert_eq!(
+            args(vec!["--layout", "WickiHayden"]).layout_type,
+            LayoutType::WickiHayden
+        );
+    }
+
+    #[test]
+    fn allows_to_configure_the_hexagonal_layouts() {
+        let args = args(vec![
+            "--layout",
+            "Janko",
+            "--start-note",
+            "48",
+            "--columns",
+            "6",
+        ]);
+        assert_eq!(args.layout_type, LayoutType::Janko);
+        assert_eq!(args.start_note, Some(48));
+        assert_eq!(args.columns, Some(6));
+    }
+
+    #[test]
+    fn allows_to_configure_the_piano_layout() {
+        let args = args(vec!["--layout", "Piano", "--octaves", "3", "--rows", "1"]);
+        assert_eq!(args.layout_type, LayoutType::Piano);
+        assert_eq!(args.octaves, Some(3));
+        assert_eq!(args.rows, Some(1));
+    }
+
+    #[test]
+    fn rejects_invalid_start_notes_and_columns() {
+        for arguments in vec![
+            vec!["--start-note", "128"],
+            vec!["--start-note", "C"],
+            vec!["--columns", "0"],
+            vec!["--octaves", "0"],
+            vec!["--rows", "-1"],
+        ] {
+            let mut with_binary = vec!["test-binary-name"];
+            with_binary.extend(arguments);
+            assert!(parse("test-binary-name".to_string(), with_binary.into_iter()).is_err());
+        }
+    }
+
+    #[test]
+    fn allows_to_change_the_tuning() {
+        assert_eq!(args(vec!["--tuning", "31edo"]).tuning, Tuning::edo(31));
+        assert_eq!(
+            args(vec!["--tuning", "9/8,2/1"]).tuning,
+            Tuning::from_ratios(vec![9.0 / 8.0, 2.0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn allows_to_change_the_concert_pitch() {
+        assert_eq!(args(vec!["--concert-pitch", "415"]).concert_pitch, 415.0);
+        assert_eq!(args(vec!["--concert-pitch", "442.5"]).concert_pitch, 442.5);
+    }
+
+    #[test]
+    fn allows_to_transpose() {
+        assert_eq!(args(vec!["--transpose", "-2"]).transposition, -2);
+        assert_eq!(args(vec!["--transpose-octaves", "1"]).transposition, 12);
+        assert_eq!(
+            args(vec!["--transpose", "3", "--transpose-octaves", "-1"]).transposition,
+            -9
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_concert_pitches_and_transpositions() {
+        for arguments in vec![
+            vec!["--concert-pitch", "0"],
+            vec!["--concert-pitch", "A"],
+            vec!["--transpose", "1.5"],
+            vec!["--transpose-octaves", "20"],
+            vec!["--transpose", "127", "--transpose-octaves", "10"],
+            vec!["--transpose", "-8", "--transpose-octaves", "-10"],
+        ] {
+            let mut with_binary = vec!["test-binary-name"];
+            with_binary.extend(arguments);
+            assert!(parse("test-binary-name".to_string(), with_binary.into_iter()).is_err());
+        }
+    }
+
+    #[test]
+    fn allows_to_change_the_pitch_mode() {
+        assert_eq!(
+            args(vec!["--pitch-mode", "Glide"]).pitch_mode,
+            PitchMode::Glide
+        );
+        assert!(parse(
+            "test-binary-name".to_string(),
+            vec!["test-binary-name", "--pitch-mode", "Smooth"].into_iter()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn requires_mpe_for_continuous_pitches_over_midi() {
+        for &(input, pitch_mode) in &[
+            ("Continuous", PitchMode::Continuous),
+            ("Glide", PitchMode::Glide),
+        ] {
+            assert!(parse(
+                "test-binary-name".to_string(),
+                vec!["test-binary-name", "--midi", "--pitch-mode", input].into_iter()
+            )
+            .is_err());
+            assert_eq!(
+                args(vec!["--midi", "--mpe", "--pitch-mode", input]).pitch_mode,
+                pitch_mode
+            );
+        }
+        assert_eq!(
+            args(vec!["--midi", "--pitch-mode", "Discrete"]).pitch_mode,
+            PitchMode::Discrete
+        );
+    }
+
+    #[test]
+    fn allows_to_map_the_secondary_axis() {
+        let args = args(vec![
+            "--secondary-axis",
+            "Cutoff",
+            "--secondary-axis-cc",
+            "74",
+            "--mpe",
+        ]);
+        assert_eq!(args.secondary_axis, SecondaryAxis::Cutoff);
+        assert_eq!(args.secondary_axis_cc, Some(74));
+        assert_eq!(args.mpe, true);
+    }
+
+    #[test]
+    fn limits_the_polyphony_for_mpe() {
+        assert_eq!(args(vec!["--mpe"]).polyphony, MPE_CHANNELS);
+        assert_eq!(args(vec!["--mpe", "--polyphony", "4"]).polyphony, 4);
+        assert!(parse(
+            "test-binary-name".to_string(),
+            vec!["test-binary-name", "--mpe", "--polyphony", "16"].into_iter()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_secondary_axis_mappings() {
+        for arguments in vec![
+            vec!["--secondary-axis", "Pitch"],
+            vec!["--secondary-axis-cc", "120"],
+        ] {
+            let mut with_binary = vec!["test-binary-name"];
+            with_binary.extend(arguments);
+            assert!(parse("test-binary-name".to_string(), with_binary.into_iter()).is_err());
+        }
+    }
+
+    #[test]
+    fn allows_to_load_layouts_from_a_file() {
+        let args = args(vec![
+            "--layout-file",
+            "layouts.toml",
+            "--layout-name",
+            "pads",
+        ]);
+        assert_eq!(args.layout_file, Some("layouts.toml".to_string()));
+        assert_eq!(args.layout_name, Some("pads".to_string()));
     }
 
     #[test]
@@ -150,6 +894,108 @@ pub mod test {
         assert_eq!(args(vec!["--dev-mode"]).dev_mode, true);
     }
 
+    #[test]
+    fn allows_to_enable_audio_in_dev_mode() {
+        assert_eq!(args(vec!["--dev-audio"]).dev_audio, true);
+    }
+
+    #[test]
+    fn allows_to_specify_the_touch_device() {
+        assert_eq!(
+            args(vec!["--device", "/dev/input/event5"]).device,
+            Some("/dev/input/event5".to_string())
+        );
+    }
+
+    #[test]
+    fn allows_to_share_the_touch_device() {
+        assert_eq!(args(vec!["--shared-input"]).shared_input, true);
+    }
+
+    #[test]
+    fn allows_to_list_devices() {
+        assert_eq!(args(vec!["--list-devices"]).list_devices, true);
+    }
+
+    #[test]
+    fn allows_to_specify_a_calibration_file() {
+        assert_eq!(
+            args(vec!["--calibration", "touch.calibration"]).calibration_file,
+            Some("touch.calibration".to_string())
+        );
+    }
+
+    #[test]
+    fn allows_to_run_the_calibration() {
+        assert_eq!(args(vec!["--calibrate"]).calibrate, true);
+    }
+
+    #[test]
+    fn allows_to_record_touches() {
+        assert_eq!(
+            args(vec!["--record-touches", "gig.touches"]).record_touches,
+            Some("gig.touches".to_string())
+        );
+    }
+
+    #[test]
+    fn allows_to_replay_touches() {
+        assert_eq!(
+            args(vec!["--replay-touches", "gig.touches"]).replay_touches,
+            Some("gig.touches".to_string())
+        );
+    }
+
+    #[test]
+    fn allows_to_configure_the_hysteresis() {
+        assert_eq!(args(vec!["--hysteresis", "15"]).hysteresis, 15);
+    }
+
+    #[test]
+    fn allows_to_configure_the_smoothing() {
+        assert_eq!(args(vec!["--smoothing", "0.5"]).smoothing, 0.5);
+    }
+
+    #[test]
+    fn rejects_smoothing_factors_of_one_and_more() {
+        assert!(parse(
+            "test-binary-name".to_string(),
+            vec!["test-binary-name", "--smoothing", "1"].into_iter()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn allows_to_configure_the_minimum_touch_duration() {
+        assert_eq!(
+            args(vec!["--min-touch-duration", "30"]).min_touch_duration,
+            Duration::from_millis(30)
+        );
+    }
+
+    #[test]
+    fn allows_to_rotate_the_instrument() {
+        assert_eq!(
+            args(vec!["--rotation", "270"]).rotation,
+            Rotation::Degrees270
+        );
+    }
+
+    #[test]
+    fn rejects_other_rotations() {
+        assert!(parse(
+            "test-binary-name".to_string(),
+            vec!["test-binary-name", "--rotation", "45"].into_iter()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn allows_to_mirror_the_instrument() {
+        let args = args(vec!["--mirror-x", "--mirror-y"]);
+        assert_eq!((args.mirror_x, args.mirror_y), (true, true));
+    }
+
     #[test]
     fn allows_to_specify_harmonics() {
         assert_eq!(
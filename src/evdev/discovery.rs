@@ -0,0 +1,87 @@
+use crate::evdev::{open_device, Protocol};
+use crate::AddMessage;
+use crate::ErrorString;
+use ::std::fs::read_dir;
+
+const INPUT_DIRECTORY: &str = "/dev/input";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchDevice {
+    pub path: String,
+    pub name: String,
+    pub protocol: Protocol,
+}
+
+fn event_device_number(file_name: &str) -> Option<u32> {
+    file_name
+        .strip_prefix("event")
+        .and_then(|number| number.parse().ok())
+}
+
+fn event_device_paths() -> Result<Vec<String>, ErrorString> {
+    let mut numbers = vec![];
+    for entry in read_dir(INPUT_DIRECTORY).add_message(format!("can't read {}", INPUT_DIRECTORY))? {
+        let entry = entry?;
+        if let Some(number) = entry.file_name().to_str().and_then(event_device_number) {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_unstable();
+    Ok(numbers
+        .into_iter()
+        .map(|number| format!("{}/event{}", INPUT_DIRECTORY, number))
+        .collect())
+}
+
+// Multitouch devices come first, so that they're preferred over
+// single touch devices.
+pub fn find_touch_devices() -> Result<Vec<TouchDevice>, ErrorString> {
+    let mut result = vec![];
+    for path in event_device_paths()? {
+        match open_device(&path) {
+            Err(_) => {}
+            Ok(device) => {
+                if let Some(protocol) = Protocol::detect(&device) {
+                    result.push(TouchDevice {
+                        name: device.name().unwrap_or("<unnamed>").to_string(),
+                        path,
+                        protocol,
+                    });
+                }
+            }
+        }
+    }
+    result.sort_by_key(|device| device.protocol);
+    Ok(result)
+}
+
+pub fn find_touch_device() -> Result<TouchDevice, ErrorString> {
+    find_touch_devices()?.into_iter().next().ok_or_else(|| {
+        ErrorString(format!(
+            "no touch device found in {}, use --device to specify one",
+            INPUT_DIRECTORY
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod event_device_number {
+        use super::*;
+
+        #[test]
+        fn parses_the_number_of_event_devices() {
+            assert_eq!(event_device_number("event0"), Some(0));
+            assert_eq!(event_device_number("event12"), Some(12));
+        }
+
+        #[test]
+        fn ignores_other_files() {
+            assert_eq!(event_device_number("mouse0"), None);
+            assert_eq!(event_device_number("by-id"), None);
+            assert_eq!(event_device_number("event"), None);
+        }
+    }
+}
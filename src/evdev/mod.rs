@@ -0,0 +1,2053 @@
+pub mod discovery;
+pub mod recording;
+#[cfg(test)]
+pub mod uinput;
+
+use crate::utils::shutdown::Shutdown;
+use crate::AddMessage;
+use crate::ErrorString;
+use ::evdev_rs::enums::{EventCode, EventType::*, EV_ABS, EV_KEY, EV_SYN::*};
+use ::evdev_rs::{Device, GrabMode, InputEvent, ReadFlag, ReadStatus, TimeVal};
+use ::nix::fcntl::OFlag;
+use ::nix::poll::{poll, PollFd, PollFlags};
+use ::std::collections::VecDeque;
+use ::std::fs::OpenOptions;
+use ::std::io::ErrorKind;
+use ::std::iter::Flatten;
+use ::std::os::unix::fs::OpenOptionsExt;
+use ::std::os::unix::io::IntoRawFd;
+use ::std::thread::sleep;
+use ::std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Devices are opened in non-blocking mode, so that reading them can be
+// interrupted by a shutdown.
+fn open_device(path: &str) -> Result<Device, ErrorString> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(path)
+        .add_message(format!("file not found: {}", path))?;
+    let mut device = Device::new().ok_or("evdev: can't initialize device")?;
+    device
+        .set_fd(file)
+        .add_message(format!("set_fd failed on {}", path))?;
+    Ok(device)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisInfo {
+    pub minimum: i32,
+    pub maximum: i32,
+}
+
+impl AxisInfo {
+    fn from_device(device: &Device, code: &EventCode) -> Result<AxisInfo, ErrorString> {
+        let abs_info = device
+            .abs_info(code)
+            .ok_or_else(|| ErrorString(format!("evdev: device doesn't report {:?}", code)))?;
+        Ok(AxisInfo {
+            minimum: abs_info.minimum,
+            maximum: abs_info.maximum,
+        })
+    }
+
+    fn size(&self) -> i32 {
+        self.maximum - self.minimum
+    }
+
+    // Maps values of this axis to the range from 0 to 1.
+    fn normalize(&self, value: i32) -> f32 {
+        if self.size() <= 0 {
+            return 1.0;
+        }
+        let normalized = (value - self.minimum) as f32 / self.size() as f32;
+        normalized.max(0.0).min(1.0)
+    }
+}
+
+// The ways in which evdev devices report touches, in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protocol {
+    // Multitouch protocol B, with slots.
+    MultiTouchB,
+    // Multitouch protocol A, contacts separated by SYN_MT_REPORT.
+    MultiTouchA,
+    // ABS_X, ABS_Y and BTN_TOUCH, e.g. resistive screens and drawing tablets.
+    SingleTouch,
+}
+
+impl Protocol {
+    pub fn detect(device: &Device) -> Option<Protocol> {
+        let has_abs = |code| device.has(&EventCode::EV_ABS(code));
+        if has_abs(EV_ABS::ABS_MT_SLOT) && has_abs(EV_ABS::ABS_MT_POSITION_X) {
+            Some(Protocol::MultiTouchB)
+        } else if has_abs(EV_ABS::ABS_MT_POSITION_X) && has_abs(EV_ABS::ABS_MT_POSITION_Y) {
+            Some(Protocol::MultiTouchA)
+        } else if has_abs(EV_ABS::ABS_X)
+            && has_abs(EV_ABS::ABS_Y)
+            && device.has(&EventCode::EV_KEY(EV_KEY::BTN_TOUCH))
+        {
+            Some(Protocol::SingleTouch)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub protocol: Protocol,
+    pub x: AxisInfo,
+    pub y: AxisInfo,
+    pub pressure: Option<AxisInfo>,
+    pub touch_major: Option<AxisInfo>,
+    pub width_major: Option<AxisInfo>,
+}
+
+impl DeviceInfo {
+    fn from_device(device: &Device) -> Result<DeviceInfo, ErrorString> {
+        let protocol = Protocol::detect(device).ok_or("evdev: device doesn't report touches")?;
+        let axis = |code| AxisInfo::from_device(device, &EventCode::EV_ABS(code));
+        let optional_axis = |code| axis(code).ok();
+        Ok(match protocol {
+            Protocol::MultiTouchB | Protocol::MultiTouchA => DeviceInfo {
+                protocol,
+                x: axis(EV_ABS::ABS_MT_POSITION_X)?,
+                y: axis(EV_ABS::ABS_MT_POSITION_Y)?,
+                pressure: optional_axis(EV_ABS::ABS_MT_PRESSURE),
+                touch_major: optional_axis(EV_ABS::ABS_MT_TOUCH_MAJOR),
+                width_major: optional_axis(EV_ABS::ABS_MT_WIDTH_MAJOR),
+            },
+            Protocol::SingleTouch => DeviceInfo {
+                protocol,
+                x: axis(EV_ABS::ABS_X)?,
+                y: axis(EV_ABS::ABS_Y)?,
+                pressure: optional_axis(EV_ABS::ABS_PRESSURE),
+                touch_major: None,
+                width_major: None,
+            },
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.x.size()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.y.size()
+    }
+
+    fn origin(&self) -> Position {
+        Position {
+            x: self.x.minimum,
+            y: self.y.minimum,
+        }
+    }
+}
+
+// Used for devices that don't report the range of ABS_MT_SLOT.
+const DEFAULT_SLOTS: usize = 10;
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// How often a shutdown is checked for while waiting for touch events.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub enum DeviceLocation {
+    Path(String),
+    Discover,
+}
+
+impl DeviceLocation {
+    fn path(&self) -> Result<String, ErrorString> {
+        match self {
+            DeviceLocation::Path(path) => Ok(path.clone()),
+            DeviceLocation::Discover => {
+                let device = discovery::find_touch_device()?;
+                eprintln!("using touch device {}: {}", device.path, device.name);
+                Ok(device.path)
+            }
+        }
+    }
+
+    fn open(&self, grab: bool) -> Result<(Device, DeviceInfo, usize), ErrorString> {
+        let path = self.path()?;
+        let mut device = open_device(&path)?;
+        let device_info = DeviceInfo::from_device(&device).add_message(path.clone())?;
+        let slots = device
+            .abs_info(&EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT))
+            .map(|abs_info| abs_info.maximum as usize + 1)
+            .unwrap_or(DEFAULT_SLOTS);
+        if grab {
+            device.grab(GrabMode::Grab).add_message(path)?;
+        }
+        Ok((device, device_info, slots))
+    }
+}
+
+fn mk_event(time: &TimeVal, event_code: EventCode, value: i32) -> InputEvent {
+    InputEvent {
+        time: TimeVal {
+            tv_sec: time.tv_sec,
+            tv_usec: time.tv_usec,
+        },
+        event_type: match event_code {
+            EventCode::EV_SYN(_) => EV_SYN,
+            EventCode::EV_KEY(_) => EV_KEY,
+            _ => EV_ABS,
+        },
+        event_code,
+        value,
+    }
+}
+
+fn now() -> TimeVal {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    TimeVal {
+        tv_sec: now.as_secs() as i64,
+        tv_usec: i64::from(now.subsec_micros()),
+    }
+}
+
+// Reports the complete state of all slots, followed by the currently active slot.
+fn slot_state_events(device: &Device, slots: usize, time: &TimeVal) -> Vec<InputEvent> {
+    let mut result = vec![];
+    for slot in 0..slots {
+        let slot_value = |code| {
+            device
+                .slot_value(slot as u32, &EventCode::EV_ABS(code))
+                .unwrap_or(-1)
+        };
+        let tracking_id = slot_value(EV_ABS::ABS_MT_TRACKING_ID);
+        result.push(mk_event(
+            time,
+            EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT),
+            slot as i32,
+        ));
+        result.push(mk_event(
+            time,
+            EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID),
+            tracking_id,
+        ));
+        if tracking_id != -1 {
+            result.push(mk_event(
+                time,
+                EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X),
+                slot_value(EV_ABS::ABS_MT_POSITION_X),
+            ));
+            result.push(mk_event(
+                time,
+                EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y),
+                slot_value(EV_ABS::ABS_MT_POSITION_Y),
+            ));
+            for code in &[
+                EV_ABS::ABS_MT_PRESSURE,
+                EV_ABS::ABS_MT_TOUCH_MAJOR,
+                EV_ABS::ABS_MT_WIDTH_MAJOR,
+            ] {
+                let event_code = EventCode::EV_ABS(*code);
+                if let Some(value) = device.slot_value(slot as u32, &event_code) {
+                    result.push(mk_event(time, event_code, value));
+                }
+            }
+        }
+    }
+    if let Some(current_slot) = device.event_value(&EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT)) {
+        result.push(mk_event(
+            time,
+            EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT),
+            current_slot,
+        ));
+    }
+    result.push(mk_event(time, EventCode::EV_SYN(SYN_REPORT), 0));
+    result
+}
+
+fn single_touch_state_events(device: &Device, time: &TimeVal) -> Vec<InputEvent> {
+    let mut result = vec![];
+    for code in &[EV_ABS::ABS_X, EV_ABS::ABS_Y, EV_ABS::ABS_PRESSURE] {
+        let event_code = EventCode::EV_ABS(*code);
+        if let Some(value) = device.event_value(&event_code) {
+            result.push(mk_event(time, event_code, value));
+        }
+    }
+    let btn_touch = EventCode::EV_KEY(EV_KEY::BTN_TOUCH);
+    let touching = device.event_value(&btn_touch).unwrap_or(0);
+    result.push(mk_event(time, btn_touch, touching));
+    result.push(mk_event(time, EventCode::EV_SYN(SYN_REPORT), 0));
+    result
+}
+
+// Reports the complete touch state of the device.
+fn state_events(
+    device: &Device,
+    protocol: Protocol,
+    slots: usize,
+    time: &TimeVal,
+) -> Vec<InputEvent> {
+    match protocol {
+        Protocol::MultiTouchB => slot_state_events(device, slots, time),
+        // Protocol A reports all contacts in every frame anyway.
+        Protocol::MultiTouchA => vec![],
+        Protocol::SingleTouch => single_touch_state_events(device, time),
+    }
+}
+
+// Ends all touches, e.g. when the device got disconnected.
+fn release_events(protocol: Protocol, slots: usize, time: &TimeVal) -> Vec<InputEvent> {
+    let mut result = vec![];
+    match protocol {
+        Protocol::MultiTouchB => {
+            for slot in 0..slots {
+                result.push(mk_event(
+                    time,
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT),
+                    slot as i32,
+                ));
+                result.push(mk_event(
+                    time,
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID),
+                    -1,
+                ));
+            }
+        }
+        // An empty frame means that there are no contacts.
+        Protocol::MultiTouchA => {}
+        Protocol::SingleTouch => {
+            result.push(mk_event(time, EventCode::EV_KEY(EV_KEY::BTN_TOUCH), 0));
+        }
+    }
+    result.push(mk_event(time, EventCode::EV_SYN(SYN_REPORT), 0));
+    result
+}
+
+// Waits until the device has events to read or the timeout is over.
+fn wait_for_events(device: &Device, timeout: Duration) {
+    if let Some(file) = device.fd() {
+        // Device::fd hands out the file descriptor owned by the device, so
+        // it must not be closed here.
+        let fd = file.into_raw_fd();
+        let _ = poll(
+            &mut [PollFd::new(fd, PollFlags::POLLIN)],
+            timeout.as_millis() as i32,
+        );
+    }
+}
+
+pub struct InputEventSource {
+    location: DeviceLocation,
+    // Whether the device is grabbed exclusively. Otherwise other programs
+    // (including the desktop) still receive the touch events.
+    grab: bool,
+    shutdown: Shutdown,
+    device: Option<Device>,
+    device_info: DeviceInfo,
+    slots: usize,
+    pending: VecDeque<InputEvent>,
+}
+
+impl InputEventSource {
+    pub fn new(
+        location: DeviceLocation,
+        grab: bool,
+        shutdown: Shutdown,
+    ) -> Result<InputEventSource, ErrorString> {
+        let (device, device_info, slots) = location.open(grab)?;
+        Ok(InputEventSource {
+            location,
+            grab,
+            shutdown,
+            device: Some(device),
+            device_info,
+            slots,
+            pending: VecDeque::new(),
+        })
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.device_info
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    // After a SYN_DROPPED libevdev hands out the events that bring its own
+    // state up to date. Instead of relaying those we end the dropped chunk with
+    // a SYN_REPORT and then report the complete state of all slots.
+    fn resync(&mut self, device: &Device, time: &TimeVal) {
+        while let Ok((ReadStatus::Sync, _)) = device.next_event(ReadFlag::SYNC) {}
+        self.pending
+            .push_back(mk_event(time, EventCode::EV_SYN(SYN_REPORT), 0));
+        self.pending.extend(state_events(
+            device,
+            self.device_info.protocol,
+            self.slots,
+            time,
+        ));
+    }
+
+    fn disconnect(&mut self, error: ::std::io::Error) {
+        eprintln!("touch device lost: {}", error);
+        self.device = None;
+        self.pending.extend(release_events(
+            self.device_info.protocol,
+            self.slots,
+            &now(),
+        ));
+    }
+
+    // Returns false if a shutdown was requested while waiting.
+    fn reconnect(&mut self) -> bool {
+        eprintln!("waiting for the touch device to reappear...");
+        loop {
+            sleep(RECONNECT_INTERVAL);
+            if self.shutdown.is_requested() {
+                return false;
+            }
+            if let Ok((device, device_info, slots)) = self.location.open(self.grab) {
+                if device_info != self.device_info || slots != self.slots {
+                    eprintln!(
+                        "warning: reconnected touch device differs from the original one: {:?}",
+                        device_info
+                    );
+                }
+                eprintln!("touch device reconnected");
+                self.pending.extend(state_events(
+                    &device,
+                    self.device_info.protocol,
+                    self.slots,
+                    &now(),
+                ));
+                self.device = Some(device);
+                return true;
+            }
+        }
+    }
+}
+
+impl Drop for InputEventSource {
+    fn drop(&mut self) {
+        match &mut self.device {
+            Some(device) if self.grab => {
+                if let Err(e) = device.grab(GrabMode::Ungrab) {
+                    eprintln!("can't release the touch device: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Iterator for InputEventSource {
+    type Item = InputEvent;
+
+    fn next(&mut self) -> Option<InputEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            if self.shutdown.is_requested() {
+                return None;
+            }
+            let device = match self.device.take() {
+                None => {
+                    if !self.reconnect() {
+                        return None;
+                    }
+                    continue;
+                }
+                Some(device) => device,
+            };
+            match device.next_event(ReadFlag::NORMAL) {
+                Ok((ReadStatus::Success, event)) => {
+                    self.device = Some(device);
+                    return Some(event);
+                }
+                Ok((ReadStatus::Sync, event)) => {
+                    self.resync(&device, &event.time);
+                    self.device = Some(device);
+                    return Some(event);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    wait_for_events(&device, POLL_INTERVAL);
+                    self.device = Some(device);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {
+                    self.device = Some(device);
+                }
+                Err(e) => self.disconnect(e),
+            }
+        }
+    }
+}
+
+pub struct SynChunkSource {
+    input_event_source: Box<dyn Iterator<Item = InputEvent>>,
+}
+
+impl SynChunkSource {
+    pub fn new(input_event_source: impl Iterator<Item = InputEvent> + 'static) -> SynChunkSource {
+        SynChunkSource {
+            input_event_source: Box::new(input_event_source),
+        }
+    }
+}
+
+impl SynChunkSource {
+    fn skip_until_syn_report(&mut self) {
+        for event in self.input_event_source.by_ref() {
+            if is_syn_report_event(&event) {
+                break;
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for SynChunkSource {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "<SynChunkSource>")
+    }
+}
+
+fn is_syn_dropped_event(event: &InputEvent) -> bool {
+    match event.event_type {
+        EV_SYN => matches!(event.event_code, EventCode::EV_SYN(SYN_DROPPED)),
+        _ => false,
+    }
+}
+
+fn is_syn_report_event(event: &InputEvent) -> bool {
+    match event.event_type {
+        EV_SYN => matches!(event.event_code, EventCode::EV_SYN(SYN_REPORT)),
+        _ => false,
+    }
+}
+
+impl Iterator for SynChunkSource {
+    type Item = Vec<InputEvent>;
+
+    fn next(&mut self) -> Option<Vec<InputEvent>> {
+        let mut result = vec![];
+        loop {
+            match self.input_event_source.next() {
+                None => {
+                    if result.is_empty() {
+                        return None;
+                    } else {
+                        break;
+                    }
+                }
+                Some(event) => {
+                    if is_syn_dropped_event(&event) {
+                        eprintln!("SynChunkSource: dropped events, resynchronizing");
+                        result.clear();
+                        self.skip_until_syn_report();
+                    } else if is_syn_report_event(&event) {
+                        break;
+                    } else {
+                        result.push(event);
+                    }
+                }
+            }
+        }
+        Some(result)
+    }
+}
+
+fn event_time(event: &InputEvent) -> Duration {
+    Duration::new(event.time.tv_sec as u64, event.time.tv_usec as u32 * 1000)
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+// Pressure and size of a contact, normalized to the range from 0 to 1.
+// Devices that don't report these axes leave them empty.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Contact {
+    pub pressure: Option<f32>,
+    pub size: Option<f32>,
+}
+
+impl Contact {
+    pub fn intensity(&self) -> f32 {
+        self.pressure.or(self.size).unwrap_or(1.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SlotState {
+    tracking_id: i32,
+    position: Position,
+    pressure: Option<i32>,
+    touch_major: Option<i32>,
+    width_major: Option<i32>,
+    btn_touch: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TouchState {
+    NoTouch {
+        tracking_id: i32,
+    },
+    Touch {
+        tracking_id: i32,
+        position: Position,
+        contact: Contact,
+    },
+}
+
+// A contact as reported by multitouch protocol A.
+#[derive(Debug, Clone, Default)]
+struct ContactReport {
+    tracking_id: Option<i32>,
+    position: Position,
+    pressure: Option<i32>,
+    touch_major: Option<i32>,
+    width_major: Option<i32>,
+}
+
+pub fn squared_distance(a: &Position, b: &Position) -> i64 {
+    let dx = i64::from(a.x - b.x);
+    let dy = i64::from(a.y - b.y);
+    dx * dx + dy * dy
+}
+
+#[derive(Debug)]
+struct TouchStateChunkSource {
+    syn_chunk_source: SynChunkSource,
+    device_info: DeviceInfo,
+    slots: Vec<SlotState>,
+    active_slot: usize,
+    // Used for protocols that don't report tracking ids.
+    next_tracking_id: i32,
+}
+
+impl TouchStateChunkSource {
+    fn new(
+        syn_chunk_source: SynChunkSource,
+        device_info: DeviceInfo,
+        slots: usize,
+    ) -> TouchStateChunkSource {
+        TouchStateChunkSource {
+            syn_chunk_source,
+            device_info,
+            slots: vec![
+                SlotState {
+                    tracking_id: 0,
+                    position: Position { x: 0, y: 0 },
+                    pressure: None,
+                    touch_major: None,
+                    width_major: None,
+                    btn_touch: false,
+                };
+                slots
+            ],
+            active_slot: 0,
+            next_tracking_id: 0,
+        }
+    }
+
+    fn new_tracking_id(&mut self) -> i32 {
+        let tracking_id = self.next_tracking_id;
+        self.next_tracking_id = self.next_tracking_id.wrapping_add(1);
+        tracking_id
+    }
+
+    fn process_chunk(&mut self, chunk: Vec<InputEvent>) -> Vec<bool> {
+        match self.device_info.protocol {
+            Protocol::MultiTouchB => self.process_slot_chunk(chunk),
+            Protocol::MultiTouchA => self.process_contact_chunk(chunk),
+            Protocol::SingleTouch => self.process_single_touch_chunk(chunk),
+        }
+    }
+
+    fn process_slot_chunk(&mut self, chunk: Vec<InputEvent>) -> Vec<bool> {
+        let mut changed = vec![false; self.slots.len()];
+        for event in chunk {
+            if let EV_ABS = event.event_type {
+                match event.event_code {
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT) => {
+                        if event.value < self.slots.len() as i32 {
+                            self.active_slot = event.value as usize
+                        };
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X) => {
+                        changed[self.active_slot] = true;
+                        self.slots[self.active_slot].position.x = event.value;
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y) => {
+                        changed[self.active_slot] = true;
+                        self.slots[self.active_slot].position.y = event.value;
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_PRESSURE) => {
+                        changed[self.active_slot] = true;
+                        self.slots[self.active_slot].pressure = Some(event.value);
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_TOUCH_MAJOR) => {
+                        changed[self.active_slot] = true;
+                        self.slots[self.active_slot].touch_major = Some(event.value);
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_WIDTH_MAJOR) => {
+                        changed[self.active_slot] = true;
+                        self.slots[self.active_slot].width_major = Some(event.value);
+                    }
+                    EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID) => {
+                        changed[self.active_slot] = true;
+                        match event.value {
+                            -1 => {
+                                self.slots[self.active_slot].btn_touch = false;
+                            }
+                            tracking_id => {
+                                self.slots[self.active_slot].tracking_id = tracking_id;
+                                self.slots[self.active_slot].btn_touch = true;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        changed
+    }
+
+    // Protocol A reports all current contacts in every chunk, so contacts
+    // missing from a chunk have been released. Contacts are kept in the slot
+    // with the same tracking id or, if the device doesn't report tracking ids,
+    // in the slot with the closest previous position.
+    fn process_contact_chunk(&mut self, chunk: Vec<InputEvent>) -> Vec<bool> {
+        let mut contacts = vec![];
+        let mut current: Option<ContactReport> = None;
+        for event in chunk {
+            match event.event_code {
+                EventCode::EV_SYN(SYN_MT_REPORT) => contacts.extend(current.take()),
+                EventCode::EV_ABS(code) => {
+                    let contact = current.get_or_insert_with(ContactReport::default);
+                    match code {
+                        EV_ABS::ABS_MT_TRACKING_ID => contact.tracking_id = Some(event.value),
+                        EV_ABS::ABS_MT_POSITION_X => contact.position.x = event.value,
+                        EV_ABS::ABS_MT_POSITION_Y => contact.position.y = event.value,
+                        EV_ABS::ABS_MT_PRESSURE => contact.pressure = Some(event.value),
+                        EV_ABS::ABS_MT_TOUCH_MAJOR => contact.touch_major = Some(event.value),
+                        EV_ABS::ABS_MT_WIDTH_MAJOR => contact.width_major = Some(event.value),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut assigned: Vec<Option<ContactReport>> = vec![None; self.slots.len()];
+        for contact in contacts {
+            if let Some(slot) = self.find_slot(&contact, &assigned) {
+                assigned[slot] = Some(contact);
+            }
+        }
+        let mut changed = vec![false; self.slots.len()];
+        for (slot, contact) in assigned.into_iter().enumerate() {
+            match contact {
+                Some(contact) => {
+                    if !self.slots[slot].btn_touch {
+                        self.slots[slot].tracking_id = match contact.tracking_id {
+                            Some(tracking_id) => tracking_id,
+                            None => self.new_tracking_id(),
+                        };
+                    }
+                    let slot_state = &mut self.slots[slot];
+                    slot_state.position = contact.position;
+                    slot_state.pressure = contact.pressure;
+                    slot_state.touch_major = contact.touch_major;
+                    slot_state.width_major = contact.width_major;
+                    slot_state.btn_touch = true;
+                    changed[slot] = true;
+                }
+                None => {
+                    if self.slots[slot].btn_touch {
+                        self.slots[slot].btn_touch = false;
+                        changed[slot] = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    fn find_slot(
+        &self,
+        contact: &ContactReport,
+        assigned: &[Option<ContactReport>],
+    ) -> Option<usize> {
+        let available = |slot: &usize| assigned[*slot].is_none();
+        let mut active = (0..self.slots.len())
+            .filter(available)
+            .filter(|slot| self.slots[*slot].btn_touch);
+        let continued = match contact.tracking_id {
+            Some(tracking_id) => active.find(|slot| self.slots[*slot].tracking_id == tracking_id),
+            None => active.min_by_key(|slot| {
+                squared_distance(&self.slots[*slot].position, &contact.position)
+            }),
+        };
+        continued.or_else(|| {
+            (0..self.slots.len())
+                .filter(available)
+                .find(|slot| !self.slots[*slot].btn_touch)
+        })
+    }
+
+    // Single touch devices are treated like multitouch devices with only
+    // one slot.
+    fn process_single_touch_chunk(&mut self, chunk: Vec<InputEvent>) -> Vec<bool> {
+        let mut changed = vec![false; self.slots.len()];
+        for event in chunk {
+            match event.event_code {
+                EventCode::EV_ABS(EV_ABS::ABS_X) => self.slots[0].position.x = event.value,
+                EventCode::EV_ABS(EV_ABS::ABS_Y) => self.slots[0].position.y = event.value,
+                EventCode::EV_ABS(EV_ABS::ABS_PRESSURE) => {
+                    self.slots[0].pressure = Some(event.value);
+                }
+                EventCode::EV_KEY(EV_KEY::BTN_TOUCH) => {
+                    let touching = event.value != 0;
+                    if touching && !self.slots[0].btn_touch {
+                        self.slots[0].tracking_id = self.new_tracking_id();
+                    }
+                    self.slots[0].btn_touch = touching;
+                }
+                _ => continue,
+            }
+            changed[0] = true;
+        }
+        changed
+    }
+
+    fn contact(&self, slot_state: &SlotState) -> Contact {
+        let normalize = |axis: &Option<AxisInfo>, value: Option<i32>| match (axis, value) {
+            (Some(axis), Some(value)) => Some(axis.normalize(value)),
+            _ => None,
+        };
+        Contact {
+            pressure: normalize(&self.device_info.pressure, slot_state.pressure),
+            size: normalize(&self.device_info.touch_major, slot_state.touch_major)
+                .or_else(|| normalize(&self.device_info.width_major, slot_state.width_major)),
+        }
+    }
+
+    fn get_touch_state_chunk(&self, previous: &[SlotState], changed: Vec<bool>) -> Vec<TouchState> {
+        let mut result = vec![];
+        for (slot, changed) in changed.iter().enumerate() {
+            if *changed {
+                let previous = &previous[slot];
+                let slot_state = &self.slots[slot];
+                if previous.btn_touch
+                    && (!slot_state.btn_touch || previous.tracking_id != slot_state.tracking_id)
+                {
+                    result.push(TouchState::NoTouch {
+                        tracking_id: previous.tracking_id,
+                    });
+                }
+                if slot_state.btn_touch {
+                    let origin = self.device_info.origin();
+                    result.push(TouchState::Touch {
+                        tracking_id: slot_state.tracking_id,
+                        position: Position {
+                            x: slot_state.position.x - origin.x,
+                            y: slot_state.position.y - origin.y,
+                        },
+                        contact: self.contact(slot_state),
+                    });
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Iterator for TouchStateChunkSource {
+    type Item = Vec<(Duration, TouchState)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.syn_chunk_source.next() {
+            None => None,
+            Some(chunk) => {
+                let time = chunk.first().map(event_time).unwrap_or_default();
+                let previous = self.slots.clone();
+                let changed = self.process_chunk(chunk);
+                Some(
+                    self.get_touch_state_chunk(&previous, changed)
+                        .into_iter()
+                        .map(|touch_state| (time, touch_state))
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+pub struct TouchStateSource(Flatten<TouchStateChunkSource>);
+
+impl TouchStateSource {
+    fn from_syn_chunk_source(
+        syn_chunk_source: SynChunkSource,
+        device_info: DeviceInfo,
+        slots: usize,
+    ) -> TouchStateSource {
+        TouchStateSource(TouchStateChunkSource::new(syn_chunk_source, device_info, slots).flatten())
+    }
+
+    pub fn new(input_event_source: InputEventSource) -> TouchStateSource {
+        let device_info = input_event_source.device_info().clone();
+        let slots = input_event_source.slots();
+        TouchStateSource::from_syn_chunk_source(
+            SynChunkSource::new(input_event_source),
+            device_info,
+            slots,
+        )
+    }
+
+    pub fn timed(self) -> impl Iterator<Item = (Duration, TouchState)> {
+        self.0
+    }
+}
+
+impl Iterator for TouchStateSource {
+    type Item = TouchState;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, touch_state)| touch_state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TouchState::*;
+    use super::*;
+    use ::evdev_rs::enums::{EventCode, EventType, EV_ABS::*};
+    use ::evdev_rs::TimeVal;
+
+    fn mk_input_event(event_type: EventType, event_code: EventCode, value: i32) -> InputEvent {
+        InputEvent {
+            time: TimeVal {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            event_type,
+            event_code,
+            value,
+        }
+    }
+
+    fn axis_info(minimum: i32, maximum: i32) -> AxisInfo {
+        AxisInfo { minimum, maximum }
+    }
+
+    fn device_info(origin: Position) -> DeviceInfo {
+        DeviceInfo {
+            protocol: Protocol::MultiTouchB,
+            x: axis_info(origin.x, origin.x + 4000),
+            y: axis_info(origin.y, origin.y + 4000),
+            pressure: None,
+            touch_major: None,
+            width_major: None,
+        }
+    }
+
+    fn touch_states(vec: Vec<InputEvent>) -> TouchStateSource {
+        TouchStateSource::from_syn_chunk_source(
+            SynChunkSource::new(vec.into_iter()),
+            device_info(Position { x: 0, y: 0 }),
+            DEFAULT_SLOTS,
+        )
+    }
+
+    mod syn_chunks {
+        use super::*;
+
+        #[test]
+        fn groups_events_until_ev_syn() {
+            let vec = vec![
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 2),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+            ];
+            assert_eq!(
+                SynChunkSource::new(vec.into_iter()).next(),
+                Some(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 2),
+                ])
+            );
+        }
+
+        #[test]
+        fn bundles_subsequent_chunks_correctly() {
+            let vec = vec![
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                //
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 2),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+            ];
+            let mut syn_chunks = SynChunkSource::new(vec.into_iter());
+            syn_chunks.next();
+            assert_eq!(
+                syn_chunks.next(),
+                Some(vec![mk_input_event(
+                    EV_ABS,
+                    EventCode::EV_ABS(ABS_MT_SLOT),
+                    2
+                )])
+            );
+        }
+
+        #[test]
+        fn handles_terminating_streams_gracefully() {
+            let vec = vec![mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1)];
+            let mut syn_chunks = SynChunkSource::new(vec.into_iter());
+            assert_eq!(
+                syn_chunks.next(),
+                Some(vec![mk_input_event(
+                    EV_ABS,
+                    EventCode::EV_ABS(ABS_MT_SLOT),
+                    1
+                )])
+            );
+            assert_eq!(syn_chunks.next(), None);
+            assert_eq!(syn_chunks.next(), None);
+        }
+
+        #[test]
+        fn discards_events_around_syn_dropped_until_the_next_syn_report() {
+            let vec = vec![
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_DROPPED), 0),
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 3),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                //
+                mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 2),
+                mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+            ];
+            let mut syn_chunks = SynChunkSource::new(vec.into_iter());
+            assert_eq!(
+                syn_chunks.next(),
+                Some(vec![mk_input_event(
+                    EV_ABS,
+                    EventCode::EV_ABS(ABS_MT_SLOT),
+                    2
+                )])
+            );
+            assert_eq!(syn_chunks.next(), None);
+        }
+    }
+
+    mod input_event_source {
+        use super::*;
+        use crate::evdev::uinput::VirtualTouchscreen;
+
+        fn open(
+            touchscreen: &VirtualTouchscreen,
+            grab: bool,
+            shutdown: Shutdown,
+        ) -> Result<InputEventSource, ErrorString> {
+            InputEventSource::new(DeviceLocation::Path(touchscreen.path()), grab, shutdown)
+        }
+
+        // Needs /dev/uinput, run with `just uinput_test`.
+        #[test]
+        #[ignore]
+        fn stops_when_a_shutdown_is_requested() {
+            let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+            let shutdown = Shutdown::new();
+            let mut input_event_source = open(&touchscreen, true, shutdown.clone()).unwrap();
+            shutdown.request();
+            assert!(input_event_source.next().is_none());
+        }
+
+        // Needs /dev/uinput, run with `just uinput_test`.
+        #[test]
+        #[ignore]
+        fn grabs_the_device_exclusively() {
+            let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+            let _grabbed = open(&touchscreen, true, Shutdown::new()).unwrap();
+            assert!(open(&touchscreen, true, Shutdown::new()).is_err());
+        }
+
+        // Needs /dev/uinput, run with `just uinput_test`.
+        #[test]
+        #[ignore]
+        fn allows_to_share_the_device() {
+            let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+            let _shared = open(&touchscreen, false, Shutdown::new()).unwrap();
+            assert!(open(&touchscreen, true, Shutdown::new()).is_ok());
+        }
+
+        // Needs /dev/uinput, run with `just uinput_test`.
+        #[test]
+        #[ignore]
+        fn releases_the_device_when_dropped() {
+            let touchscreen = VirtualTouchscreen::new_or_fail(800, 600);
+            drop(open(&touchscreen, true, Shutdown::new()).unwrap());
+            assert!(open(&touchscreen, true, Shutdown::new()).is_ok());
+        }
+    }
+
+    mod touch_states {
+        use super::*;
+
+        mod slot_zero {
+            use super::*;
+
+            #[test]
+            fn relays_a_position() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![Touch {
+                        tracking_id: 0,
+                        position: Position { x: 23, y: 42 },
+                        contact: Contact::default()
+                    }]
+                );
+            }
+
+            mod tracking_ids {
+                use super::*;
+
+                #[test]
+                fn touch_events_includes_the_correct_tracking_id() {
+                    let touch_states = touch_states(vec![
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 42),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 0),
+                        mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    ]);
+                    assert_eq!(
+                        touch_states.collect::<Vec<TouchState>>(),
+                        vec![Touch {
+                            tracking_id: 42,
+                            position: Position { x: 0, y: 0 },
+                            contact: Contact::default()
+                        },]
+                    );
+                }
+
+                #[test]
+                fn no_touch_events_includes_the_correct_tracking_id() {
+                    let touch_states = touch_states(vec![
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 42),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 0),
+                        mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                        //
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                        mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    ]);
+                    assert_eq!(
+                        touch_states.collect::<Vec<TouchState>>(),
+                        vec![
+                            Touch {
+                                tracking_id: 42,
+                                position: Position { x: 0, y: 0 },
+                                contact: Contact::default(),
+                            },
+                            NoTouch { tracking_id: 42 },
+                        ]
+                    );
+                }
+            }
+
+            #[test]
+            fn relays_following_positions() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 51),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 84),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 51, y: 84 },
+                            contact: Contact::default()
+                        }
+                    ]
+                );
+            }
+
+            #[test]
+            fn handles_syn_chunks_without_y() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 51),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default(),
+                        },
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 51, y: 42 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn handles_syn_chunks_without_x() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 84),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default(),
+                        },
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 84 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn recognizes_touch_releases() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default(),
+                        },
+                        NoTouch { tracking_id: 0 },
+                    ]
+                );
+            }
+
+            #[test]
+            fn treats_note_on_events_in_other_slots_correctly() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 1000),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 1000),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 51),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 84),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        Touch {
+                            tracking_id: 1,
+                            position: Position { x: 1000, y: 1000 },
+                            contact: Contact::default()
+                        },
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 51, y: 84 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn treats_note_off_events_in_other_slots_correctly() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 1000),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 1000),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 51),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 84),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default(),
+                        },
+                        Touch {
+                            tracking_id: 1,
+                            position: Position { x: 1000, y: 1000 },
+                            contact: Contact::default(),
+                        },
+                        NoTouch { tracking_id: 1 },
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 51, y: 84 },
+                            contact: Contact::default(),
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn assumes_slot_zero_as_active_at_startup() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![Touch {
+                        tracking_id: 0,
+                        position: Position { x: 23, y: 42 },
+                        contact: Contact::default()
+                    },]
+                );
+            }
+
+            #[test]
+            fn tracks_slot_changes_and_touch_releases_in_the_same_syn_chunk_correctly() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 1000),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 1000),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 0,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        NoTouch { tracking_id: 0 },
+                        Touch {
+                            tracking_id: 1,
+                            position: Position { x: 1000, y: 1000 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+        }
+
+        mod resynchronization {
+            use super::*;
+
+            fn touch_in_slot_zero(tracking_id: i32) -> Vec<InputEvent> {
+                vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), tracking_id),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]
+            }
+
+            fn syn_dropped() -> Vec<InputEvent> {
+                vec![
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_DROPPED), 0),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]
+            }
+
+            #[test]
+            fn releases_touches_that_ended_while_events_were_dropped() {
+                let touch_states = touch_states(
+                    vec![
+                        touch_in_slot_zero(5),
+                        syn_dropped(),
+                        vec![
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                            mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                        ],
+                    ]
+                    .concat(),
+                );
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 5,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        NoTouch { tracking_id: 5 },
+                    ]
+                );
+            }
+
+            #[test]
+            fn replaces_touches_that_changed_while_events_were_dropped() {
+                let touch_states = touch_states(
+                    vec![touch_in_slot_zero(5), syn_dropped(), touch_in_slot_zero(7)].concat(),
+                );
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 5,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        NoTouch { tracking_id: 5 },
+                        Touch {
+                            tracking_id: 7,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn keeps_touches_that_continued_while_events_were_dropped() {
+                let touch_states = touch_states(
+                    vec![touch_in_slot_zero(5), syn_dropped(), touch_in_slot_zero(5)].concat(),
+                );
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 5,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        Touch {
+                            tracking_id: 5,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn ignores_releases_of_inactive_slots() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 3),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(touch_states.collect::<Vec<TouchState>>(), vec![]);
+            }
+
+            #[test]
+            fn releases_all_touches_when_the_device_is_lost() {
+                let time = TimeVal {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                };
+                let touch_states = touch_states(
+                    vec![
+                        touch_in_slot_zero(5),
+                        release_events(Protocol::MultiTouchB, DEFAULT_SLOTS, &time),
+                    ]
+                    .concat(),
+                );
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 5,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default()
+                        },
+                        NoTouch { tracking_id: 5 },
+                    ]
+                );
+            }
+        }
+
+        #[test]
+        fn translates_positions_relative_to_the_device_origin() {
+            let touch_states = TouchStateSource::from_syn_chunk_source(
+                SynChunkSource::new(
+                    vec![
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 123),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 142),
+                        mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    ]
+                    .into_iter(),
+                ),
+                device_info(Position { x: 100, y: 100 }),
+                DEFAULT_SLOTS,
+            );
+            assert_eq!(
+                touch_states.collect::<Vec<TouchState>>(),
+                vec![Touch {
+                    tracking_id: 0,
+                    position: Position { x: 23, y: 42 },
+                    contact: Contact::default()
+                }]
+            );
+        }
+
+        #[test]
+        fn includes_the_time_of_the_input_events() {
+            let touch_states = touch_states(
+                vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]
+                .into_iter()
+                .map(|event| InputEvent {
+                    time: TimeVal {
+                        tv_sec: 3,
+                        tv_usec: 500,
+                    },
+                    ..event
+                })
+                .collect(),
+            );
+            assert_eq!(
+                touch_states
+                    .timed()
+                    .collect::<Vec<(Duration, TouchState)>>(),
+                vec![(
+                    Duration::new(3, 500_000),
+                    Touch {
+                        tracking_id: 0,
+                        position: Position { x: 23, y: 42 },
+                        contact: Contact::default()
+                    }
+                )]
+            );
+        }
+
+        mod other_slots {
+            use super::*;
+
+            #[test]
+            fn relays_a_position_for_other_slots() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![Touch {
+                        tracking_id: 1,
+                        position: Position { x: 23, y: 42 },
+                        contact: Contact::default()
+                    }]
+                );
+            }
+
+            #[test]
+            fn note_off_events_contain_the_slot() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 1),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                    //
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), -1),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![
+                        Touch {
+                            tracking_id: 1,
+                            position: Position { x: 23, y: 42 },
+                            contact: Contact::default(),
+                        },
+                        NoTouch { tracking_id: 1 }
+                    ]
+                );
+            }
+
+            #[test]
+            fn supports_devices_with_more_slots() {
+                let touch_states = TouchStateSource::from_syn_chunk_source(
+                    SynChunkSource::new(
+                        vec![
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 12),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 12),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                            mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                        ]
+                        .into_iter(),
+                    ),
+                    device_info(Position { x: 0, y: 0 }),
+                    16,
+                );
+                assert_eq!(
+                    touch_states.collect::<Vec<TouchState>>(),
+                    vec![Touch {
+                        tracking_id: 12,
+                        position: Position { x: 23, y: 42 },
+                        contact: Contact::default()
+                    }]
+                );
+            }
+
+            #[test]
+            fn handles_out_of_bound_slots_gracefully() {
+                let touch_states = touch_states(vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 1000),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                ]);
+                let _ = touch_states.collect::<Vec<TouchState>>();
+            }
+        }
+
+        fn protocol_touch_states(protocol: Protocol, events: Vec<InputEvent>) -> Vec<TouchState> {
+            TouchStateSource::from_syn_chunk_source(
+                SynChunkSource::new(events.into_iter()),
+                DeviceInfo {
+                    protocol,
+                    ..device_info(Position { x: 0, y: 0 })
+                },
+                DEFAULT_SLOTS,
+            )
+            .collect()
+        }
+
+        fn touch(tracking_id: i32, x: i32, y: i32) -> TouchState {
+            Touch {
+                tracking_id,
+                position: Position { x, y },
+                contact: Contact::default(),
+            }
+        }
+
+        fn syn_report() -> InputEvent {
+            mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0)
+        }
+
+        mod protocol_a {
+            use super::*;
+
+            fn contact(x: i32, y: i32) -> Vec<InputEvent> {
+                vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), x),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), y),
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_MT_REPORT), 0),
+                ]
+            }
+
+            fn empty_frame() -> Vec<InputEvent> {
+                vec![
+                    mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_MT_REPORT), 0),
+                    syn_report(),
+                ]
+            }
+
+            #[test]
+            fn relays_all_reported_contacts() {
+                let events = vec![contact(10, 20), contact(30, 40), vec![syn_report()]].concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::MultiTouchA, events),
+                    vec![touch(0, 10, 20), touch(1, 30, 40)]
+                );
+            }
+
+            #[test]
+            fn keeps_tracking_ids_of_the_closest_contacts() {
+                let events = vec![
+                    contact(10, 20),
+                    contact(30, 40),
+                    vec![syn_report()],
+                    contact(32, 41),
+                    contact(11, 22),
+                    vec![syn_report()],
+                ]
+                .concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::MultiTouchA, events),
+                    vec![
+                        touch(0, 10, 20),
+                        touch(1, 30, 40),
+                        touch(0, 11, 22),
+                        touch(1, 32, 41),
+                    ]
+                );
+            }
+
+            #[test]
+            fn uses_reported_tracking_ids() {
+                let events = vec![
+                    vec![mk_input_event(
+                        EV_ABS,
+                        EventCode::EV_ABS(ABS_MT_TRACKING_ID),
+                        7,
+                    )],
+                    contact(10, 20),
+                    vec![syn_report()],
+                ]
+                .concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::MultiTouchA, events),
+                    vec![touch(7, 10, 20)]
+                );
+            }
+
+            #[test]
+            fn releases_contacts_missing_from_a_frame() {
+                let events = vec![
+                    contact(10, 20),
+                    contact(30, 40),
+                    vec![syn_report()],
+                    contact(30, 40),
+                    vec![syn_report()],
+                    empty_frame(),
+                ]
+                .concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::MultiTouchA, events),
+                    vec![
+                        touch(0, 10, 20),
+                        touch(1, 30, 40),
+                        NoTouch { tracking_id: 0 },
+                        touch(1, 30, 40),
+                        NoTouch { tracking_id: 1 },
+                    ]
+                );
+            }
+        }
+
+        mod single_touch {
+            use super::*;
+
+            fn btn_touch(value: i32) -> InputEvent {
+                mk_input_event(EV_KEY, EventCode::EV_KEY(EV_KEY::BTN_TOUCH), value)
+            }
+
+            fn position(x: i32, y: i32) -> Vec<InputEvent> {
+                vec![
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_X), x),
+                    mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_Y), y),
+                ]
+            }
+
+            #[test]
+            fn relays_touches_and_releases() {
+                let events = vec![
+                    vec![btn_touch(1)],
+                    position(10, 20),
+                    vec![syn_report()],
+                    position(15, 25),
+                    vec![syn_report(), btn_touch(0), syn_report()],
+                ]
+                .concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::SingleTouch, events),
+                    vec![
+                        touch(0, 10, 20),
+                        touch(0, 15, 25),
+                        NoTouch { tracking_id: 0 },
+                    ]
+                );
+            }
+
+            #[test]
+            fn uses_new_tracking_ids_for_following_touches() {
+                let events = vec![
+                    vec![btn_touch(1)],
+                    position(10, 20),
+                    vec![syn_report(), btn_touch(0), syn_report(), btn_touch(1)],
+                    position(30, 40),
+                    vec![syn_report()],
+                ]
+                .concat();
+                assert_eq!(
+                    protocol_touch_states(Protocol::SingleTouch, events),
+                    vec![
+                        touch(0, 10, 20),
+                        NoTouch { tracking_id: 0 },
+                        touch(1, 30, 40),
+                    ]
+                );
+            }
+
+            #[test]
+            fn ignores_movements_without_contact() {
+                let events = vec![position(10, 20), vec![syn_report()]].concat();
+                assert_eq!(protocol_touch_states(Protocol::SingleTouch, events), vec![]);
+            }
+        }
+
+        mod contact {
+            use super::*;
+
+            fn contact_touch_states(
+                device_info: DeviceInfo,
+                contact_events: Vec<InputEvent>,
+            ) -> Vec<TouchState> {
+                let events = vec![
+                    vec![
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_SLOT), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TRACKING_ID), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_X), 23),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_POSITION_Y), 42),
+                    ],
+                    contact_events,
+                    vec![mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0)],
+                ]
+                .concat();
+                TouchStateSource::from_syn_chunk_source(
+                    SynChunkSource::new(events.into_iter()),
+                    device_info,
+                    DEFAULT_SLOTS,
+                )
+                .collect()
+            }
+
+            fn touch_with(contact: Contact) -> TouchState {
+                Touch {
+                    tracking_id: 0,
+                    position: Position { x: 23, y: 42 },
+                    contact,
+                }
+            }
+
+            #[test]
+            fn normalizes_the_pressure() {
+                let device_info = DeviceInfo {
+                    pressure: Some(axis_info(0, 200)),
+                    ..device_info(Position { x: 0, y: 0 })
+                };
+                assert_eq!(
+                    contact_touch_states(
+                        device_info,
+                        vec![mk_input_event(
+                            EV_ABS,
+                            EventCode::EV_ABS(ABS_MT_PRESSURE),
+                            50
+                        )]
+                    ),
+                    vec![touch_with(Contact {
+                        pressure: Some(0.25),
+                        size: None,
+                    })]
+                );
+            }
+
+            #[test]
+            fn uses_the_touch_major_axis_as_size() {
+                let device_info = DeviceInfo {
+                    touch_major: Some(axis_info(0, 100)),
+                    width_major: Some(axis_info(0, 10)),
+                    ..device_info(Position { x: 0, y: 0 })
+                };
+                assert_eq!(
+                    contact_touch_states(
+                        device_info,
+                        vec![
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_TOUCH_MAJOR), 30),
+                            mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_WIDTH_MAJOR), 5),
+                        ]
+                    ),
+                    vec![touch_with(Contact {
+                        pressure: None,
+                        size: Some(0.3),
+                    })]
+                );
+            }
+
+            #[test]
+            fn falls_back_to_the_width_major_axis() {
+                let device_info = DeviceInfo {
+                    width_major: Some(axis_info(0, 10)),
+                    ..device_info(Position { x: 0, y: 0 })
+                };
+                assert_eq!(
+                    contact_touch_states(
+                        device_info,
+                        vec![mk_input_event(
+                            EV_ABS,
+                            EventCode::EV_ABS(ABS_MT_WIDTH_MAJOR),
+                            5
+                        )]
+                    ),
+                    vec![touch_with(Contact {
+                        pressure: None,
+                        size: Some(0.5),
+                    })]
+                );
+            }
+
+            #[test]
+            fn clamps_values_outside_of_the_reported_range() {
+                let device_info = DeviceInfo {
+                    pressure: Some(axis_info(0, 200)),
+                    ..device_info(Position { x: 0, y: 0 })
+                };
+                assert_eq!(
+                    contact_touch_states(
+                        device_info,
+                        vec![mk_input_event(
+                            EV_ABS,
+                            EventCode::EV_ABS(ABS_MT_PRESSURE),
+                            300
+                        )]
+                    ),
+                    vec![touch_with(Contact {
+                        pressure: Some(1.0),
+                        size: None,
+                    })]
+                );
+            }
+
+            #[test]
+            fn ignores_axes_that_the_device_does_not_report() {
+                assert_eq!(
+                    contact_touch_states(
+                        device_info(Position { x: 0, y: 0 }),
+                        vec![mk_input_event(
+                            EV_ABS,
+                            EventCode::EV_ABS(ABS_MT_PRESSURE),
+                            50
+                        )]
+                    ),
+                    vec![touch_with(Contact::default())]
+                );
+            }
+
+            #[test]
+            fn reports_pressure_changes() {
+                let device_info = DeviceInfo {
+                    pressure: Some(axis_info(0, 100)),
+                    ..device_info(Position { x: 0, y: 0 })
+                };
+                let touch_states = contact_touch_states(
+                    device_info,
+                    vec![
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_PRESSURE), 10),
+                        mk_input_event(EV_SYN, EventCode::EV_SYN(SYN_REPORT), 0),
+                        mk_input_event(EV_ABS, EventCode::EV_ABS(ABS_MT_PRESSURE), 80),
+                    ],
+                );
+                assert_eq!(
+                    touch_states,
+                    vec![
+                        touch_with(Contact {
+                            pressure: Some(0.1),
+                            size: None,
+                        }),
+                        touch_with(Contact {
+                            pressure: Some(0.8),
+                            size: None,
+                        }),
+                    ]
+                );
+            }
+
+            #[test]
+            fn uses_the_pressure_as_intensity() {
+                let contact = Contact {
+                    pressure: Some(0.3),
+                    size: Some(0.9),
+                };
+                assert_eq!(contact.intensity(), 0.3);
+                assert_eq!(Contact::default().intensity(), 1.0);
+            }
+        }
+    }
+}
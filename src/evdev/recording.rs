@@ -0,0 +1,358 @@
+use crate::evdev::{AxisInfo, Contact, DeviceInfo, Position, Protocol, TouchState};
+use crate::utils::shutdown::Shutdown;
+use crate::AddMessage;
+use crate::ErrorString;
+use ::std::fs::{self, File};
+use ::std::io::{BufWriter, Write};
+use ::std::thread::sleep;
+use ::std::time::{Duration, Instant};
+
+const HEADER: &str = "# touchscreen-instrument touch recording";
+
+// How often a replay that waits for its next touch checks for a shutdown.
+const SHUTDOWN_INTERVAL: Duration = Duration::from_millis(100);
+
+fn format_touch_state(time: Duration, touch_state: &TouchState) -> String {
+    let time = format!("{}.{:06}", time.as_secs(), time.subsec_micros());
+    match touch_state {
+        TouchState::Touch {
+            tracking_id,
+            position,
+            contact,
+        } => {
+            let mut line = format!(
+                "{} touch {} {} {}",
+                time, tracking_id, position.x, position.y
+            );
+            if let Some(pressure) = contact.pressure {
+                line += &format!(" pressure {}", pressure);
+            }
+            if let Some(size) = contact.size {
+                line += &format!(" size {}", size);
+            }
+            line
+        }
+        TouchState::NoTouch { tracking_id } => format!("{} notouch {}", time, tracking_id),
+    }
+}
+
+fn parse_contact(words: &[&str]) -> Result<Contact, ErrorString> {
+    let parse_value = |word: &str| -> Result<Option<f32>, ErrorString> {
+        let value = word
+            .parse()
+            .map_err(|_| ErrorString(format!("invalid number: {}", word)))?;
+        Ok(Some(value))
+    };
+    let mut contact = Contact::default();
+    for pair in words.chunks(2) {
+        match pair {
+            ["pressure", value] => contact.pressure = parse_value(value)?,
+            ["size", value] => contact.size = parse_value(value)?,
+            _ => return Err(ErrorString(format!("invalid contact: {}", pair.join(" ")))),
+        }
+    }
+    Ok(contact)
+}
+
+fn parse_touch_state(line: &str) -> Result<(Duration, TouchState), ErrorString> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let parse_int = |word: &str| -> Result<i32, ErrorString> {
+        word.parse()
+            .map_err(|_| ErrorString(format!("invalid number: {}", word)))
+    };
+    let touch_state = match words.as_slice() {
+        [_, "touch", tracking_id, x, y, contact @ ..] => TouchState::Touch {
+            tracking_id: parse_int(tracking_id)?,
+            position: Position {
+                x: parse_int(x)?,
+                y: parse_int(y)?,
+            },
+            contact: parse_contact(contact)?,
+        },
+        [_, "notouch", tracking_id] => TouchState::NoTouch {
+            tracking_id: parse_int(tracking_id)?,
+        },
+        _ => return Err(ErrorString(format!("invalid touch: {}", line))),
+    };
+    let time: f64 = words[0].parse()?;
+    if time < 0.0 {
+        return Err(ErrorString(format!("negative time: {}", line)));
+    }
+    Ok((
+        Duration::from_micros((time * 1_000_000.0).round() as u64),
+        touch_state,
+    ))
+}
+
+fn format_dimensions(device_info: &DeviceInfo) -> String {
+    format!("size {} {}", device_info.width(), device_info.height())
+}
+
+fn parse_dimensions(line: &str) -> Result<DeviceInfo, ErrorString> {
+    let axis_info = |size: &str| -> Result<AxisInfo, ErrorString> {
+        Ok(AxisInfo {
+            minimum: 0,
+            maximum: size
+                .parse()
+                .map_err(|_| ErrorString(format!("invalid size: {}", size)))?,
+        })
+    };
+    match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        ["size", width, height] => Ok(DeviceInfo {
+            protocol: Protocol::MultiTouchB,
+            x: axis_info(width)?,
+            y: axis_info(height)?,
+            pressure: None,
+            touch_major: None,
+            width_major: None,
+        }),
+        _ => Err(ErrorString(format!("expected touch dimensions: {}", line))),
+    }
+}
+
+pub struct Recorder<Touches> {
+    touches: Touches,
+    file: BufWriter<File>,
+    start: Option<Duration>,
+    shutdown: Shutdown,
+}
+
+impl<Touches> Recorder<Touches>
+where
+    Touches: Iterator<Item = (Duration, TouchState)>,
+{
+    pub fn new(
+        file: &str,
+        device_info: &DeviceInfo,
+        touches: Touches,
+        shutdown: Shutdown,
+    ) -> Result<Recorder<Touches>, ErrorString> {
+        let mut writer =
+            BufWriter::new(File::create(file).add_message(format!("can't create {}", file))?);
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "{}", format_dimensions(device_info))?;
+        Ok(Recorder {
+            touches,
+            file: writer,
+            start: None,
+            shutdown,
+        })
+    }
+
+    fn write(&mut self, time: Duration, touch_state: &TouchState) -> Result<(), ErrorString> {
+        let start = *self.start.get_or_insert(time);
+        let time = time.checked_sub(start).unwrap_or_default();
+        writeln!(self.file, "{}", format_touch_state(time, touch_state))?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+impl<Touches> Iterator for Recorder<Touches>
+where
+    Touches: Iterator<Item = (Duration, TouchState)>,
+{
+    type Item = TouchState;
+
+    fn next(&mut self) -> Option<TouchState> {
+        if self.shutdown.is_requested() {
+            return None;
+        }
+        let (time, touch_state) = self.touches.next()?;
+        if let Err(e) = self.write(time, &touch_state) {
+            eprintln!("error recording touches: {:?}", e);
+        }
+        Some(touch_state)
+    }
+}
+
+pub struct Replay {
+    device_info: DeviceInfo,
+    touches: ::std::vec::IntoIter<(Duration, TouchState)>,
+    start: Option<Instant>,
+    shutdown: Shutdown,
+}
+
+impl Replay {
+    pub fn load(file: &str, shutdown: Shutdown) -> Result<Replay, ErrorString> {
+        let contents =
+            fs::read_to_string(file).add_message(format!("can't read recording {}", file))?;
+        Replay::parse(&contents, shutdown).add_message(format!("invalid recording {}", file))
+    }
+
+    fn parse(contents: &str, shutdown: Shutdown) -> Result<Replay, ErrorString> {
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+        let device_info = match lines.next() {
+            None => return Err(ErrorString::from("empty recording")),
+            Some((number, line)) => {
+                parse_dimensions(line).add_message(format!("line {}", number + 1))?
+            }
+        };
+        let mut touches = vec![];
+        for (number, line) in lines {
+            touches.push(parse_touch_state(line).add_message(format!("line {}", number + 1))?);
+        }
+        Ok(Replay {
+            device_info,
+            touches: touches.into_iter(),
+            start: None,
+            shutdown,
+        })
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.device_info
+    }
+}
+
+impl Iterator for Replay {
+    type Item = TouchState;
+
+    fn next(&mut self) -> Option<TouchState> {
+        let (time, touch_state) = self.touches.next()?;
+        let start = *self.start.get_or_insert_with(Instant::now);
+        loop {
+            if self.shutdown.is_requested() {
+                return None;
+            }
+            match (start + time).checked_duration_since(Instant::now()) {
+                Some(remaining) => sleep(remaining.min(SHUTDOWN_INTERVAL)),
+                None => return Some(touch_state),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn touch(tracking_id: i32, x: i32, y: i32) -> TouchState {
+        TouchState::Touch {
+            tracking_id,
+            position: Position { x, y },
+            contact: Contact::default(),
+        }
+    }
+
+    mod touch_states {
+        use super::*;
+
+        #[test]
+        fn round_trips_touches() {
+            let touch_state = touch(3, 120, 4500);
+            let time = Duration::new(12, 345_678_000);
+            assert_eq!(
+                parse_touch_state(&format_touch_state(time, &touch_state)).unwrap(),
+                (time, touch_state)
+            );
+        }
+
+        #[test]
+        fn round_trips_pressure_and_size() {
+            let touch_state = TouchState::Touch {
+                tracking_id: 3,
+                position: Position { x: 120, y: 4500 },
+                contact: Contact {
+                    pressure: Some(0.25),
+                    size: Some(0.7),
+                },
+            };
+            let time = Duration::new(1, 0);
+            assert_eq!(
+                parse_touch_state(&format_touch_state(time, &touch_state)).unwrap(),
+                (time, touch_state)
+            );
+        }
+
+        #[test]
+        fn round_trips_releases() {
+            let touch_state = TouchState::NoTouch { tracking_id: 3 };
+            let time = Duration::new(0, 16_000_000);
+            assert_eq!(
+                parse_touch_state(&format_touch_state(time, &touch_state)).unwrap(),
+                (time, touch_state)
+            );
+        }
+
+        #[test]
+        fn rejects_invalid_lines() {
+            assert!(parse_touch_state("0.5 touch 3 120").is_err());
+            assert!(parse_touch_state("0.5 press 3").is_err());
+            assert!(parse_touch_state("foo notouch 3").is_err());
+            assert!(parse_touch_state("-1 notouch 3").is_err());
+            assert!(parse_touch_state("0.5 touch 3 120 4500 pressure").is_err());
+            assert!(parse_touch_state("0.5 touch 3 120 4500 force 0.5").is_err());
+        }
+    }
+
+    mod replay {
+        use super::*;
+
+        #[test]
+        fn reads_the_touch_dimensions() {
+            let replay =
+                Replay::parse(&format!("{}\nsize 800 600\n", HEADER), Shutdown::new()).unwrap();
+            assert_eq!(replay.device_info().width(), 800);
+            assert_eq!(replay.device_info().height(), 600);
+        }
+
+        #[test]
+        fn yields_the_recorded_touches() {
+            let replay = Replay::parse(
+                &format!(
+                    "{}\nsize 800 600\n0.000000 touch 0 10 20\n0.001000 notouch 0\n",
+                    HEADER
+                ),
+                Shutdown::new(),
+            )
+            .unwrap();
+            assert_eq!(
+                replay.collect::<Vec<TouchState>>(),
+                vec![touch(0, 10, 20), TouchState::NoTouch { tracking_id: 0 }]
+            );
+        }
+
+        #[test]
+        fn replays_with_the_recorded_timing() {
+            let replay = Replay::parse(
+                "size 800 600\n0.000000 touch 0 10 20\n0.050000 notouch 0\n",
+                Shutdown::new(),
+            )
+            .unwrap();
+            let start = Instant::now();
+            assert_eq!(replay.count(), 2);
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        }
+
+        #[test]
+        fn stops_when_a_shutdown_is_requested() {
+            let shutdown = Shutdown::new();
+            let mut replay = Replay::parse(
+                "size 800 600\n0.000000 touch 0 10 20\n60.000000 notouch 0\n",
+                shutdown.clone(),
+            )
+            .unwrap();
+            assert_eq!(replay.next(), Some(touch(0, 10, 20)));
+            let start = Instant::now();
+            ::std::thread::spawn(move || {
+                sleep(Duration::from_millis(50));
+                shutdown.request();
+            });
+            assert_eq!(replay.next(), None);
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+
+        #[test]
+        fn reports_line_numbers_in_errors() {
+            let ErrorString(error) =
+                Replay::parse("size 800 600\n\n0.0 touch 0 x 20\n", Shutdown::new())
+                    .err()
+                    .unwrap();
+            assert!(error.contains("line 3"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::evdev::{mk_event, now, Position};
+use ::evdev_rs::enums::{EventCode, EventType, EV_ABS, EV_KEY, EV_SYN};
+use ::evdev_rs::uinput::UInputDevice;
+use ::evdev_rs::{AbsInfo, Device};
+use ::std::path::Path;
+use ::std::thread::sleep;
+use ::std::time::{Duration, Instant};
+
+const SLOTS: i32 = 10;
+
+// A multitouch (protocol B) device created through /dev/uinput, to test the
+// input path against real kernel devices.
+pub struct VirtualTouchscreen {
+    device: UInputDevice,
+    path: String,
+}
+
+fn abs_info(maximum: i32) -> AbsInfo {
+    AbsInfo {
+        value: 0,
+        minimum: 0,
+        maximum,
+        fuzz: 0,
+        flat: 0,
+        resolution: 0,
+    }
+}
+
+impl VirtualTouchscreen {
+    // Returns None if uinput is not available, e.g. when /dev/uinput doesn't
+    // exist or isn't writable.
+    pub fn new(width: i32, height: i32) -> Option<VirtualTouchscreen> {
+        let device = Device::new()?;
+        device.set_name("touchscreen-instrument virtual touchscreen");
+        device.enable(&EventType::EV_KEY).ok()?;
+        device.enable(&EventCode::EV_KEY(EV_KEY::BTN_TOUCH)).ok()?;
+        device.enable(&EventType::EV_ABS).ok()?;
+        for &(code, maximum) in &[
+            (EV_ABS::ABS_X, width),
+            (EV_ABS::ABS_Y, height),
+            (EV_ABS::ABS_MT_SLOT, SLOTS - 1),
+            (EV_ABS::ABS_MT_TRACKING_ID, 0xffff),
+            (EV_ABS::ABS_MT_POSITION_X, width),
+            (EV_ABS::ABS_MT_POSITION_Y, height),
+        ] {
+            device
+                .enable_event_code(&EventCode::EV_ABS(code), Some(&abs_info(maximum)))
+                .ok()?;
+        }
+        let device = UInputDevice::create_from_device(&device).ok()?;
+        let path = device.devnode()?.to_string();
+        let virtual_touchscreen = VirtualTouchscreen { device, path };
+        virtual_touchscreen.wait_for_device_node();
+        Some(virtual_touchscreen)
+    }
+
+    // Like new, but fails the calling test if uinput is not available.
+    pub fn new_or_fail(width: i32, height: i32) -> VirtualTouchscreen {
+        VirtualTouchscreen::new(width, height)
+            .expect("can't create uinput device, /dev/uinput has to be writable")
+    }
+
+    // The device node is created asynchronously by the kernel (or udev).
+    fn wait_for_device_node(&self) {
+        let start = Instant::now();
+        while !Path::new(&self.path).exists() && start.elapsed() < Duration::from_secs(2) {
+            sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn write(&self, events: Vec<(EventCode, i32)>) {
+        let time = now();
+        for (event_code, value) in events {
+            self.device
+                .write_event(&mk_event(&time, event_code, value))
+                .expect("writing to uinput device failed");
+        }
+        self.device
+            .write_event(&mk_event(&time, EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0))
+            .expect("writing to uinput device failed");
+    }
+
+    pub fn touch(&self, slot: i32, tracking_id: i32, position: Position) {
+        self.write(vec![
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT), slot),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID), tracking_id),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X), position.x),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y), position.y),
+        ]);
+    }
+
+    pub fn move_to(&self, slot: i32, position: Position) {
+        self.write(vec![
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT), slot),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_X), position.x),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_POSITION_Y), position.y),
+        ]);
+    }
+
+    pub fn release(&self, slot: i32) {
+        self.write(vec![
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_SLOT), slot),
+            (EventCode::EV_ABS(EV_ABS::ABS_MT_TRACKING_ID), -1),
+        ]);
+    }
+}
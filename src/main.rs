@@ -10,20 +10,34 @@ extern crate custom_derive;
 extern crate enum_derive;
 
 mod areas;
+mod calibration;
 mod cli;
 mod evdev;
 mod sound;
 mod utils;
 
-use areas::layouts::{grid, grid2, parallelograms};
+use areas::layout_file;
+use areas::layouts::{
+    grid, grid2, parallelograms, IsomorphicLayout, Piano, BOSANQUET_WILSON, FOKKER, HARMONIC_TABLE,
+    JANKO, PIANO, WICKI_HAYDEN,
+};
+use areas::mounting::Mounting;
+use areas::touch_filter::{TouchFilter, TouchFilterConfig};
 use areas::{note_event_source::NoteEventSource, Areas};
+use calibration::Calibration;
+use evdev::discovery::find_touch_devices;
+use evdev::recording::{Recorder, Replay};
 use evdev::*;
 use sound::audio_player::AudioPlayer;
 use sound::midi_player::MidiPlayer;
-use sound::Player;
+use sound::pitch::PitchControl;
+use sound::voice_allocator::VoiceAllocator;
+use sound::{NullPlayer, Player};
 use std::clone::Clone;
 use std::fmt::Debug;
 use std::process::exit;
+use std::sync::mpsc::{channel, Receiver};
+use utils::shutdown::Shutdown;
 
 const TOUCH_WIDTH: i32 = 16383;
 const TOUCH_HEIGHT: i32 = 9570;
@@ -83,6 +97,12 @@ impl From<jack::Error> for ErrorString {
     }
 }
 
+impl From<toml::de::Error> for ErrorString {
+    fn from(e: toml::de::Error) -> ErrorString {
+        ErrorString(format!("{}", e))
+    }
+}
+
 impl From<std::num::ParseFloatError> for ErrorString {
     fn from(e: std::num::ParseFloatError) -> ErrorString {
         ErrorString(format!("{:?}", e))
@@ -105,6 +125,12 @@ custom_derive! {
         Parallelograms,
         Grid,
         Grid2,
+        WickiHayden,
+        HarmonicTable,
+        Janko,
+        BosanquetWilson,
+        Fokker,
+        Piano,
     }
 }
 
@@ -114,37 +140,239 @@ impl Default for LayoutType {
     }
 }
 
-fn get_areas(layout_type: LayoutType) -> Areas {
-    match layout_type {
-        LayoutType::Parallelograms => parallelograms(TOUCH_WIDTH as i32, TOUCH_HEIGHT as i32),
-        LayoutType::Grid => grid(TOUCH_WIDTH as i32, TOUCH_HEIGHT as i32, 16, 11, 36),
-        LayoutType::Grid2 => grid2(TOUCH_WIDTH as i32, TOUCH_HEIGHT as i32),
+fn isomorphic_areas(
+    cli_args: &cli::Args,
+    layout: IsomorphicLayout,
+    touch_width: i32,
+    touch_height: i32,
+) -> Areas {
+    IsomorphicLayout {
+        start_midi_note: cli_args.start_note.unwrap_or(layout.start_midi_note),
+        columns: cli_args.columns.unwrap_or(layout.columns),
+        ..layout
+    }
+    .areas(touch_width, touch_height, &cli_args.tuning)
+}
+
+fn get_areas(cli_args: &cli::Args, mounting: &Mounting) -> Result<Areas, ErrorString> {
+    let (touch_width, touch_height) = (mounting.width(), mounting.height());
+    if let Some(file) = &cli_args.layout_file {
+        return layout_file::load(
+            file,
+            cli_args.layout_name.as_deref(),
+            touch_width,
+            touch_height,
+        )
+        .map(|areas| areas.with_tuning(cli_args.tuning.clone()));
+    }
+    let isomorphic_layout = match cli_args.layout_type {
+        LayoutType::Parallelograms => {
+            return Ok(parallelograms(touch_width, touch_height, &cli_args.tuning))
+        }
+        LayoutType::Grid => {
+            return Ok(grid(
+                touch_width,
+                touch_height,
+                16,
+                11,
+                36,
+                &cli_args.tuning,
+            ))
+        }
+        LayoutType::Grid2 => return Ok(grid2(touch_width, touch_height, &cli_args.tuning)),
+        LayoutType::Piano => {
+            let piano = Piano {
+                start_midi_note: cli_args.start_note.unwrap_or(PIANO.start_midi_note),
+                octaves: cli_args.octaves.unwrap_or(PIANO.octaves),
+                rows: cli_args.rows.unwrap_or(PIANO.rows),
+            };
+            return Ok(piano.areas(touch_width, touch_height, &cli_args.tuning));
+        }
+        LayoutType::WickiHayden => WICKI_HAYDEN,
+        LayoutType::HarmonicTable => HARMONIC_TABLE,
+        LayoutType::Janko => JANKO,
+        LayoutType::BosanquetWilson => BOSANQUET_WILSON,
+        LayoutType::Fokker => FOKKER,
+    };
+    Ok(isomorphic_areas(
+        cli_args,
+        isomorphic_layout,
+        touch_width,
+        touch_height,
+    ))
+}
+
+fn dev_mode_device_info() -> DeviceInfo {
+    DeviceInfo {
+        protocol: Protocol::MultiTouchB,
+        x: AxisInfo {
+            minimum: 0,
+            maximum: TOUCH_WIDTH,
+        },
+        y: AxisInfo {
+            minimum: 0,
+            maximum: TOUCH_HEIGHT,
+        },
+        pressure: None,
+        touch_major: None,
+        width_major: None,
+    }
+}
+
+fn get_device_location(cli_args: &cli::Args) -> DeviceLocation {
+    match &cli_args.device {
+        Some(path) => DeviceLocation::Path(path.clone()),
+        None => DeviceLocation::Discover,
     }
 }
 
-fn get_note_event_source(cli_args: &cli::Args) -> Result<NoteEventSource, ErrorString> {
-    let areas = get_areas(cli_args.layout_type);
-    areas.clone().spawn_ui(cli_args);
-    let touches = if cli_args.dev_mode {
-        TouchStateSource::blocking()
+fn list_devices() -> Result<(), ErrorString> {
+    for device in find_touch_devices()? {
+        println!("{}: {} ({:?})", device.path, device.name, device.protocol);
+    }
+    Ok(())
+}
+
+fn get_voice_allocator(cli_args: &cli::Args) -> VoiceAllocator {
+    VoiceAllocator::new(cli_args.polyphony, cli_args.voice_stealing)
+}
+
+fn get_touch_state_source(
+    cli_args: &cli::Args,
+    shutdown: Shutdown,
+) -> Result<(Box<dyn Iterator<Item = TouchState>>, DeviceInfo), ErrorString> {
+    if let Some(file) = &cli_args.replay_touches {
+        let replay = Replay::load(file, shutdown)?;
+        let device_info = replay.device_info().clone();
+        return Ok((Box::new(replay), device_info));
+    }
+    let input_event_source = InputEventSource::new(
+        get_device_location(cli_args),
+        !cli_args.shared_input,
+        shutdown.clone(),
+    )?;
+    let device_info = input_event_source.device_info().clone();
+    let touches = TouchStateSource::new(input_event_source);
+    match &cli_args.record_touches {
+        None => Ok((Box::new(touches), device_info)),
+        Some(file) => {
+            let recorder = Recorder::new(file, &device_info, touches.timed(), shutdown)?;
+            Ok((Box::new(recorder), device_info))
+        }
+    }
+}
+
+fn get_calibrated_touch_state_source(
+    cli_args: &cli::Args,
+    shutdown: Shutdown,
+) -> Result<(Box<dyn Iterator<Item = TouchState>>, DeviceInfo), ErrorString> {
+    let (touches, device_info) = get_touch_state_source(cli_args, shutdown)?;
+    match &cli_args.calibration_file {
+        None => Ok((touches, device_info)),
+        Some(file) => {
+            let calibration = Calibration::load(file)?;
+            Ok((
+                Box::new(touches.map(move |touch_state| calibration.calibrate(touch_state))),
+                device_info,
+            ))
+        }
+    }
+}
+
+// Reads the touches in a thread of their own and sends them on already
+// mounted. The source is opened inside of that thread, so that it doesn't
+// have to be Send.
+fn spawn_touch_reader<Open>(
+    cli_args: &cli::Args,
+    open: Open,
+) -> Result<(Receiver<TouchState>, Mounting), ErrorString>
+where
+    Open: FnOnce(
+            &cli::Args,
+        ) -> Result<(Box<dyn Iterator<Item = TouchState>>, DeviceInfo), ErrorString>
+        + Send
+        + 'static,
+{
+    let cli_args = cli_args.clone();
+    let (mounting_sender, mounting_receiver) = channel();
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        let (touches, device_info) = match open(&cli_args) {
+            Ok(source) => source,
+            Err(e) => {
+                let _ = mounting_sender.send(Err(e));
+                return;
+            }
+        };
+        let mounting = Mounting::new(&cli_args, &device_info);
+        let _ = mounting_sender.send(Ok(mounting.clone()));
+        for touch_state in touches {
+            if sender.send(mounting.apply(touch_state)).is_err() {
+                break;
+            }
+        }
+    });
+    let mounting = mounting_receiver
+        .recv()
+        .map_err(|_| ErrorString::from("touch reader stopped unexpectedly"))??;
+    Ok((receiver, mounting))
+}
+
+fn get_note_event_source(
+    cli_args: &cli::Args,
+    pitch_control: PitchControl,
+) -> Result<NoteEventSource, ErrorString> {
+    let shutdown = Shutdown::new();
+    let (touches, mounting, dev_input) = if cli_args.dev_mode {
+        let (sender, receiver) = channel();
+        let (touches, mounting) = spawn_touch_reader(cli_args, move |_| {
+            Ok((Box::new(receiver.into_iter()), dev_mode_device_info()))
+        })?;
+        (touches, mounting, Some(sender))
     } else {
-        TouchStateSource::new("/dev/input/by-id/usb-ILITEK_Multi-Touch-V5100-event-if00")?
+        let reader_shutdown = shutdown.clone();
+        let (touches, mounting) = spawn_touch_reader(cli_args, move |cli_args| {
+            get_calibrated_touch_state_source(cli_args, reader_shutdown)
+        })?;
+        (touches, mounting, None)
     };
-    Ok(NoteEventSource::new(areas, touches))
+    let areas = get_areas(cli_args, &mounting)?;
+    areas
+        .clone()
+        .spawn_ui(cli_args, mounting, dev_input, pitch_control, shutdown);
+    let touches = TouchFilter::new(areas.clone(), TouchFilterConfig::new(cli_args), touches);
+    Ok(
+        NoteEventSource::new(areas, touches, get_voice_allocator(cli_args))
+            .with_pitch_mode(cli_args.pitch_mode),
+    )
 }
 
-fn get_player(cli_args: &cli::Args) -> Result<Box<dyn Player>, ErrorString> {
-    if cli_args.midi {
-        Ok(Box::new(MidiPlayer::new()?))
+fn get_player(
+    cli_args: &cli::Args,
+    pitch_control: PitchControl,
+) -> Result<Box<dyn Player>, ErrorString> {
+    if cli_args.dev_mode && !cli_args.dev_audio {
+        Ok(Box::new(NullPlayer))
+    } else if cli_args.midi {
+        Ok(Box::new(MidiPlayer::new(cli_args, pitch_control)?))
     } else {
-        Ok(Box::new(AudioPlayer::new(&cli_args)?))
+        Ok(Box::new(AudioPlayer::new(&cli_args, pitch_control)?))
     }
 }
 
 fn run() -> Result<(), ErrorString> {
     let cli_args = &cli::parse(get_binary_name()?, std::env::args())?;
-    let note_event_source = get_note_event_source(cli_args)?;
-    let player = get_player(cli_args)?;
+    if cli_args.list_devices {
+        return list_devices();
+    }
+    if cli_args.calibrate {
+        let shutdown = Shutdown::new();
+        let (touches, device_info) = get_touch_state_source(cli_args, shutdown.clone())?;
+        return calibration::run(cli_args, &device_info, touches, shutdown);
+    }
+    let pitch_control = PitchControl::new(cli_args.concert_pitch, cli_args.transposition);
+    let note_event_source = get_note_event_source(cli_args, pitch_control.clone())?;
+    let mut player = get_player(cli_args, pitch_control)?;
     player.consume(note_event_source);
     Ok(())
 }
@@ -4,19 +4,23 @@ use super::Player;
 use crate::cli;
 use crate::get_binary_name;
 use crate::sound::midi_controller::MidiController;
-use crate::sound::{NoteEvent, NoteEventSource, POLYPHONY};
+use crate::sound::pitch::PitchControl;
+use crate::sound::{NoteEvent, NoteEventSource};
+use crate::utils::triple_buffer::{triple_buffer, Receiver, Sender};
 use crate::ErrorString;
 use jack::*;
-use skipchannel::*;
 use std::*;
 
 pub struct AudioPlayer {
     _async_client: AsyncClient<Logger, AudioProcessHandler>,
-    sender: Sender<[NoteEvent; POLYPHONY]>,
+    sender: Sender<Vec<NoteEvent>>,
 }
 
 impl AudioPlayer {
-    pub fn new(cli_args: &cli::Args) -> Result<AudioPlayer, ErrorString> {
+    pub fn new(
+        cli_args: &cli::Args,
+        pitch_control: PitchControl,
+    ) -> Result<AudioPlayer, ErrorString> {
         let name = get_binary_name()?;
         let (client, _status) = jack::Client::new(&name, jack::ClientOptions::empty())?;
         let midi_controller = MidiController::new(&client)?;
@@ -27,12 +31,13 @@ impl AudioPlayer {
         };
 
         let logger = Logger::new_and_spawn();
-        let (sender, receiver) = skipchannel();
+        let (sender, receiver) = triple_buffer();
         let process_handler = AudioProcessHandler {
             logger: logger.clone(),
             audio_ports,
             midi_controller,
             receiver,
+            pitch_control,
             generators,
         };
         let async_client = client.activate_async(logger, process_handler)?;
@@ -45,7 +50,7 @@ impl AudioPlayer {
 }
 
 impl Player for AudioPlayer {
-    fn consume(&self, note_event_source: NoteEventSource) {
+    fn consume(&mut self, note_event_source: NoteEventSource) {
         for voices in note_event_source {
             self.sender.send(voices);
         }
@@ -61,20 +66,22 @@ pub struct AudioProcessHandler {
     logger: Logger,
     audio_ports: Stereo<Port<AudioOut>>,
     midi_controller: MidiController,
-    receiver: Receiver<[NoteEvent; POLYPHONY]>,
+    receiver: Receiver<Vec<NoteEvent>>,
+    pitch_control: PitchControl,
     generators: Generators,
 }
 
 impl AudioProcessHandler {
-    fn handle_events(&mut self, scope: &ProcessScope) {
+    fn handle_events(&mut self, client: &Client, scope: &ProcessScope) {
         self.midi_controller
             .handle_events(&mut self.generators, scope);
-        self.handle_note_events();
+        self.generators.pitch_factor = self.pitch_control.factor();
+        self.handle_note_events(client.sample_rate());
     }
 
-    fn handle_note_events(&mut self) {
+    fn handle_note_events(&mut self, sample_rate: usize) {
         if let Some(voices) = self.receiver.recv() {
-            Generators::handle_note_events(&mut self.generators, voices);
+            Generators::handle_note_events(&mut self.generators, sample_rate, voices);
         }
     }
 
@@ -103,7 +110,7 @@ impl AudioProcessHandler {
 
 impl ProcessHandler for AudioProcessHandler {
     fn process(&mut self, client: &Client, scope: &ProcessScope) -> Control {
-        self.handle_events(scope);
+        self.handle_events(client, scope);
         self.fill_buffers(client, scope);
         Control::Continue
     }
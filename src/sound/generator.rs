@@ -1,8 +1,60 @@
 use crate::cli;
 use crate::sound::wave_form::WaveForm;
 use crate::sound::NoteEvent;
-use crate::sound::{POLYPHONY, TAU};
-use crate::utils::Slots;
+use crate::sound::TAU;
+
+custom_derive! {
+#[derive(Debug, Clone, Copy, IterVariants(SecondaryAxisVariants), PartialEq)]
+    // What the position of a touch across its area (v) controls.
+    pub enum SecondaryAxis {
+        Off,
+        Volume,
+        Cutoff,
+        Harmonics,
+    }
+}
+
+impl Default for SecondaryAxis {
+    fn default() -> SecondaryAxis {
+        SecondaryAxis::Off
+    }
+}
+
+// Range of the lowpass filter for SecondaryAxis::Cutoff, in Hz.
+const MIN_CUTOFF: f32 = 100.0;
+const MAX_CUTOFF: f32 = 10000.0;
+
+// Coefficient of the one-pole lowpass filter for SecondaryAxis::Cutoff.
+fn lowpass_coefficient(v: f32, sample_rate: usize) -> f32 {
+    let cutoff = MIN_CUTOFF * (MAX_CUTOFF / MIN_CUTOFF).powf(v);
+    1.0 - (-TAU * cutoff / sample_rate as f32).exp()
+}
+
+impl SecondaryAxis {
+    // Computes a sample of a voice, where filtered is the state of the voice's
+    // lowpass filter.
+    fn run(
+        self,
+        wave_form: &WaveForm,
+        sine: &WaveForm,
+        phase: f32,
+        v: f32,
+        cutoff_coefficient: f32,
+        filtered: &mut f32,
+    ) -> f32 {
+        let base = wave_form.run(phase);
+        match self {
+            SecondaryAxis::Off => base,
+            SecondaryAxis::Volume => base * v,
+            SecondaryAxis::Cutoff => {
+                *filtered += (base - *filtered) * cutoff_coefficient;
+                *filtered
+            }
+            // mixes a sine wave with the configured harmonics
+            SecondaryAxis::Harmonics => sine.run(phase) * (1.0 - v) + base * v,
+        }
+    }
+}
 
 pub struct Envelope {
     pub attack: f32,
@@ -14,11 +66,19 @@ pub struct Envelope {
 pub struct Generators {
     amplitude: f32,
     pub midi_controller_volume: f32,
+    // Applied to all voice frequencies, see `PitchControl`.
+    pub pitch_factor: f32,
+    pub secondary_axis: SecondaryAxis,
     pub envelope: Envelope,
     pub wave_form: WaveForm,
+    // for SecondaryAxis::Harmonics
+    sine: WaveForm,
     pub voices: Vec<VoiceState>,
 }
 
+// Voices are scaled down so that ten simultaneous voices don't clip.
+const HEADROOM_VOICES: f32 = 10.0;
+
 pub const MIN_ATTACK: f32 = 0.005;
 pub const MAX_ATTACK: f32 = 0.3;
 
@@ -33,11 +93,11 @@ pub const MAX_RELEASE: f32 = 1.0;
 
 impl Generators {
     pub fn new(cli_args: &cli::Args) -> Generators {
-        let unit_slots: Slots<()> = [(); 10];
-        let slots = unit_slots.len();
         Generators {
-            amplitude: cli_args.volume / slots as f32,
+            amplitude: cli_args.volume / HEADROOM_VOICES,
             midi_controller_volume: 1.0,
+            pitch_factor: 1.0,
+            secondary_axis: cli_args.secondary_axis,
             envelope: Envelope {
                 attack: MIN_ATTACK,
                 decay: MIN_DECAY,
@@ -45,15 +105,24 @@ impl Generators {
                 release: MIN_RELEASE,
             },
             wave_form: WaveForm::new(&cli_args.wave_form_config),
-            voices: vec![VoiceState::default(); POLYPHONY],
+            sine: WaveForm::sine(),
+            voices: vec![VoiceState::default(); cli_args.polyphony],
         }
     }
 
-    pub fn handle_note_events(&mut self, voice_events: [NoteEvent; POLYPHONY]) {
+    pub fn handle_note_events(&mut self, sample_rate: usize, voice_events: &[NoteEvent]) {
         for (voice, event) in self.voices.iter_mut().zip(voice_events.iter()) {
             match event {
                 NoteEvent::NoteOff => voice.note_off(&self.envelope),
-                NoteEvent::NoteOn(frequency) => voice.note_on(*frequency),
+                NoteEvent::NoteOn {
+                    frequency,
+                    velocity,
+                    v,
+                    ..
+                } => {
+                    voice.note_on(*frequency, *velocity);
+                    voice.set_v(*v, sample_rate);
+                }
             }
         }
     }
@@ -61,15 +130,26 @@ impl Generators {
     pub fn generate(&mut self, sample_rate: usize, buffer: &mut [f32]) {
         for voice in self.voices.iter_mut() {
             for sample in buffer.iter_mut() {
-                voice.step(sample_rate, &self.envelope);
+                voice.step(sample_rate, self.pitch_factor, &self.envelope);
                 match *voice {
                     VoiceState::Playing {
                         phase,
+                        velocity,
+                        v,
+                        cutoff_coefficient,
+                        ref mut filtered,
                         ref envelope_phase,
                         ..
                     } => {
-                        *sample += self.wave_form.run(phase)
-                            * self.amplitude
+                        *sample += self.secondary_axis.run(
+                            &self.wave_form,
+                            &self.sine,
+                            phase,
+                            v,
+                            cutoff_coefficient,
+                            filtered,
+                        ) * self.amplitude
+                            * velocity
                             * self.midi_controller_volume
                             * envelope_phase.get_amplitude(&self.envelope);
                     }
@@ -103,7 +183,13 @@ impl EnvelopePhase {
 pub enum VoiceState {
     Playing {
         frequency: f32,
+        velocity: f32,
         phase: f32,
+        // the position across the area, see `SecondaryAxis`
+        v: f32,
+        // depends on v, see `lowpass_coefficient`
+        cutoff_coefficient: f32,
+        filtered: f32,
         envelope_phase: EnvelopePhase,
     },
     Muted,
@@ -116,14 +202,16 @@ impl Default for VoiceState {
 }
 
 impl VoiceState {
-    pub fn note_on(&mut self, new_frequency: f32) {
+    pub fn note_on(&mut self, new_frequency: f32, new_velocity: f32) {
         match *self {
             VoiceState::Playing {
                 ref mut frequency,
+                ref mut velocity,
                 ref mut envelope_phase,
                 ..
             } => {
                 *frequency = new_frequency;
+                *velocity = new_velocity;
                 match envelope_phase {
                     EnvelopePhase::Attacking { .. } => {}
                     EnvelopePhase::Decaying { .. } => {}
@@ -136,13 +224,30 @@ impl VoiceState {
             VoiceState::Muted => {
                 *self = VoiceState::Playing {
                     frequency: new_frequency,
+                    velocity: new_velocity,
                     phase: 0.0,
+                    v: 1.0,
+                    // lets the wave form pass unfiltered until `set_v` is called
+                    cutoff_coefficient: 1.0,
+                    filtered: 0.0,
                     envelope_phase: EnvelopePhase::Attacking { amplitude: 0.0 },
                 };
             }
         };
     }
 
+    pub fn set_v(&mut self, new_v: f32, sample_rate: usize) {
+        if let VoiceState::Playing {
+            ref mut v,
+            ref mut cutoff_coefficient,
+            ..
+        } = *self
+        {
+            *v = new_v;
+            *cutoff_coefficient = lowpass_coefficient(new_v, sample_rate);
+        }
+    }
+
     pub fn note_off(&mut self, envelope: &Envelope) {
         match *self {
             VoiceState::Playing {
@@ -157,14 +262,14 @@ impl VoiceState {
         }
     }
 
-    fn crank_phase(&mut self, sample_rate: usize) {
+    fn crank_phase(&mut self, sample_rate: usize, pitch_factor: f32) {
         match *self {
             VoiceState::Playing {
                 frequency,
                 ref mut phase,
                 ..
             } => {
-                *phase += frequency * TAU / sample_rate as f32;
+                *phase += frequency * pitch_factor * TAU / sample_rate as f32;
                 *phase %= TAU;
             }
             VoiceState::Muted => {}
@@ -213,8 +318,8 @@ impl VoiceState {
         }
     }
 
-    fn step(&mut self, sample_rate: usize, envelope: &Envelope) {
-        self.crank_phase(sample_rate);
+    fn step(&mut self, sample_rate: usize, pitch_factor: f32, envelope: &Envelope) {
+        self.crank_phase(sample_rate, pitch_factor);
         self.step_envelope(sample_rate, envelope);
     }
 }
@@ -222,7 +327,7 @@ impl VoiceState {
 #[cfg(test)]
 pub mod test {
     use super::*;
-    use crate::sound::mk_voices;
+    use crate::sound::DEFAULT_POLYPHONY;
 
     const SAMPLE_RATE: usize = 44100;
 
@@ -251,11 +356,12 @@ pub mod test {
             #[test]
             fn reaches_2_pi_after_1_second() {
                 let mut voice = VoiceState::default();
-                voice.note_on(1.0);
+                voice.note_on(1.0, 1.0);
                 let sample_rate = 100;
                 for _ in 0..(sample_rate - 1) {
                     voice.step(
                         sample_rate,
+                        1.0,
                         &Envelope {
                             attack: 0.0,
                             decay: MIN_DECAY,
@@ -273,10 +379,11 @@ pub mod test {
             #[test]
             fn increases_the_phase_for_one_sample() {
                 let mut voice = VoiceState::default();
-                voice.note_on(1.0);
+                voice.note_on(1.0, 1.0);
                 assert_eq!(voice.get_phase(), 0.0);
                 voice.step(
                     SAMPLE_RATE,
+                    1.0,
                     &Envelope {
                         attack: 0.0,
                         decay: MIN_DECAY,
@@ -290,10 +397,11 @@ pub mod test {
             #[test]
             fn wraps_around_at_2_pi() {
                 let mut voice = VoiceState::default();
-                voice.note_on(1.0);
+                voice.note_on(1.0, 1.0);
                 for _ in 0..SAMPLE_RATE {
                     voice.step(
                         SAMPLE_RATE,
+                        1.0,
                         &Envelope {
                             attack: 0.0,
                             decay: MIN_DECAY,
@@ -314,6 +422,8 @@ pub mod test {
             Generators {
                 amplitude: 1.0,
                 midi_controller_volume: 1.0,
+                pitch_factor: 1.0,
+                secondary_axis: SecondaryAxis::Off,
                 envelope: Envelope {
                     attack: 0.0,
                     decay: MIN_DECAY,
@@ -321,7 +431,8 @@ pub mod test {
                     release: 0.0,
                 },
                 wave_form: WaveForm::from_function(|x| x.sin(), SAMPLE_RATE),
-                voices: vec![VoiceState::default(); POLYPHONY],
+                sine: WaveForm::sine(),
+                voices: vec![VoiceState::default(); DEFAULT_POLYPHONY],
             }
         }
 
@@ -329,6 +440,8 @@ pub mod test {
             Generators {
                 amplitude: 1.0,
                 midi_controller_volume: 1.0,
+                pitch_factor: 1.0,
+                secondary_axis: SecondaryAxis::Off,
                 envelope: Envelope {
                     attack: 0.0,
                     decay: MIN_DECAY,
@@ -336,28 +449,34 @@ pub mod test {
                     release: 0.0,
                 },
                 wave_form: WaveForm::from_function(|x| x.sin(), SAMPLE_RATE),
+                sine: WaveForm::sine(),
                 voices: vec![VoiceState::default()],
             }
         }
 
         mod handle_note_events {
             use super::*;
+            use crate::sound::test::note_on;
 
             #[test]
             fn switches_on_the_voice_with_the_same_index_as_the_note_event() {
-                for i in 0..POLYPHONY {
+                for i in 0..DEFAULT_POLYPHONY {
                     let mut generators = sine_generators();
                     let voices = {
-                        let mut result = mk_voices(NoteEvent::NoteOff);
-                        result[i] = NoteEvent::NoteOn(42.0);
+                        let mut result = vec![NoteEvent::NoteOff; DEFAULT_POLYPHONY];
+                        result[i] = note_on(42.0);
                         result
                     };
-                    generators.handle_note_events(voices);
+                    generators.handle_note_events(SAMPLE_RATE, &voices);
                     let expected = {
-                        let mut result = mk_voices(VoiceState::Muted);
+                        let mut result = vec![VoiceState::Muted; DEFAULT_POLYPHONY];
                         result[i] = VoiceState::Playing {
                             frequency: 42.0,
+                            velocity: 1.0,
                             phase: 0.0,
+                            v: 0.5,
+                            cutoff_coefficient: lowpass_coefficient(0.5, SAMPLE_RATE),
+                            filtered: 0.0,
                             envelope_phase: EnvelopePhase::Attacking { amplitude: 0.0 },
                         };
                         result
@@ -368,17 +487,21 @@ pub mod test {
 
             #[test]
             fn switches_off_the_correct_voice() {
-                for i in 0..POLYPHONY {
+                for i in 0..DEFAULT_POLYPHONY {
                     let mut generators = sine_generators();
                     let voices = {
-                        let mut result = mk_voices(NoteEvent::NoteOff);
-                        result[i] = NoteEvent::NoteOn(42.0);
+                        let mut result = vec![NoteEvent::NoteOff; DEFAULT_POLYPHONY];
+                        result[i] = note_on(42.0);
                         result
                     };
-                    generators.handle_note_events(voices);
-                    generators.handle_note_events(mk_voices(NoteEvent::NoteOff));
+                    generators.handle_note_events(SAMPLE_RATE, &voices);
+                    let note_offs = vec![NoteEvent::NoteOff; DEFAULT_POLYPHONY];
+                    generators.handle_note_events(SAMPLE_RATE, &note_offs);
                     generators.generate(SAMPLE_RATE, &mut [0.0]);
-                    assert_eq!(generators.voices, mk_voices(VoiceState::Muted));
+                    assert_eq!(
+                        generators.voices,
+                        vec![VoiceState::Muted; DEFAULT_POLYPHONY]
+                    );
                 }
             }
         }
@@ -388,7 +511,7 @@ pub mod test {
 
             impl Generators {
                 fn note_on(&mut self, i: usize, frequency: f32) {
-                    self.voices[i].note_on(frequency);
+                    self.voices[i].note_on(frequency, 1.0);
                 }
 
                 fn note_off(&mut self, i: usize) {
@@ -398,8 +521,8 @@ pub mod test {
 
             #[test]
             fn new_creates_as_many_voices_as_configured() {
-                let generators = Generators::new(&cli::test::args(vec![]));
-                assert_eq!(generators.voices.len(), POLYPHONY);
+                let generators = Generators::new(&cli::test::args(vec!["--polyphony", "7"]));
+                assert_eq!(generators.voices.len(), 7);
             }
 
             #[test]
@@ -474,11 +597,80 @@ pub mod test {
                 );
             }
 
+            #[test]
+            fn applies_the_pitch_factor() {
+                let mut generators = monophonic_sine_generators();
+                generators.pitch_factor = 2.0;
+                generators.note_on(0, 300.0);
+                let mut buffer = [0.0; 10];
+                generators.generate(SAMPLE_RATE, &mut buffer);
+                assert_eq!(buffer[0], (600.0 * TAU / SAMPLE_RATE as f32).sin());
+                assert_eq!(buffer[1], (2.0 * 600.0 * TAU / SAMPLE_RATE as f32).sin());
+            }
+
+            mod secondary_axis {
+                use super::*;
+
+                fn generate(
+                    secondary_axis: SecondaryAxis,
+                    wave_form: fn(f32) -> f32,
+                    frequency: f32,
+                    v: f32,
+                ) -> Vec<f32> {
+                    let mut generators = Generators {
+                        secondary_axis,
+                        wave_form: WaveForm::from_function(wave_form, SAMPLE_RATE),
+                        ..monophonic_sine_generators()
+                    };
+                    generators.note_on(0, frequency);
+                    generators.voices[0].set_v(v, SAMPLE_RATE);
+                    let mut buffer = vec![0.0; 100];
+                    generators.generate(SAMPLE_RATE, &mut buffer);
+                    buffer
+                }
+
+                #[test]
+                fn is_ignored_by_default() {
+                    assert_eq!(
+                        generate(SecondaryAxis::Off, |x| x.sin(), 300.0, 0.2),
+                        generate(SecondaryAxis::Off, |x| x.sin(), 300.0, 1.0)
+                    );
+                }
+
+                #[test]
+                fn controls_the_volume() {
+                    let buffer = generate(SecondaryAxis::Volume, |x| x.sin(), 300.0, 0.5);
+                    assert_eq!(buffer[0], (300.0 * TAU / SAMPLE_RATE as f32).sin() * 0.5);
+                }
+
+                #[test]
+                fn mixes_a_sine_wave_with_the_harmonics() {
+                    let buffer = generate(SecondaryAxis::Harmonics, |_| 1.0, 300.0, 0.25);
+                    assert_close(
+                        buffer[0],
+                        (300.0 * TAU / SAMPLE_RATE as f32).sin() * 0.75 + 0.25,
+                    );
+                }
+
+                #[test]
+                fn lowers_the_cutoff_for_low_positions() {
+                    let loudness = |v: f32| -> f32 {
+                        generate(SecondaryAxis::Cutoff, |x| x.sin(), 5000.0, v)
+                            .iter()
+                            .map(|sample| sample.abs())
+                            .sum()
+                    };
+                    assert!(loudness(0.0) < loudness(1.0) / 4.0);
+                }
+            }
+
             #[test]
             fn is_initially_muted() {
                 let mut generators = Generators {
                     amplitude: 1.0,
                     midi_controller_volume: 1.0,
+                    pitch_factor: 1.0,
+                    secondary_axis: SecondaryAxis::Off,
                     envelope: Envelope {
                         attack: 0.0,
                         decay: MIN_DECAY,
@@ -486,6 +678,7 @@ pub mod test {
                         release: 0.0,
                     },
                     wave_form: WaveForm::from_function(|x| x.sin(), 10000),
+                    sine: WaveForm::sine(),
                     voices: vec![VoiceState::default()],
                 };
                 let mut buffer = [0.0; 10];
@@ -494,6 +687,15 @@ pub mod test {
                 assert_eq!(buffer[2], 0.0);
             }
 
+            #[test]
+            fn scales_the_amplitude_by_the_velocity() {
+                let mut generators = monophonic_sine_generators();
+                generators.voices[0].note_on(1.0, 0.5);
+                let mut buffer = [0.0; 10];
+                generators.generate(SAMPLE_RATE, &mut buffer);
+                assert_eq!(buffer[0], (TAU / SAMPLE_RATE as f32).sin() * 0.5);
+            }
+
             #[test]
             fn can_be_muted() {
                 let mut generators = monophonic_sine_generators();
@@ -511,6 +713,8 @@ pub mod test {
                 let mut generators = Generators {
                     amplitude: 1.0,
                     midi_controller_volume: 1.0,
+                    pitch_factor: 1.0,
+                    secondary_axis: SecondaryAxis::Off,
                     envelope: Envelope {
                         attack: 0.0,
                         decay: MIN_DECAY,
@@ -518,6 +722,7 @@ pub mod test {
                         release: 0.0,
                     },
                     wave_form: WaveForm::from_function(|phase| phase * 5.0, 10000),
+                    sine: WaveForm::sine(),
                     voices: vec![VoiceState::default()],
                 };
                 generators.note_on(0, 1.0);
@@ -532,6 +737,8 @@ pub mod test {
                 let mut generators = Generators {
                     amplitude: 0.25,
                     midi_controller_volume: 1.0,
+                    pitch_factor: 1.0,
+                    secondary_axis: SecondaryAxis::Off,
                     envelope: Envelope {
                         attack: 0.0,
                         decay: MIN_DECAY,
@@ -539,6 +746,7 @@ pub mod test {
                         release: 0.0,
                     },
                     wave_form: WaveForm::from_function(|_phase| 0.4, 10000),
+                    sine: WaveForm::sine(),
                     voices: vec![VoiceState::default()],
                 };
                 generators.note_on(0, 1.0);
@@ -552,6 +760,8 @@ pub mod test {
                 let mut generators = Generators {
                     amplitude: 1.0,
                     midi_controller_volume: 1.0,
+                    pitch_factor: 1.0,
+                    secondary_axis: SecondaryAxis::Off,
                     envelope: Envelope {
                         attack: 0.0,
                         decay: MIN_DECAY,
@@ -559,6 +769,7 @@ pub mod test {
                         release: 0.0,
                     },
                     wave_form: WaveForm::from_function(|_phase| 0.4, 10000),
+                    sine: WaveForm::sine(),
                     voices: vec![VoiceState::default()],
                 };
                 generators.note_on(0, 1.0);
@@ -600,8 +811,11 @@ pub mod test {
                     Generators {
                         amplitude: 1.0,
                         midi_controller_volume: 1.0,
+                        pitch_factor: 1.0,
+                        secondary_axis: SecondaryAxis::Off,
                         envelope,
                         wave_form: WaveForm::from_function(wave_form, 10000),
+                        sine: WaveForm::sine(),
                         voices: vec![VoiceState::default()],
                     }
                 }
@@ -818,6 +1032,8 @@ pub mod test {
                     let mut generators = Generators {
                         amplitude: 0.5,
                         midi_controller_volume: 1.0,
+                        pitch_factor: 1.0,
+                        secondary_axis: SecondaryAxis::Off,
                         envelope: Envelope {
                             attack: 0.0,
                             decay: MIN_DECAY,
@@ -825,6 +1041,7 @@ pub mod test {
                             release: 0.0,
                         },
                         wave_form: WaveForm::from_function(|_phase| 0.5, 10000),
+                        sine: WaveForm::sine(),
                         voices: vec![VoiceState::default()],
                     };
                     generators.note_on(0, 440.0);
@@ -840,6 +1057,8 @@ pub mod test {
                     let mut generators = Generators {
                         amplitude: 1.0,
                         midi_controller_volume: 1.0,
+                        pitch_factor: 1.0,
+                        secondary_axis: SecondaryAxis::Off,
                         envelope: Envelope {
                             attack: 0.0,
                             decay: MIN_DECAY,
@@ -847,6 +1066,7 @@ pub mod test {
                             release: 1.0,
                         },
                         wave_form: WaveForm::from_function(|_phase| 0.5, 10000),
+                        sine: WaveForm::sine(),
                         voices: vec![VoiceState::default()],
                     };
                     let mut buffer = [0.0; 10];
@@ -1,9 +1,52 @@
+// In MIDI Polyphonic Expression the first channel is reserved for messages
+// that apply to all notes, so 15 channels are left for single voices.
+pub const MPE_CHANNELS: usize = 15;
+
+#[cfg(test)]
 pub fn midi_to_frequency(midi: i32) -> f32 {
     440.0 * 2.0_f32.powf(((midi - 69) as f32) / 12.0)
 }
 
-pub fn frequency_to_midi(frequency: f32) -> u8 {
-    (((12.0 * (frequency / 440.0).log2()).round() as i16) + 69) as u8
+// The midi note of the frequency, including the fraction of a semitone.
+pub fn frequency_to_fractional_midi(frequency: f32) -> f32 {
+    12.0 * (frequency / 440.0).log2() + 69.0
+}
+
+// Pitches outside of the midi range are clamped to its lowest or highest
+// note, since values above 127 would be read as status bytes.
+pub fn nearest_midi_note(fractional_midi: f32) -> u8 {
+    fractional_midi.round().max(0.0).min(127.0) as u8
+}
+
+// The pitch bend range (in semitones, in both directions) that synthesizers
+// use by default. In MPE it's the range of the first channel.
+pub const PITCH_BEND_RANGE: f32 = 2.0;
+
+// The default pitch bend range of the other channels in MPE.
+pub const MPE_PITCH_BEND_RANGE: f32 = 48.0;
+
+// Converts an offset in semitones to a 14 bit pitch bend message, 8192 being
+// no pitch bend.
+pub fn pitch_bend_to_midi(channel: u8, semitones: f32, range: f32) -> [u8; 3] {
+    let value = (8192.0 + semitones / range * 8192.0)
+        .round()
+        .max(0.0)
+        .min(16383.0) as u16;
+    [
+        0b1110_0000 | channel,
+        (value & 0x7f) as u8,
+        (value >> 7) as u8,
+    ]
+}
+
+// Velocities of 0 would be interpreted as note offs.
+pub fn velocity_to_midi(velocity: f32) -> u8 {
+    (velocity * 127.0).round().max(1.0).min(127.0) as u8
+}
+
+// Converts values from 0 to 1 to controller values.
+pub fn controller_to_midi(value: f32) -> u8 {
+    (value * 127.0).round().max(0.0).min(127.0) as u8
 }
 
 #[cfg(test)]
@@ -32,6 +75,10 @@ mod test {
     mod frequency_to_midi {
         use super::*;
 
+        fn frequency_to_midi(frequency: f32) -> u8 {
+            nearest_midi_note(frequency_to_fractional_midi(frequency))
+        }
+
         #[test]
         fn converts_the_concert_pitch_correctly() {
             assert_eq!(frequency_to_midi(440.0), 69);
@@ -47,5 +94,83 @@ mod test {
             assert_eq!(frequency_to_midi(269.0), 60);
             assert_eq!(frequency_to_midi(270.0), 61);
         }
+
+        #[test]
+        fn clamps_to_the_midi_range() {
+            assert_eq!(frequency_to_midi(2.0), 0);
+            assert_eq!(frequency_to_midi(20000.0), 127);
+            assert_eq!(frequency_to_midi(1_000_000.0), 127);
+        }
+    }
+
+    mod frequency_to_fractional_midi {
+        use super::*;
+
+        #[test]
+        fn keeps_fractions_of_semitones() {
+            let fractional_midi = frequency_to_fractional_midi(440.0 * 2.0_f32.powf(0.25 / 12.0));
+            assert!(
+                (fractional_midi - 69.25).abs() < 0.001,
+                "{}",
+                fractional_midi
+            );
+        }
+    }
+
+    mod pitch_bend_to_midi {
+        use super::*;
+
+        #[test]
+        fn sends_the_center_for_no_offset() {
+            assert_eq!(pitch_bend_to_midi(0, 0.0, 2.0), [0b11100000, 0, 64]);
+        }
+
+        #[test]
+        fn scales_offsets_to_the_range() {
+            assert_eq!(pitch_bend_to_midi(0, 1.0, 2.0), [0b11100000, 0, 96]);
+            assert_eq!(pitch_bend_to_midi(0, -0.5, 2.0), [0b11100000, 0, 48]);
+            assert_eq!(pitch_bend_to_midi(3, 12.0, 48.0), [0b11100011, 0, 80]);
+        }
+
+        #[test]
+        fn clamps_to_the_range() {
+            assert_eq!(pitch_bend_to_midi(0, 2.0, 2.0), [0b11100000, 127, 127]);
+            assert_eq!(pitch_bend_to_midi(0, -3.0, 2.0), [0b11100000, 0, 0]);
+        }
+    }
+
+    mod velocity_to_midi {
+        use super::*;
+
+        #[test]
+        fn converts_full_velocity() {
+            assert_eq!(velocity_to_midi(1.0), 127);
+        }
+
+        #[test]
+        fn scales_velocities() {
+            assert_eq!(velocity_to_midi(0.5), 64);
+        }
+
+        #[test]
+        fn never_returns_zero() {
+            assert_eq!(velocity_to_midi(0.0), 1);
+        }
+
+        #[test]
+        fn clamps_out_of_range_velocities() {
+            assert_eq!(velocity_to_midi(2.0), 127);
+        }
+    }
+
+    mod controller_to_midi {
+        use super::*;
+
+        #[test]
+        fn scales_values() {
+            assert_eq!(controller_to_midi(0.0), 0);
+            assert_eq!(controller_to_midi(0.5), 64);
+            assert_eq!(controller_to_midi(1.0), 127);
+        }
     }
 }
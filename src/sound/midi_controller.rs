@@ -257,7 +257,7 @@ impl MidiController {
         })
     }
 
-    pub fn handle_events(&self, generators: &mut Generators, scope: &ProcessScope) {
+    pub fn handle_events(&mut self, generators: &mut Generators, scope: &ProcessScope) {
         self.event_handler
             .handle_events(generators, self.port.iter(scope));
     }
@@ -278,7 +278,7 @@ impl EventHandler {
         }
     }
 
-    fn handle_events<'a, Iter>(&self, generators: &mut Generators, raw_events: Iter)
+    fn handle_events<'a, Iter>(&mut self, generators: &mut Generators, raw_events: Iter)
     where
         Iter: Iterator<Item = RawMidi<'a>>,
     {
@@ -291,7 +291,7 @@ impl EventHandler {
     }
 
     fn handle_midi_controller_event(
-        &self,
+        &mut self,
         generators: &mut Generators,
         event: MidiControllerEvent,
     ) {
@@ -307,7 +307,7 @@ impl EventHandler {
         }
     }
 
-    fn poll_hammond_generator(&self, generators: &mut Generators) {
+    fn poll_hammond_generator(&mut self, generators: &mut Generators) {
         if let Some(new_wave_form) = self.hammond_generator.poll() {
             generators.wave_form = new_wave_form;
         }
@@ -364,7 +364,7 @@ mod test {
                 bytes: &[176, 11, 64],
             }];
             let mut generators = sine_generators();
-            let event_handler = EventHandler::new();
+            let mut event_handler = EventHandler::new();
             event_handler.handle_events(&mut generators, events.into_iter());
             assert_eq!(
                 generators.midi_controller_volume,
@@ -423,7 +423,7 @@ mod test {
                 bytes: &[176, 3, 42],
             }];
             let mut generators = sine_generators();
-            let event_handler = EventHandler::new();
+            let mut event_handler = EventHandler::new();
             let expected = mk_hammond(
                 &[MidiControllerEvent::convert_to_volume_factor(42)],
                 generators.wave_form.table.len(),
@@ -444,7 +444,7 @@ mod test {
         #[test]
         fn adjusts_the_midi_controller_volume() {
             let mut generators = sine_generators();
-            let event_handler = EventHandler::new();
+            let mut event_handler = EventHandler::new();
             event_handler
                 .handle_midi_controller_event(&mut generators, MidiControllerEvent::Volume(0.7));
             assert_eq!(generators.midi_controller_volume, 0.7);
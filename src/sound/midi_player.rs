@@ -1,21 +1,29 @@
 #![allow(clippy::needless_range_loop)]
 
 use super::Player;
-use crate::areas::note_event_source::NoteEventSource;
-use crate::sound::midi::frequency_to_midi;
-use crate::sound::{NoteEvent, POLYPHONY};
+use crate::areas::note_event_source::{NoteEventSource, PitchMode};
+use crate::cli;
+use crate::sound::midi::{
+    controller_to_midi, frequency_to_fractional_midi, nearest_midi_note, pitch_bend_to_midi,
+    velocity_to_midi, MPE_CHANNELS, MPE_PITCH_BEND_RANGE, PITCH_BEND_RANGE,
+};
+use crate::sound::pitch::PitchControl;
+use crate::sound::NoteEvent;
+use crate::utils::triple_buffer::{triple_buffer, Receiver, Sender};
 use crate::{get_binary_name, ErrorString};
 use jack::*;
-use skipchannel::*;
 
 pub struct MidiPlayer {
     _active_client: AsyncClient<(), MidiProcessHandler>,
-    sender: Sender<[NoteEvent; POLYPHONY]>,
+    sender: Sender<Vec<NoteEvent>>,
 }
 
 impl MidiPlayer {
-    pub fn new() -> Result<MidiPlayer, ErrorString> {
-        let (sender, receiver) = skipchannel();
+    pub fn new(
+        cli_args: &cli::Args,
+        pitch_control: PitchControl,
+    ) -> Result<MidiPlayer, ErrorString> {
+        let (sender, receiver) = triple_buffer();
         let (client, _status) =
             jack::Client::new(&get_binary_name()?, jack::ClientOptions::NO_START_SERVER)?;
         let port = client.register_port("output", MidiOut)?;
@@ -24,7 +32,13 @@ impl MidiPlayer {
             MidiProcessHandler {
                 port,
                 receiver,
-                midi_converter: MidiConverter::new(),
+                pitch_control,
+                midi_converter: MidiConverter {
+                    secondary_axis_cc: cli_args.secondary_axis_cc,
+                    mpe: cli_args.mpe,
+                    glide: cli_args.pitch_mode != PitchMode::Discrete,
+                    ..MidiConverter::new(cli_args.polyphony)
+                },
             },
         )?;
         Ok(MidiPlayer {
@@ -35,7 +49,7 @@ impl MidiPlayer {
 }
 
 impl Player for MidiPlayer {
-    fn consume(&self, note_event_source: NoteEventSource) {
+    fn consume(&mut self, note_event_source: NoteEventSource) {
         for slots in note_event_source {
             self.sender.send(slots)
         }
@@ -44,68 +58,226 @@ impl Player for MidiPlayer {
 
 struct MidiProcessHandler {
     port: Port<MidiOut>,
-    receiver: Receiver<[NoteEvent; POLYPHONY]>,
+    receiver: Receiver<Vec<NoteEvent>>,
+    pitch_control: PitchControl,
     midi_converter: MidiConverter,
 }
 
 impl ProcessHandler for MidiProcessHandler {
     fn process(&mut self, _client: &Client, scope: &ProcessScope) -> Control {
         let mut writer = self.port.writer(scope);
+        let mut write = |raw_midi: RawMidi| {
+            let result = writer.write(&raw_midi);
+            match result {
+                Ok(()) => {}
+                Err(e) => eprintln!("MidiProcessHandler.process: error: {:?}", e),
+            }
+        };
+        self.midi_converter.configure(&mut write);
+        self.midi_converter
+            .set_pitch_factor(self.pitch_control.factor(), &mut write);
         match self.receiver.recv() {
             None => {}
-            Some(note_event) => self.midi_converter.connect(note_event, |raw_midi| {
-                let result = writer.write(&raw_midi);
-                match result {
-                    Ok(()) => {}
-                    Err(e) => eprintln!("MidiProcessHandler.process: error: {:?}", e),
-                }
-            }),
+            Some(note_event) => self.midi_converter.connect(note_event, &mut write),
         }
         Control::Continue
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+struct MidiVoice {
+    note: u8,
+    pressure: u8,
+    secondary_axis: u8,
+}
+
 struct MidiConverter {
-    voices: [Option<u8>; POLYPHONY],
+    voices: Vec<Option<MidiVoice>>,
+    // The whole semitones of the factor that is applied to all incoming
+    // frequencies, see `PitchControl` and `set_pitch_factor`.
+    pitch_factor: f32,
+    // the last pitch bend sent on the first channel
+    pitch_bend: [u8; 3],
+    // The controller that the position across the areas (v) is sent to.
+    secondary_axis_cc: Option<u8>,
+    // Sends every voice on its own channel (2 to 16), so that controllers
+    // apply to single notes, as in MIDI Polyphonic Expression. This requires
+    // a polyphony of at most `MPE_CHANNELS`. Notes are then bent from the
+    // nearest midi note to their exact pitch.
+    mpe: bool,
+    // With MPE: the last pitch bend sent on the channel of every voice.
+    voice_pitch_bends: Vec<[u8; 3]>,
+    // Follows pitch changes of held notes with pitch bend instead of
+    // switching notes, for the continuous pitch modes. Requires MPE.
+    glide: bool,
+    configured: bool,
 }
 
 impl MidiConverter {
-    fn new() -> MidiConverter {
+    fn new(polyphony: usize) -> MidiConverter {
         MidiConverter {
-            voices: [None; POLYPHONY],
+            voices: vec![None; polyphony],
+            pitch_factor: 1.0,
+            pitch_bend: pitch_bend_to_midi(0, 0.0, PITCH_BEND_RANGE),
+            secondary_axis_cc: None,
+            mpe: false,
+            voice_pitch_bends: (0..polyphony)
+                .map(|index| pitch_bend_to_midi(1 + index as u8, 0.0, MPE_PITCH_BEND_RANGE))
+                .collect(),
+            glide: false,
+            configured: false,
         }
     }
 
-    fn connect<F>(&mut self, voice_events: [NoteEvent; POLYPHONY], mut callback: F)
+    // Sends the MPE Configuration Message (registered parameter 6 on the
+    // first channel) once, which sets up all other channels for single
+    // voices.
+    fn configure<F>(&mut self, mut callback: F)
     where
         F: FnMut(RawMidi),
     {
-        #[inline]
-        fn send_midi<F: FnMut(RawMidi)>(callback: &mut F, bytes: [u8; 3]) {
+        if self.mpe && !self.configured {
+            for bytes in &[
+                [0b1011_0000, 101, 0],
+                [0b1011_0000, 100, 6],
+                [0b1011_0000, 6, MPE_CHANNELS as u8],
+            ] {
+                callback(RawMidi { time: 0, bytes });
+            }
+        }
+        self.configured = true;
+    }
+
+    // Concert pitches like 442 Hz are less than a semitone away from 440 Hz.
+    // The difference is sent as pitch bend on the first channel, which
+    // applies to all notes (in MPE as well).
+    fn set_pitch_factor<F>(&mut self, pitch_factor: f32, mut callback: F)
+    where
+        F: FnMut(RawMidi),
+    {
+        let semitones = 12.0 * pitch_factor.log2();
+        self.pitch_factor = 2.0_f32.powf(semitones.round() / 12.0);
+        let pitch_bend = pitch_bend_to_midi(0, semitones - semitones.round(), PITCH_BEND_RANGE);
+        if pitch_bend != self.pitch_bend {
             callback(RawMidi {
                 time: 0,
-                bytes: &bytes,
+                bytes: &pitch_bend,
             });
+            self.pitch_bend = pitch_bend;
+        }
+    }
+
+    fn connect<F>(&mut self, voice_events: &[NoteEvent], mut callback: F)
+    where
+        F: FnMut(RawMidi),
+    {
+        #[inline]
+        fn send_midi<F: FnMut(RawMidi)>(callback: &mut F, bytes: &[u8]) {
+            callback(RawMidi { time: 0, bytes });
         };
 
-        for (voice, event) in self.voices.iter_mut().zip(voice_events.iter()) {
-            match (&voice, event) {
-                (None, NoteEvent::NoteOn(frequency)) => {
-                    let midi_note = frequency_to_midi(*frequency);
-                    send_midi(&mut callback, [0b1001_0000, midi_note, 127]);
-                    *voice = Some(midi_note);
+        let pitch_factor = self.pitch_factor;
+        let secondary_axis_cc = self.secondary_axis_cc;
+        let send_secondary_axis = |callback: &mut F, channel: u8, value: u8| {
+            if let Some(controller) = secondary_axis_cc {
+                send_midi(callback, &[0b1011_0000 | channel, controller, value]);
+            }
+        };
+        let mpe = self.mpe;
+        let send_pitch_bend =
+            |callback: &mut F, channel: u8, pitch_bend: &mut [u8; 3], note: u8, pitch: f32| {
+                if mpe {
+                    let new_pitch_bend =
+                        pitch_bend_to_midi(channel, pitch - f32::from(note), MPE_PITCH_BEND_RANGE);
+                    if new_pitch_bend != *pitch_bend {
+                        send_midi(callback, &new_pitch_bend);
+                        *pitch_bend = new_pitch_bend;
+                    }
+                }
+            };
+        let glide = self.mpe && self.glide;
+        let voices = self
+            .voices
+            .iter_mut()
+            .zip(self.voice_pitch_bends.iter_mut())
+            .zip(voice_events.iter());
+        for (index, ((voice, pitch_bend), event)) in voices.enumerate() {
+            let channel = if self.mpe { 1 + index as u8 } else { 0 };
+            match (*voice, event) {
+                (
+                    None,
+                    NoteEvent::NoteOn {
+                        frequency,
+                        velocity,
+                        v,
+                        ..
+                    },
+                ) => {
+                    let pitch = frequency_to_fractional_midi(*frequency * pitch_factor);
+                    let note = nearest_midi_note(pitch);
+                    let velocity = velocity_to_midi(*velocity);
+                    let secondary_axis = controller_to_midi(*v);
+                    send_pitch_bend(&mut callback, channel, pitch_bend, note, pitch);
+                    send_secondary_axis(&mut callback, channel, secondary_axis);
+                    send_midi(&mut callback, &[0b1001_0000 | channel, note, velocity]);
+                    *voice = Some(MidiVoice {
+                        note,
+                        pressure: velocity,
+                        secondary_axis,
+                    });
                 }
-                (Some(midi_note), NoteEvent::NoteOff) => {
-                    send_midi(&mut callback, [0b1000_0000, *midi_note, 0]);
+                (Some(MidiVoice { note, .. }), NoteEvent::NoteOff) => {
+                    send_midi(&mut callback, &[0b1000_0000 | channel, note, 0]);
                     *voice = None;
                 }
-                (Some(old_midi_note), NoteEvent::NoteOn(frequency)) => {
-                    let new_midi_note = frequency_to_midi(*frequency);
-                    if *old_midi_note != new_midi_note {
-                        send_midi(&mut callback, [0b1000_0000, *old_midi_note, 0]);
-                        send_midi(&mut callback, [0b1001_0000, new_midi_note, 127]);
-                        *voice = Some(new_midi_note);
+                (
+                    Some(old_voice),
+                    NoteEvent::NoteOn {
+                        frequency,
+                        velocity,
+                        v,
+                        ..
+                    },
+                ) => {
+                    let pitch = frequency_to_fractional_midi(*frequency * pitch_factor);
+                    // Gliding notes stay on their note as long as the pitch
+                    // bend reaches.
+                    let new_note = if glide
+                        && (pitch - f32::from(old_voice.note)).abs() <= MPE_PITCH_BEND_RANGE
+                    {
+                        old_voice.note
+                    } else {
+                        nearest_midi_note(pitch)
+                    };
+                    let velocity = velocity_to_midi(*velocity);
+                    let secondary_axis = controller_to_midi(*v);
+                    if old_voice.secondary_axis != secondary_axis {
+                        send_secondary_axis(&mut callback, channel, secondary_axis);
                     }
+                    if old_voice.note != new_note {
+                        send_midi(&mut callback, &[0b1000_0000 | channel, old_voice.note, 0]);
+                        send_pitch_bend(&mut callback, channel, pitch_bend, new_note, pitch);
+                        send_midi(&mut callback, &[0b1001_0000 | channel, new_note, velocity]);
+                    } else {
+                        send_pitch_bend(&mut callback, channel, pitch_bend, new_note, pitch);
+                        if old_voice.pressure != velocity {
+                            // With mpe every voice has its own channel, so
+                            // channel pressure is the per-note expression.
+                            if mpe {
+                                send_midi(&mut callback, &[0b1101_0000 | channel, velocity]);
+                            } else {
+                                send_midi(
+                                    &mut callback,
+                                    &[0b1010_0000 | channel, new_note, velocity],
+                                );
+                            }
+                        }
+                    }
+                    *voice = Some(MidiVoice {
+                        note: new_note,
+                        pressure: velocity,
+                        secondary_axis,
+                    });
                 }
                 (None, NoteEvent::NoteOff) => {}
             }
@@ -120,7 +292,8 @@ mod test {
     mod midi_converter {
         use super::*;
         use crate::sound::midi::midi_to_frequency;
-        use crate::sound::test::mk_test_voices;
+        use crate::sound::test::{mk_test_voices, note_on};
+        use crate::sound::DEFAULT_POLYPHONY;
         use NoteEvent::*;
 
         fn make_midi(bytes: &'static [u8]) -> RawMidi<'static> {
@@ -138,10 +311,17 @@ mod test {
         }
 
         fn expect_raw_midi_poly(chunks: Vec<Vec<(usize, NoteEvent)>>, expecteds: Vec<RawMidi>) {
-            let mut converter = MidiConverter::new();
+            expect_raw_midi_with(MidiConverter::new(DEFAULT_POLYPHONY), chunks, expecteds);
+        }
+
+        fn expect_raw_midi_with(
+            mut converter: MidiConverter,
+            chunks: Vec<Vec<(usize, NoteEvent)>>,
+            expecteds: Vec<RawMidi>,
+        ) {
             let mut result = vec![];
             for events in chunks {
-                converter.connect(mk_test_voices(events), |raw_midi| {
+                converter.connect(&mk_test_voices(events), |raw_midi| {
                     result.push(format!("{:?}", raw_midi.bytes));
                 });
             }
@@ -158,7 +338,7 @@ mod test {
             #[test]
             fn converts_note_on_events() {
                 expect_raw_midi(
-                    vec![vec![NoteOn(440.0)]],
+                    vec![vec![note_on(440.0)]],
                     vec![make_midi(&[0b10010000, 69, 127])],
                 );
             }
@@ -166,7 +346,7 @@ mod test {
             #[test]
             fn converts_other_notes_correctly() {
                 expect_raw_midi(
-                    vec![vec![NoteOn(midi_to_frequency(60))]],
+                    vec![vec![note_on(midi_to_frequency(60))]],
                     vec![make_midi(&[0b10010000, 60, 127])],
                 );
             }
@@ -174,7 +354,7 @@ mod test {
             #[test]
             fn converts_note_off_events_correctly() {
                 expect_raw_midi(
-                    vec![vec![NoteOn(midi_to_frequency(57))], vec![]],
+                    vec![vec![note_on(midi_to_frequency(57))], vec![]],
                     vec![
                         make_midi(&[0b10010000, 57, 127]),
                         make_midi(&[0b10000000, 57, 0]),
@@ -185,7 +365,7 @@ mod test {
             #[test]
             fn two_consecutive_note_off_events_trigger_only_one_note_off() {
                 expect_raw_midi(
-                    vec![vec![NoteOn(midi_to_frequency(57))], vec![], vec![]],
+                    vec![vec![note_on(midi_to_frequency(57))], vec![], vec![]],
                     vec![
                         make_midi(&[0b10010000, 57, 127]),
                         make_midi(&[0b10000000, 57, 0]),
@@ -197,8 +377,8 @@ mod test {
             fn two_consecutive_note_on_events_trigger_a_note_off_in_between() {
                 expect_raw_midi(
                     vec![
-                        vec![NoteOn(midi_to_frequency(57))],
-                        vec![NoteOn(midi_to_frequency(60))],
+                        vec![note_on(midi_to_frequency(57))],
+                        vec![note_on(midi_to_frequency(60))],
                     ],
                     vec![
                         make_midi(&[0b10010000, 57, 127]),
@@ -212,8 +392,8 @@ mod test {
             fn two_consecutive_note_on_events_of_the_same_pitch_trigger_only_one_event() {
                 expect_raw_midi(
                     vec![
-                        vec![NoteOn(midi_to_frequency(60))],
-                        vec![NoteOn(midi_to_frequency(60))],
+                        vec![note_on(midi_to_frequency(60))],
+                        vec![note_on(midi_to_frequency(60))],
                     ],
                     vec![make_midi(&[0b10010000, 60, 127])],
                 );
@@ -223,8 +403,8 @@ mod test {
             fn two_consecutive_note_on_events_leave_the_converter_in_a_valid_state() {
                 expect_raw_midi(
                     vec![
-                        vec![NoteOn(midi_to_frequency(57))],
-                        vec![NoteOn(midi_to_frequency(60))],
+                        vec![note_on(midi_to_frequency(57))],
+                        vec![note_on(midi_to_frequency(60))],
                         vec![],
                     ],
                     vec![
@@ -237,6 +417,402 @@ mod test {
             }
         }
 
+        mod velocity {
+            use super::*;
+
+            fn note_on_with_velocity(midi_note: i32, velocity: f32) -> NoteEvent {
+                NoteOn {
+                    frequency: midi_to_frequency(midi_note),
+                    velocity,
+                    u: 0.5,
+                    v: 0.5,
+                }
+            }
+
+            #[test]
+            fn uses_the_velocity_for_note_ons() {
+                expect_raw_midi(
+                    vec![vec![note_on_with_velocity(60, 0.5)]],
+                    vec![make_midi(&[0b10010000, 60, 64])],
+                );
+            }
+
+            #[test]
+            fn sends_polyphonic_aftertouch_for_velocity_changes() {
+                expect_raw_midi(
+                    vec![
+                        vec![note_on_with_velocity(60, 0.5)],
+                        vec![note_on_with_velocity(60, 1.0)],
+                    ],
+                    vec![
+                        make_midi(&[0b10010000, 60, 64]),
+                        make_midi(&[0b10100000, 60, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn sends_channel_pressure_for_velocity_changes_with_mpe() {
+                expect_raw_midi_with(
+                    MidiConverter {
+                        mpe: true,
+                        ..MidiConverter::new(DEFAULT_POLYPHONY)
+                    },
+                    vec![
+                        vec![(0, note_on_with_velocity(60, 0.5))],
+                        vec![(0, note_on_with_velocity(60, 1.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b10010001, 60, 64]),
+                        make_midi(&[0b11010001, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn does_not_repeat_unchanged_aftertouch() {
+                expect_raw_midi(
+                    vec![
+                        vec![note_on_with_velocity(60, 0.5)],
+                        vec![note_on_with_velocity(60, 0.5)],
+                    ],
+                    vec![make_midi(&[0b10010000, 60, 64])],
+                );
+            }
+
+            #[test]
+            fn uses_the_new_velocity_when_changing_notes() {
+                expect_raw_midi(
+                    vec![
+                        vec![note_on_with_velocity(60, 1.0)],
+                        vec![note_on_with_velocity(62, 0.5)],
+                    ],
+                    vec![
+                        make_midi(&[0b10010000, 60, 127]),
+                        make_midi(&[0b10000000, 60, 0]),
+                        make_midi(&[0b10010000, 62, 64]),
+                    ],
+                );
+            }
+        }
+
+        mod pitch {
+            use super::*;
+
+            // Plays middle c with the given concert pitch and transposition.
+            fn play(concert_pitch: f32, transposition: i32) -> Vec<Vec<u8>> {
+                let mut converter = MidiConverter::new(DEFAULT_POLYPHONY);
+                let mut result = vec![];
+                let pitch_factor = PitchControl::new(concert_pitch, transposition).factor();
+                converter.set_pitch_factor(pitch_factor, |raw_midi| {
+                    result.push(raw_midi.bytes.to_vec());
+                });
+                converter.connect(
+                    &mk_test_voices(vec![(0, note_on(midi_to_frequency(60)))]),
+                    |raw_midi| {
+                        result.push(raw_midi.bytes.to_vec());
+                    },
+                );
+                result
+            }
+
+            #[test]
+            fn does_not_send_pitch_bend_for_440_hz() {
+                assert_eq!(play(440.0, 0), vec![vec![0b10010000, 60, 127]]);
+            }
+
+            #[test]
+            fn applies_the_transposition() {
+                assert_eq!(play(440.0, -12), vec![vec![0b10010000, 48, 127]]);
+            }
+
+            #[test]
+            fn sends_pitch_bend_for_concert_pitches_close_to_440_hz() {
+                assert_eq!(
+                    play(432.0, 0),
+                    vec![vec![0b11100000, 107, 53], vec![0b10010000, 60, 127]]
+                );
+                assert_eq!(
+                    play(442.0, 0),
+                    vec![vec![0b11100000, 66, 66], vec![0b10010000, 60, 127]]
+                );
+            }
+
+            #[test]
+            fn applies_whole_semitones_of_the_concert_pitch_to_the_notes() {
+                assert_eq!(
+                    play(466.0, 0),
+                    vec![vec![0b11100000, 103, 63], vec![0b10010000, 61, 127]]
+                );
+            }
+
+            #[test]
+            fn sends_pitch_bend_when_the_concert_pitch_changes_while_playing() {
+                let pitch_control = PitchControl::new(440.0, 0);
+                let mut converter = MidiConverter::new(DEFAULT_POLYPHONY);
+                let mut result = vec![];
+                for _ in 0..2 {
+                    converter.set_pitch_factor(pitch_control.factor(), |raw_midi| {
+                        result.push(raw_midi.bytes.to_vec());
+                    });
+                    pitch_control.change_concert_pitch(1.0);
+                }
+                assert_eq!(result, vec![vec![0b11100000, 33, 65]]);
+            }
+
+            #[test]
+            fn switches_notes_when_transposed_while_playing() {
+                let mut converter = MidiConverter::new(DEFAULT_POLYPHONY);
+                let mut result = vec![];
+                converter.connect(
+                    &mk_test_voices(vec![(0, note_on(midi_to_frequency(60)))]),
+                    |raw_midi| {
+                        result.push(raw_midi.bytes.to_vec());
+                    },
+                );
+                converter.set_pitch_factor(PitchControl::new(440.0, 2).factor(), |raw_midi| {
+                    result.push(raw_midi.bytes.to_vec());
+                });
+                converter.connect(
+                    &mk_test_voices(vec![(0, note_on(midi_to_frequency(60)))]),
+                    |raw_midi| {
+                        result.push(raw_midi.bytes.to_vec());
+                    },
+                );
+                assert_eq!(
+                    result,
+                    vec![
+                        vec![0b10010000, 60, 127],
+                        vec![0b10000000, 60, 0],
+                        vec![0b10010000, 62, 127],
+                    ]
+                );
+            }
+        }
+
+        mod mpe_pitch_bend {
+            use super::*;
+
+            fn note_on_at_pitch(fractional_midi: f32) -> NoteEvent {
+                note_on(440.0 * 2.0_f32.powf((fractional_midi - 69.0) / 12.0))
+            }
+
+            fn converter(glide: bool) -> MidiConverter {
+                MidiConverter {
+                    mpe: true,
+                    glide,
+                    ..MidiConverter::new(DEFAULT_POLYPHONY)
+                }
+            }
+
+            #[test]
+            fn bends_notes_to_their_exact_pitch() {
+                expect_raw_midi_with(
+                    converter(false),
+                    vec![vec![(0, note_on_at_pitch(60.25))]],
+                    vec![
+                        make_midi(&[0b11100001, 43, 64]),
+                        make_midi(&[0b10010001, 60, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn is_not_sent_without_mpe() {
+                expect_raw_midi(
+                    vec![vec![note_on_at_pitch(60.25)]],
+                    vec![make_midi(&[0b10010000, 60, 127])],
+                );
+            }
+
+            #[test]
+            fn resets_the_pitch_bend_for_the_next_note() {
+                expect_raw_midi_with(
+                    converter(false),
+                    vec![
+                        vec![(0, note_on_at_pitch(60.25))],
+                        vec![],
+                        vec![(0, note_on_at_pitch(62.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b11100001, 43, 64]),
+                        make_midi(&[0b10010001, 60, 127]),
+                        make_midi(&[0b10000001, 60, 0]),
+                        make_midi(&[0b11100001, 0, 64]),
+                        make_midi(&[0b10010001, 62, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn switches_notes_without_glide() {
+                expect_raw_midi_with(
+                    converter(false),
+                    vec![
+                        vec![(0, note_on_at_pitch(60.0))],
+                        vec![(0, note_on_at_pitch(62.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b10010001, 60, 127]),
+                        make_midi(&[0b10000001, 60, 0]),
+                        make_midi(&[0b10010001, 62, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn follows_gliding_notes_with_pitch_bend() {
+                expect_raw_midi_with(
+                    converter(true),
+                    vec![
+                        vec![(0, note_on_at_pitch(60.0))],
+                        vec![(0, note_on_at_pitch(62.5))],
+                        vec![(0, note_on_at_pitch(62.5))],
+                    ],
+                    vec![
+                        make_midi(&[0b10010001, 60, 127]),
+                        make_midi(&[0b11100001, 43, 67]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn switches_notes_when_gliding_out_of_the_pitch_bend_range() {
+                expect_raw_midi_with(
+                    converter(true),
+                    vec![
+                        vec![(0, note_on_at_pitch(20.0))],
+                        vec![(0, note_on_at_pitch(80.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b10010001, 20, 127]),
+                        make_midi(&[0b10000001, 20, 0]),
+                        make_midi(&[0b10010001, 80, 127]),
+                    ],
+                );
+            }
+        }
+
+        mod secondary_axis {
+            use super::*;
+
+            fn note_on_at(midi_note: i32, v: f32) -> NoteEvent {
+                NoteOn {
+                    frequency: midi_to_frequency(midi_note),
+                    velocity: 1.0,
+                    u: 0.5,
+                    v,
+                }
+            }
+
+            fn converter(mpe: bool) -> MidiConverter {
+                MidiConverter {
+                    secondary_axis_cc: Some(74),
+                    mpe,
+                    ..MidiConverter::new(DEFAULT_POLYPHONY)
+                }
+            }
+
+            #[test]
+            fn is_not_sent_by_default() {
+                expect_raw_midi(
+                    vec![vec![note_on_at(60, 0.2)], vec![note_on_at(60, 0.8)]],
+                    vec![make_midi(&[0b10010000, 60, 127])],
+                );
+            }
+
+            #[test]
+            fn sends_the_configured_controller_before_note_ons() {
+                expect_raw_midi_with(
+                    converter(false),
+                    vec![vec![(0, note_on_at(60, 0.5))]],
+                    vec![
+                        make_midi(&[0b10110000, 74, 64]),
+                        make_midi(&[0b10010000, 60, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn sends_changes() {
+                expect_raw_midi_with(
+                    converter(false),
+                    vec![
+                        vec![(0, note_on_at(60, 0.5))],
+                        vec![(0, note_on_at(60, 0.5))],
+                        vec![(0, note_on_at(60, 1.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b10110000, 74, 64]),
+                        make_midi(&[0b10010000, 60, 127]),
+                        make_midi(&[0b10110000, 74, 127]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn uses_one_channel_per_voice_for_mpe() {
+                expect_raw_midi_with(
+                    converter(true),
+                    vec![
+                        vec![(0, note_on_at(60, 0.0))],
+                        vec![(0, note_on_at(60, 0.0)), (1, note_on_at(62, 1.0))],
+                        vec![(1, note_on_at(62, 1.0))],
+                    ],
+                    vec![
+                        make_midi(&[0b10110001, 74, 0]),
+                        make_midi(&[0b10010001, 60, 127]),
+                        make_midi(&[0b10110010, 74, 127]),
+                        make_midi(&[0b10010010, 62, 127]),
+                        make_midi(&[0b10000001, 60, 0]),
+                    ],
+                );
+            }
+
+            #[test]
+            fn uses_the_last_channel_for_the_fifteenth_voice() {
+                expect_raw_midi_with(
+                    MidiConverter {
+                        mpe: true,
+                        ..MidiConverter::new(MPE_CHANNELS)
+                    },
+                    vec![vec![(14, note_on(midi_to_frequency(60)))]],
+                    vec![make_midi(&[0b10011111, 60, 127])],
+                );
+            }
+
+            fn configuration(mut converter: MidiConverter) -> Vec<Vec<u8>> {
+                let mut result = vec![];
+                for _ in 0..2 {
+                    converter.configure(|raw_midi| result.push(raw_midi.bytes.to_vec()));
+                }
+                result
+            }
+
+            #[test]
+            fn sends_the_mpe_configuration_once() {
+                assert_eq!(
+                    configuration(MidiConverter {
+                        mpe: true,
+                        ..MidiConverter::new(MPE_CHANNELS)
+                    }),
+                    vec![
+                        vec![0b10110000, 101, 0],
+                        vec![0b10110000, 100, 6],
+                        vec![0b10110000, 6, 15],
+                    ]
+                );
+            }
+
+            #[test]
+            fn does_not_send_the_mpe_configuration_without_mpe() {
+                assert_eq!(
+                    configuration(MidiConverter::new(DEFAULT_POLYPHONY)),
+                    Vec::<Vec<u8>>::new()
+                );
+            }
+        }
+
         mod polyphony {
             use super::*;
 
@@ -244,10 +820,10 @@ mod test {
             fn allows_to_play_two_notes_simultaneously() {
                 expect_raw_midi_poly(
                     vec![
-                        vec![(0, NoteOn(midi_to_frequency(60)))],
+                        vec![(0, note_on(midi_to_frequency(60)))],
                         vec![
-                            (0, NoteOn(midi_to_frequency(60))),
-                            (1, NoteOn(midi_to_frequency(62))),
+                            (0, note_on(midi_to_frequency(60))),
+                            (1, note_on(midi_to_frequency(62))),
                         ],
                     ],
                     vec![
@@ -261,12 +837,12 @@ mod test {
             fn handles_overlapping_legato_melodies_correctly() {
                 expect_raw_midi_poly(
                     vec![
-                        vec![(0, NoteOn(midi_to_frequency(60)))],
+                        vec![(0, note_on(midi_to_frequency(60)))],
                         vec![
-                            (0, NoteOn(midi_to_frequency(60))),
-                            (1, NoteOn(midi_to_frequency(62))),
+                            (0, note_on(midi_to_frequency(60))),
+                            (1, note_on(midi_to_frequency(62))),
                         ],
-                        vec![(1, NoteOn(midi_to_frequency(62)))],
+                        vec![(1, note_on(midi_to_frequency(62)))],
                         vec![],
                     ],
                     vec![
@@ -282,12 +858,12 @@ mod test {
             fn handles_note_offs_for_temporary_additional_notes_correctly() {
                 expect_raw_midi_poly(
                     vec![
-                        vec![(0, NoteOn(midi_to_frequency(60)))],
+                        vec![(0, note_on(midi_to_frequency(60)))],
                         vec![
-                            (0, NoteOn(midi_to_frequency(60))),
-                            (1, NoteOn(midi_to_frequency(62))),
+                            (0, note_on(midi_to_frequency(60))),
+                            (1, note_on(midi_to_frequency(62))),
                         ],
-                        vec![(0, NoteOn(midi_to_frequency(60)))],
+                        vec![(0, note_on(midi_to_frequency(60)))],
                         vec![],
                     ],
                     vec![
@@ -302,7 +878,7 @@ mod test {
             #[test]
             fn does_not_rely_on_the_first_slot_being_used() {
                 expect_raw_midi_poly(
-                    vec![vec![(1, NoteOn(midi_to_frequency(60)))]],
+                    vec![vec![(1, note_on(midi_to_frequency(60)))]],
                     vec![make_midi(&[0b10010000, 60, 127])],
                 );
             }
@@ -310,7 +886,10 @@ mod test {
             #[test]
             fn uses_the_last_slot() {
                 expect_raw_midi_poly(
-                    vec![vec![(POLYPHONY - 1, NoteOn(midi_to_frequency(60)))]],
+                    vec![vec![(
+                        DEFAULT_POLYPHONY - 1,
+                        note_on(midi_to_frequency(60)),
+                    )]],
                     vec![make_midi(&[0b10010000, 60, 127])],
                 );
             }
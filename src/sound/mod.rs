@@ -5,55 +5,58 @@ pub mod logger;
 pub mod midi;
 pub mod midi_controller;
 pub mod midi_player;
+pub mod pitch;
+pub mod scala;
+pub mod tuning;
+pub mod voice_allocator;
 pub mod wave_form;
 
 use crate::areas::note_event_source::NoteEventSource;
 
 const TAU: f32 = ::std::f32::consts::PI * 2.0;
 
-pub const POLYPHONY: usize = 20;
-
-pub fn mk_voices<T: Clone>(element: T) -> [T; POLYPHONY] {
-    [
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element.clone(),
-        element,
-    ]
-}
+pub const DEFAULT_POLYPHONY: usize = 20;
 
 pub trait Player {
-    fn consume(&self, note_event_source: NoteEventSource);
+    fn consume(&mut self, note_event_source: NoteEventSource);
+}
+
+pub struct NullPlayer;
+
+impl Player for NullPlayer {
+    fn consume(&mut self, note_event_source: NoteEventSource) {
+        for _ in note_event_source {}
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum NoteEvent {
     NoteOff,
-    NoteOn(f32),
+    // The velocity ranges from 0 to 1, as do u and v, the position of the
+    // touch inside of its area (see `Shape::normalized_position`).
+    NoteOn {
+        frequency: f32,
+        velocity: f32,
+        u: f32,
+        v: f32,
+    },
 }
 
 #[cfg(test)]
 pub mod test {
     use super::*;
 
-    pub fn mk_test_voices(note_ons: Vec<(usize, NoteEvent)>) -> [NoteEvent; POLYPHONY] {
-        let mut result = mk_voices(NoteEvent::NoteOff);
+    pub fn note_on(frequency: f32) -> NoteEvent {
+        NoteEvent::NoteOn {
+            frequency,
+            velocity: 1.0,
+            u: 0.5,
+            v: 0.5,
+        }
+    }
+
+    pub fn mk_test_voices(note_ons: Vec<(usize, NoteEvent)>) -> Vec<NoteEvent> {
+        let mut result = vec![NoteEvent::NoteOff; DEFAULT_POLYPHONY];
         for (i, note) in note_ons {
             result[i] = note;
         }
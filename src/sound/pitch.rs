@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::sync::Arc;
+
+pub const DEFAULT_CONCERT_PITCH: f32 = 440.0;
+
+pub const MIN_CONCERT_PITCH: f32 = 220.0;
+pub const MAX_CONCERT_PITCH: f32 = 880.0;
+
+// in semitones, in both directions
+pub const MAX_TRANSPOSITION: i32 = 127;
+
+// Concert pitch and transposition, shared between the ui (which allows to
+// change them while playing) and the players (which apply them to the
+// frequencies coming from the areas).
+#[derive(Debug, Clone)]
+pub struct PitchControl {
+    // stored as the bits of an f32
+    concert_pitch: Arc<AtomicU32>,
+    // in semitones
+    transposition: Arc<AtomicI32>,
+}
+
+impl PitchControl {
+    pub fn new(concert_pitch: f32, transposition: i32) -> PitchControl {
+        PitchControl {
+            concert_pitch: Arc::new(AtomicU32::new(concert_pitch.to_bits())),
+            transposition: Arc::new(AtomicI32::new(clamp_transposition(transposition))),
+        }
+    }
+
+    pub fn concert_pitch(&self) -> f32 {
+        f32::from_bits(self.concert_pitch.load(Ordering::SeqCst))
+    }
+
+    pub fn change_concert_pitch(&self, hertz: f32) {
+        let concert_pitch = (self.concert_pitch() + hertz)
+            .max(MIN_CONCERT_PITCH)
+            .min(MAX_CONCERT_PITCH);
+        self.concert_pitch
+            .store(concert_pitch.to_bits(), Ordering::SeqCst);
+    }
+
+    pub fn transposition(&self) -> i32 {
+        self.transposition.load(Ordering::SeqCst)
+    }
+
+    pub fn transpose(&self, semitones: i32) {
+        let _ =
+            self.transposition
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |transposition| {
+                    Some(clamp_transposition(transposition.saturating_add(semitones)))
+                });
+    }
+
+    // The factor to multiply frequencies with.
+    pub fn factor(&self) -> f32 {
+        self.concert_pitch() / DEFAULT_CONCERT_PITCH
+            * 2.0_f32.powf(self.transposition() as f32 / 12.0)
+    }
+}
+
+fn clamp_transposition(transposition: i32) -> i32 {
+    transposition.max(-MAX_TRANSPOSITION).min(MAX_TRANSPOSITION)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn leaves_frequencies_alone_by_default() {
+        assert_eq!(PitchControl::new(440.0, 0).factor(), 1.0);
+    }
+
+    #[test]
+    fn applies_the_concert_pitch() {
+        assert_eq!(PitchControl::new(442.0, 0).factor(), 442.0 / 440.0);
+    }
+
+    #[test]
+    fn applies_the_transposition() {
+        assert_eq!(PitchControl::new(440.0, 12).factor(), 2.0);
+        assert_eq!(PitchControl::new(440.0, -24).factor(), 0.25);
+        assert_eq!(PitchControl::new(220.0, 12).factor(), 1.0);
+    }
+
+    #[test]
+    fn is_shared_between_clones() {
+        let pitch_control = PitchControl::new(440.0, 0);
+        let clone = pitch_control.clone();
+        ::std::thread::spawn(move || {
+            clone.transpose(-12);
+            clone.change_concert_pitch(2.0);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(pitch_control.transposition(), -12);
+        assert_eq!(pitch_control.concert_pitch(), 442.0);
+    }
+
+    #[test]
+    fn keeps_the_concert_pitch_in_range() {
+        let pitch_control = PitchControl::new(MAX_CONCERT_PITCH, 0);
+        pitch_control.change_concert_pitch(1.0);
+        assert_eq!(pitch_control.concert_pitch(), MAX_CONCERT_PITCH);
+    }
+
+    #[test]
+    fn keeps_the_transposition_in_range() {
+        let pitch_control = PitchControl::new(440.0, MAX_TRANSPOSITION - 6);
+        pitch_control.transpose(12);
+        assert_eq!(pitch_control.transposition(), MAX_TRANSPOSITION);
+        pitch_control.transpose(-1000);
+        assert_eq!(pitch_control.transposition(), -MAX_TRANSPOSITION);
+    }
+}
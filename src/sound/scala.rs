@@ -0,0 +1,272 @@
+// Parsers for the Scala tuning formats, see
+// http://www.huygens-fokker.org/scala/scl_format.html and
+// http://www.huygens-fokker.org/scala/help.htm#mappings
+use crate::sound::tuning::{parse_pitch, KeyboardMapping, Tuning};
+use crate::AddMessage;
+use crate::ErrorString;
+use ::std::fs;
+use ::std::str::FromStr;
+
+// Lines that aren't comments, with their line numbers.
+fn lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+fn line_error<T>(line_number: usize, message: String) -> Result<T, ErrorString> {
+    Err(ErrorString(format!("line {}: {}", line_number, message)))
+}
+
+fn parse_value<T: FromStr>(
+    (line_number, line): (usize, &str),
+    description: &str,
+) -> Result<T, ErrorString> {
+    match line.split_whitespace().next().map(str::parse) {
+        Some(Ok(value)) => Ok(value),
+        _ => line_error(line_number, format!("invalid {}: {}", description, line)),
+    }
+}
+
+pub fn parse_scl(contents: &str) -> Result<Tuning, ErrorString> {
+    let mut lines = lines(contents);
+    let end_of_file = |description: &str| {
+        Err(ErrorString(format!(
+            "unexpected end of file, expected {}",
+            description
+        )))
+    };
+    if lines.next().is_none() {
+        return end_of_file("a description");
+    }
+    let number_of_notes: usize = match lines.next() {
+        Some(line) => parse_value(line, "number of notes")?,
+        None => return end_of_file("the number of notes"),
+    };
+    let mut degrees = vec![];
+    for _ in 0..number_of_notes {
+        match lines.next() {
+            Some((line_number, line)) => {
+                let pitch = line.split_whitespace().next().unwrap_or("");
+                match parse_pitch(pitch) {
+                    Ok(cents) => degrees.push(cents),
+                    Err(ErrorString(message)) => return line_error(line_number, message),
+                }
+            }
+            None => {
+                return end_of_file(&format!(
+                    "{} pitches, found {}",
+                    number_of_notes,
+                    degrees.len()
+                ))
+            }
+        }
+    }
+    Tuning::from_cents(degrees)
+}
+
+pub fn parse_kbm(contents: &str) -> Result<KeyboardMapping, ErrorString> {
+    let mut lines = lines(contents).filter(|(_, line)| !line.is_empty());
+    let mut next = |description: &str| match lines.next() {
+        Some(line) => Ok(line),
+        None => Err(ErrorString(format!(
+            "unexpected end of file, expected {}",
+            description
+        ))),
+    };
+    let map_size: usize = parse_value(next("the map size")?, "map size")?;
+    let first_note = parse_value(next("the first note")?, "first note")?;
+    let last_note = parse_value(next("the last note")?, "last note")?;
+    let middle_note = parse_value(next("the middle note")?, "middle note")?;
+    let reference_note = parse_value(next("the reference note")?, "reference note")?;
+    let (line_number, line) = next("the reference frequency")?;
+    let reference_frequency: f32 = parse_value((line_number, line), "reference frequency")?;
+    if reference_frequency <= 0.0 {
+        return line_error(
+            line_number,
+            format!("invalid reference frequency: {}", line),
+        );
+    }
+    let octave_degree = parse_value(next("the octave degree")?, "octave degree")?;
+    let mut keys = vec![];
+    // Missing keys at the end are unmapped.
+    for (line_number, line) in lines.take(map_size) {
+        keys.push(match line.split_whitespace().next() {
+            Some("x") => None,
+            _ => Some(parse_value((line_number, line), "scale degree")?),
+        });
+    }
+    keys.resize(map_size, None);
+    Ok(KeyboardMapping {
+        keys,
+        first_note,
+        last_note,
+        middle_note,
+        reference_note,
+        reference_frequency,
+        octave_degree,
+    })
+}
+
+pub fn load_scl(file: &str) -> Result<Tuning, ErrorString> {
+    parse_scl(&fs::read_to_string(file).add_message(format!("cannot read {}", file))?)
+        .add_message(file.to_string())
+}
+
+pub fn load_kbm(file: &str) -> Result<KeyboardMapping, ErrorString> {
+    parse_kbm(&fs::read_to_string(file).add_message(format!("cannot read {}", file))?)
+        .add_message(file.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn error(result: Result<impl ::std::fmt::Debug, ErrorString>) -> String {
+        match result {
+            Ok(value) => panic!("expected an error, got: {:?}", value),
+            Err(ErrorString(message)) => message,
+        }
+    }
+
+    mod scl {
+        use super::*;
+
+        const MEANTONE: &str = "! meantone.scl
+!
+Quarter comma meantone (approximated)
+ 4
+!
+ 193.157
+ 386.314 major third
+ 696.579
+ 2/1
+";
+
+        #[test]
+        fn parses_cents_and_ratios() {
+            assert_eq!(
+                parse_scl(MEANTONE).unwrap(),
+                Tuning::from_cents(vec![193.157, 386.314, 696.579, 1200.0]).unwrap()
+            );
+        }
+
+        #[test]
+        fn allows_empty_descriptions() {
+            assert_eq!(
+                parse_scl("\n1\n3/1").unwrap(),
+                Tuning::from_ratios(vec![3.0]).unwrap()
+            );
+        }
+
+        #[test]
+        fn reports_invalid_pitches_with_line_numbers() {
+            assert_eq!(
+                error(parse_scl(&MEANTONE.replace("696.579", "fifth"))),
+                "line 8: invalid pitch: fifth, expected cents (e.g. 701.955) or a ratio (e.g. 3/2)"
+            );
+        }
+
+        #[test]
+        fn reports_an_invalid_number_of_notes() {
+            assert_eq!(
+                error(parse_scl(&MEANTONE.replace(" 4\n", " four\n"))),
+                "line 4: invalid number of notes: four"
+            );
+        }
+
+        #[test]
+        fn reports_missing_pitches() {
+            assert_eq!(
+                error(parse_scl(&MEANTONE.replace(" 4\n", " 5\n"))),
+                "unexpected end of file, expected 5 pitches, found 4"
+            );
+        }
+    }
+
+    mod kbm {
+        use super::*;
+
+        // maps the seven notes of a scale to the white keys
+        const WHITE_KEYS: &str = "! white keys
+12
+0
+127
+60
+69
+440.0
+7
+! mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+
+        #[test]
+        fn parses_keyboard_mappings() {
+            assert_eq!(
+                parse_kbm(WHITE_KEYS).unwrap(),
+                KeyboardMapping {
+                    keys: vec![
+                        Some(0),
+                        None,
+                        Some(1),
+                        None,
+                        Some(2),
+                        Some(3),
+                        None,
+                        Some(4),
+                        None,
+                        Some(5),
+                        None,
+                        Some(6),
+                    ],
+                    first_note: 0,
+                    last_note: 127,
+                    middle_note: 60,
+                    reference_note: 69,
+                    reference_frequency: 440.0,
+                    octave_degree: 7,
+                }
+            );
+        }
+
+        #[test]
+        fn leaves_missing_keys_unmapped() {
+            let mapping = parse_kbm(&WHITE_KEYS.replace("x\n5\nx\n6\n", "")).unwrap();
+            assert_eq!(mapping.keys.len(), 12);
+            assert_eq!(&mapping.keys[8..], &[None, None, None, None]);
+        }
+
+        #[test]
+        fn reports_errors_with_line_numbers() {
+            assert_eq!(
+                error(parse_kbm(&WHITE_KEYS.replace("440.0", "A"))),
+                "line 7: invalid reference frequency: A"
+            );
+            assert_eq!(
+                error(parse_kbm(&WHITE_KEYS.replace("\n3\n", "\nthree\n"))),
+                "line 15: invalid scale degree: three"
+            );
+        }
+
+        #[test]
+        fn reports_missing_header_lines() {
+            assert_eq!(
+                error(parse_kbm("12\n0\n127\n")),
+                "unexpected end of file, expected the middle note"
+            );
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use crate::sound::scala;
+use crate::ErrorString;
+
+// Note 0 is the C five octaves below the middle C in every tuning, so that
+// 12edo agrees with midi notes.
+const A4_CENTS: f32 = 6900.0;
+
+const A4_FREQUENCY: f32 = 440.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    // Pitches of the scale degrees in cents above the first degree, which
+    // is left out (as in Scala files). The last one is the period (usually
+    // the octave) after which the scale repeats.
+    degrees: Vec<f32>,
+    keyboard_mapping: Option<KeyboardMapping>,
+}
+
+// Maps notes to scale degrees, as in Scala .kbm files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    // Scale degrees of the notes of one repetition of the mapping, starting
+    // at the middle note. None leaves a note unmapped. If there are no keys,
+    // notes are mapped to consecutive scale degrees.
+    pub keys: Vec<Option<i32>>,
+    pub first_note: i32,
+    pub last_note: i32,
+    // the note that plays the first scale degree
+    pub middle_note: i32,
+    pub reference_note: i32,
+    pub reference_frequency: f32,
+    // the scale degree reached after one repetition of the mapping
+    pub octave_degree: i32,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning::edo(12)
+    }
+}
+
+impl Tuning {
+    // Equal division of the octave into the given number of steps.
+    pub fn edo(divisions: i32) -> Tuning {
+        Tuning {
+            degrees: (1..=divisions)
+                .map(|step| step as f32 * 1200.0 / divisions as f32)
+                .collect(),
+            keyboard_mapping: None,
+        }
+    }
+
+    pub fn from_cents(degrees: Vec<f32>) -> Result<Tuning, ErrorString> {
+        match degrees.last() {
+            Some(period) if *period > 0.0 => Ok(Tuning {
+                degrees,
+                keyboard_mapping: None,
+            }),
+            Some(period) => Err(ErrorString(format!(
+                "invalid tuning: the period (the last degree) has to be positive, got {} cents",
+                period
+            ))),
+            None => Err(ErrorString::from("invalid tuning: no scale degrees given")),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn from_ratios(ratios: Vec<f32>) -> Result<Tuning, ErrorString> {
+        Tuning::from_cents(ratios.into_iter().map(ratio_to_cents).collect())
+    }
+
+    pub fn with_keyboard_mapping(
+        self,
+        keyboard_mapping: KeyboardMapping,
+    ) -> Result<Tuning, ErrorString> {
+        let tuning = Tuning {
+            keyboard_mapping: Some(keyboard_mapping.clone()),
+            ..self
+        };
+        if tuning.degree(keyboard_mapping.reference_note).is_none() {
+            return Err(ErrorString(format!(
+                "the reference note {} of the keyboard mapping is not mapped",
+                keyboard_mapping.reference_note
+            )));
+        }
+        Ok(tuning)
+    }
+
+    // Parses either a Scala file (ending in .scl), an equal division of the
+    // octave, e.g. "31edo", or a comma separated list of scale degrees, e.g.
+    // "9/8,5/4,4/3,3/2,5/3,15/8,2/1".
+    pub fn parse(input: &str) -> Result<Tuning, ErrorString> {
+        if input.ends_with(".scl") {
+            return scala::load_scl(input);
+        }
+        let lowercase = input.trim().to_lowercase();
+        if let Some(divisions) = lowercase.strip_suffix("edo") {
+            return match divisions.trim_end_matches('-').parse() {
+                Ok(divisions) if divisions > 0 => Ok(Tuning::edo(divisions)),
+                _ => Err(ErrorString(format!(
+                    "invalid tuning: {}, expected a positive number of divisions, e.g. 31edo",
+                    input
+                ))),
+            };
+        }
+        let degrees: Result<Vec<f32>, ErrorString> = input.split(',').map(parse_pitch).collect();
+        Tuning::from_cents(degrees?)
+    }
+
+    // number of scale degrees per period
+    pub fn size(&self) -> i32 {
+        self.degrees.len() as i32
+    }
+
+    // number of notes after which the colors repeat
+    pub fn notes_per_period(&self) -> i32 {
+        match &self.keyboard_mapping {
+            Some(mapping) if !mapping.keys.is_empty() => mapping.keys.len() as i32,
+            _ => self.size(),
+        }
+    }
+
+    fn period(&self) -> f32 {
+        self.degrees[self.degrees.len() - 1]
+    }
+
+    // Without a keyboard mapping notes are scale degrees.
+    fn degree(&self, note: i32) -> Option<i32> {
+        match &self.keyboard_mapping {
+            None => Some(note),
+            Some(mapping) => {
+                if note < mapping.first_note || note > mapping.last_note {
+                    return None;
+                }
+                let offset = note - mapping.middle_note;
+                if mapping.keys.is_empty() {
+                    return Some(offset);
+                }
+                let size = mapping.keys.len() as i32;
+                mapping.keys[offset.rem_euclid(size) as usize]
+                    .map(|degree| offset.div_euclid(size) * mapping.octave_degree + degree)
+            }
+        }
+    }
+
+    pub fn cents(&self, degree: i32) -> f32 {
+        let periods = degree.div_euclid(self.size());
+        let degree = degree.rem_euclid(self.size());
+        let degree_cents = if degree == 0 {
+            0.0
+        } else {
+            self.degrees[degree as usize - 1]
+        };
+        periods as f32 * self.period() + degree_cents
+    }
+
+    // None for notes that the keyboard mapping leaves unmapped.
+    pub fn frequency(&self, note: i32) -> Option<f32> {
+        let cents = self.cents(self.degree(note)?);
+        Some(match &self.keyboard_mapping {
+            None => A4_FREQUENCY * 2.0_f32.powf((cents - A4_CENTS) / 1200.0),
+            Some(mapping) => {
+                let reference_cents = self.cents(self.degree(mapping.reference_note)?);
+                mapping.reference_frequency * 2.0_f32.powf((cents - reference_cents) / 1200.0)
+            }
+        })
+    }
+
+    // The note that comes closest to the given number of (12edo) semitones.
+    // This is used to translate the built-in layouts to other tunings. With
+    // a keyboard mapping the notes are used like midi notes.
+    pub fn steps(&self, semitones: i32) -> i32 {
+        if self.keyboard_mapping.is_some() {
+            return semitones;
+        }
+        let cents = semitones as f32 * 100.0;
+        let estimate = (cents / self.period() * self.size() as f32).round() as i32;
+        (estimate - 1..=estimate + 1)
+            .min_by(|a, b| {
+                let distance = |note: i32| (self.cents(note) - cents).abs();
+                distance(*a)
+                    .partial_cmp(&distance(*b))
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(estimate)
+    }
+}
+
+fn ratio_to_cents(ratio: f32) -> f32 {
+    1200.0 * ratio.log2()
+}
+
+// Pitches are given like in Scala files: values containing a period are
+// cents, other values are ratios like "3/2" or "2".
+pub fn parse_pitch(input: &str) -> Result<f32, ErrorString> {
+    let input = input.trim();
+    let error = || {
+        ErrorString(format!(
+            "invalid pitch: {}, expected cents (e.g. 701.955) or a ratio (e.g. 3/2)",
+            input
+        ))
+    };
+    if input.contains('.') {
+        return input.parse().map_err(|_| error());
+    }
+    let mut parts = input.splitn(2, '/');
+    let numerator: u32 = parts.next().unwrap_or("").parse().map_err(|_| error())?;
+    let denominator: u32 = match parts.next() {
+        Some(denominator) => denominator.parse().map_err(|_| error())?,
+        None => 1,
+    };
+    if numerator == 0 || denominator == 0 {
+        return Err(error());
+    }
+    Ok(ratio_to_cents(numerator as f32 / denominator as f32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sound::midi::midi_to_frequency;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    mod edo {
+        use super::*;
+
+        #[test]
+        fn agrees_with_midi_notes_by_default() {
+            let tuning = Tuning::default();
+            for note in 0..128 {
+                assert_eq!(tuning.frequency(note), Some(midi_to_frequency(note)));
+            }
+        }
+
+        #[test]
+        fn divides_the_octave_into_equal_steps() {
+            let tuning = Tuning::edo(31);
+            assert_eq!(tuning.size(), 31);
+            assert_close(tuning.cents(1), 1200.0 / 31.0);
+            assert_close(tuning.cents(31), 1200.0);
+            assert_close(tuning.cents(-1), -1200.0 / 31.0);
+        }
+
+        #[test]
+        fn starts_on_the_same_c_as_midi_notes() {
+            assert_close(
+                Tuning::edo(19).frequency(19 * 5).unwrap(),
+                midi_to_frequency(60),
+            );
+            assert_close(
+                Tuning::edo(53).frequency(53 * 4).unwrap(),
+                midi_to_frequency(48),
+            );
+        }
+
+        #[test]
+        fn translates_semitones_into_steps() {
+            let tuning = Tuning::edo(31);
+            assert_eq!(tuning.steps(7), 18);
+            assert_eq!(tuning.steps(2), 5);
+            assert_eq!(tuning.steps(12), 31);
+            assert_eq!(tuning.steps(-12), -31);
+            assert_eq!(Tuning::default().steps(5), 5);
+        }
+    }
+
+    mod ratios {
+        use super::*;
+
+        fn just_major() -> Tuning {
+            Tuning::from_ratios(vec![
+                9.0 / 8.0,
+                5.0 / 4.0,
+                4.0 / 3.0,
+                3.0 / 2.0,
+                5.0 / 3.0,
+                15.0 / 8.0,
+                2.0,
+            ])
+            .unwrap()
+        }
+
+        #[test]
+        fn uses_the_given_ratios() {
+            let tuning = just_major();
+            assert_eq!(tuning.size(), 7);
+            let c = tuning.frequency(7 * 5).unwrap();
+            assert_close(c, midi_to_frequency(60));
+            assert_close(tuning.frequency(7 * 5 + 4).unwrap(), c * 3.0 / 2.0);
+            assert_close(tuning.frequency(7 * 6 + 2).unwrap(), c * 2.0 * 5.0 / 4.0);
+        }
+
+        #[test]
+        fn translates_semitones_into_the_nearest_steps() {
+            let tuning = just_major();
+            assert_eq!(tuning.steps(7), 4);
+            assert_eq!(tuning.steps(5), 3);
+            assert_eq!(tuning.steps(12), 7);
+        }
+    }
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn parses_equal_divisions() {
+            assert_eq!(Tuning::parse("19edo").unwrap(), Tuning::edo(19));
+            assert_eq!(Tuning::parse("22-EDO").unwrap(), Tuning::edo(22));
+        }
+
+        #[test]
+        fn parses_ratios_and_cents() {
+            let tuning = Tuning::parse("9/8, 386.3, 2").unwrap();
+            assert_eq!(tuning.size(), 3);
+            assert_close(tuning.cents(1), 203.91);
+            assert_close(tuning.cents(2), 386.3);
+            assert_close(tuning.cents(3), 1200.0);
+        }
+
+        #[test]
+        fn rejects_invalid_tunings() {
+            for input in &["0edo", "xedo", "", "3/0", "1/2/3", "abc", "-100.0"] {
+                assert!(Tuning::parse(input).is_err(), "{}", input);
+            }
+        }
+    }
+
+    mod keyboard_mapping {
+        use super::*;
+
+        fn white_keys() -> KeyboardMapping {
+            KeyboardMapping {
+                keys: vec![
+                    Some(0),
+                    None,
+                    Some(1),
+                    None,
+                    Some(2),
+                    Some(3),
+                    None,
+                    Some(4),
+                    None,
+                    Some(5),
+                    None,
+                    Some(6),
+                ],
+                first_note: 0,
+                last_note: 127,
+                middle_note: 60,
+                reference_note: 69,
+                reference_frequency: 440.0,
+                octave_degree: 7,
+            }
+        }
+
+        fn pythagorean() -> Tuning {
+            Tuning::parse("9/8,81/64,4/3,3/2,27/16,243/128,2/1").unwrap()
+        }
+
+        #[test]
+        fn maps_notes_to_scale_degrees() {
+            let tuning = pythagorean().with_keyboard_mapping(white_keys()).unwrap();
+            assert_eq!(tuning.frequency(69), Some(440.0));
+            let c = tuning.frequency(60).unwrap();
+            assert_close(c, 440.0 / (27.0 / 16.0));
+            assert_close(tuning.frequency(67).unwrap(), c * 3.0 / 2.0);
+            assert_close(tuning.frequency(72).unwrap(), c * 2.0);
+            assert_close(tuning.frequency(59).unwrap(), c / 2.0 * 243.0 / 128.0);
+        }
+
+        #[test]
+        fn leaves_notes_unmapped() {
+            let tuning = pythagorean().with_keyboard_mapping(white_keys()).unwrap();
+            assert_eq!(tuning.frequency(61), None);
+            let tuning = pythagorean()
+                .with_keyboard_mapping(KeyboardMapping {
+                    first_note: 48,
+                    last_note: 84,
+                    ..white_keys()
+                })
+                .unwrap();
+            assert_eq!(tuning.frequency(47), None);
+            assert_eq!(tuning.frequency(85), None);
+        }
+
+        #[test]
+        fn supports_linear_mappings() {
+            let tuning = Tuning::edo(31)
+                .with_keyboard_mapping(KeyboardMapping {
+                    keys: vec![],
+                    reference_note: 60,
+                    reference_frequency: 261.6,
+                    ..white_keys()
+                })
+                .unwrap();
+            assert_eq!(tuning.frequency(60), Some(261.6));
+            assert_close(tuning.frequency(91).unwrap(), 2.0 * 261.6);
+        }
+
+        #[test]
+        fn rejects_unmapped_reference_notes() {
+            assert!(pythagorean()
+                .with_keyboard_mapping(KeyboardMapping {
+                    reference_note: 70,
+                    ..white_keys()
+                })
+                .is_err());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+custom_derive! {
+#[derive(Debug, Clone, Copy, IterVariants(VoiceStealingVariants), PartialEq)]
+    pub enum VoiceStealing {
+        Oldest,
+        Quietest,
+    }
+}
+
+impl Default for VoiceStealing {
+    fn default() -> VoiceStealing {
+        VoiceStealing::Oldest
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Voice {
+    tracking_id: i32,
+    started: u64,
+    level: f32,
+}
+
+pub struct VoiceAllocator {
+    voice_stealing: VoiceStealing,
+    voices: Vec<Option<Voice>>,
+    released: Vec<u64>,
+    stolen: Vec<i32>,
+    clock: u64,
+}
+
+impl VoiceAllocator {
+    pub fn new(polyphony: usize, voice_stealing: VoiceStealing) -> VoiceAllocator {
+        VoiceAllocator {
+            voice_stealing,
+            voices: vec![None; polyphony],
+            released: vec![0; polyphony],
+            stolen: vec![],
+            clock: 0,
+        }
+    }
+
+    pub fn polyphony(&self) -> usize {
+        self.voices.len()
+    }
+
+    fn find(&self, tracking_id: i32) -> Option<usize> {
+        self.voices.iter().position(|voice| match voice {
+            Some(voice) => voice.tracking_id == tracking_id,
+            None => false,
+        })
+    }
+
+    // Prefers the voice that was released the longest time ago, so that
+    // release tails aren't cut off unnecessarily.
+    fn free_voice(&self) -> Option<usize> {
+        (0..self.voices.len())
+            .filter(|index| self.voices[*index].is_none())
+            .min_by_key(|index| self.released[*index])
+    }
+
+    fn victim(&self) -> Option<usize> {
+        let voices = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter_map(|(index, voice)| voice.as_ref().map(|voice| (index, voice)));
+        let victim = match self.voice_stealing {
+            VoiceStealing::Oldest => voices.min_by_key(|(_, voice)| voice.started),
+            VoiceStealing::Quietest => voices.min_by(|(_, a), (_, b)| {
+                a.level
+                    .partial_cmp(&b.level)
+                    .unwrap_or(::std::cmp::Ordering::Equal)
+                    .then(a.started.cmp(&b.started))
+            }),
+        };
+        victim.map(|(index, _)| index)
+    }
+
+    /// Returns the voice for the given touch, allocating a new one if
+    /// necessary. Touches that had their voice stolen don't get a voice
+    /// until they're released.
+    pub fn touch(&mut self, tracking_id: i32, level: f32) -> Option<usize> {
+        self.clock += 1;
+        if let Some(index) = self.find(tracking_id) {
+            if let Some(voice) = &mut self.voices[index] {
+                voice.level = level;
+            }
+            return Some(index);
+        }
+        if self.stolen.contains(&tracking_id) {
+            return None;
+        }
+        let index = match self.free_voice() {
+            Some(index) => index,
+            None => {
+                let index = self.victim()?;
+                if let Some(victim) = &self.voices[index] {
+                    self.stolen.push(victim.tracking_id);
+                }
+                index
+            }
+        };
+        self.voices[index] = Some(Voice {
+            tracking_id,
+            started: self.clock,
+            level,
+        });
+        Some(index)
+    }
+
+    /// Frees the voice of the given touch and returns it.
+    pub fn release(&mut self, tracking_id: i32) -> Option<usize> {
+        self.clock += 1;
+        self.stolen.retain(|stolen| *stolen != tracking_id);
+        let index = self.find(tracking_id)?;
+        self.voices[index] = None;
+        self.released[index] = self.clock;
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn allocator(polyphony: usize) -> VoiceAllocator {
+        VoiceAllocator::new(polyphony, VoiceStealing::Oldest)
+    }
+
+    #[test]
+    fn allocates_free_voices_in_order() {
+        let mut allocator = allocator(3);
+        assert_eq!(allocator.touch(7, 1.0), Some(0));
+        assert_eq!(allocator.touch(3, 1.0), Some(1));
+        assert_eq!(allocator.touch(42, 1.0), Some(2));
+    }
+
+    #[test]
+    fn keeps_the_voice_for_ongoing_touches() {
+        let mut allocator = allocator(3);
+        allocator.touch(7, 1.0);
+        allocator.touch(3, 1.0);
+        assert_eq!(allocator.touch(7, 1.0), Some(0));
+        assert_eq!(allocator.touch(3, 1.0), Some(1));
+    }
+
+    #[test]
+    fn does_not_collide_for_tracking_ids_that_are_a_multiple_of_the_polyphony_apart() {
+        let mut allocator = allocator(20);
+        assert_eq!(allocator.touch(1, 1.0), Some(0));
+        assert_eq!(allocator.touch(21, 1.0), Some(1));
+        assert_eq!(allocator.touch(41, 1.0), Some(2));
+    }
+
+    #[test]
+    fn handles_big_tracking_ids() {
+        let mut allocator = allocator(20);
+        assert_eq!(allocator.touch(65535, 1.0), Some(0));
+        assert_eq!(allocator.release(65535), Some(0));
+    }
+
+    #[test]
+    fn reuses_released_voices() {
+        let mut allocator = allocator(2);
+        allocator.touch(1, 1.0);
+        allocator.touch(2, 1.0);
+        assert_eq!(allocator.release(1), Some(0));
+        assert_eq!(allocator.touch(3, 1.0), Some(0));
+    }
+
+    #[test]
+    fn prefers_voices_that_were_released_a_long_time_ago() {
+        let mut allocator = allocator(3);
+        allocator.touch(1, 1.0);
+        allocator.touch(2, 1.0);
+        allocator.release(2);
+        allocator.release(1);
+        assert_eq!(allocator.touch(3, 1.0), Some(2));
+        assert_eq!(allocator.touch(4, 1.0), Some(1));
+    }
+
+    #[test]
+    fn ignores_releases_of_unknown_touches() {
+        let mut allocator = allocator(2);
+        assert_eq!(allocator.release(1), None);
+    }
+
+    mod stealing {
+        use super::*;
+
+        #[test]
+        fn steals_the_oldest_voice_when_all_voices_are_in_use() {
+            let mut allocator = allocator(2);
+            allocator.touch(1, 1.0);
+            allocator.touch(2, 1.0);
+            allocator.touch(1, 1.0);
+            assert_eq!(allocator.touch(3, 1.0), Some(0));
+        }
+
+        #[test]
+        fn steals_the_quietest_voice_if_configured() {
+            let mut allocator = VoiceAllocator::new(3, VoiceStealing::Quietest);
+            allocator.touch(1, 1.0);
+            allocator.touch(2, 0.2);
+            allocator.touch(3, 0.5);
+            assert_eq!(allocator.touch(4, 1.0), Some(1));
+        }
+
+        #[test]
+        fn steals_the_oldest_of_equally_quiet_voices() {
+            let mut allocator = VoiceAllocator::new(2, VoiceStealing::Quietest);
+            allocator.touch(1, 1.0);
+            allocator.touch(2, 1.0);
+            assert_eq!(allocator.touch(3, 1.0), Some(0));
+        }
+
+        #[test]
+        fn ignores_touches_whose_voice_was_stolen_until_they_are_released() {
+            let mut allocator = allocator(1);
+            allocator.touch(1, 1.0);
+            assert_eq!(allocator.touch(2, 1.0), Some(0));
+            assert_eq!(allocator.touch(1, 1.0), None);
+            assert_eq!(allocator.release(1), None);
+            assert_eq!(allocator.release(2), Some(0));
+            assert_eq!(allocator.touch(1, 1.0), Some(0));
+        }
+    }
+}
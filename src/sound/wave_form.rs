@@ -25,6 +25,10 @@ impl WaveForm {
         mk_hammond(&wave_form_config.harmonics, WaveForm::TABLE_SIZE)
     }
 
+    pub fn sine() -> WaveForm {
+        WaveForm::from_function(f32::sin, WaveForm::TABLE_SIZE)
+    }
+
     pub fn from_function<F: Fn(f32) -> f32>(function: F, size: usize) -> WaveForm {
         let mut table: Vec<f32> = vec![0.0; size];
         for (i, cell) in table.iter_mut().enumerate() {
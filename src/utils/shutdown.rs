@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Shared flag that tells long running loops (e.g. reading the touch device)
+// to stop, so that everything can be cleaned up before exiting.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn is_not_requested_initially() {
+        assert!(!Shutdown::new().is_requested());
+    }
+
+    #[test]
+    fn is_shared_between_clones() {
+        let shutdown = Shutdown::new();
+        let clone = shutdown.clone();
+        ::std::thread::spawn(move || clone.request())
+            .join()
+            .unwrap();
+        assert!(shutdown.is_requested());
+    }
+}
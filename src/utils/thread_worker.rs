@@ -1,4 +1,4 @@
-use skipchannel::*;
+use crate::utils::triple_buffer::{triple_buffer, Receiver, Sender};
 use std::thread::{sleep, spawn, JoinHandle};
 use std::time::Duration;
 
@@ -7,15 +7,17 @@ enum Message<T> {
     StopThread,
 }
 
+// Both directions only hand over the last value. They are wrapped in Options,
+// so that the values can be taken out of the buffers without allocating.
 pub struct ThreadWorker<Input: Send, Output: Send> {
     thread: Option<JoinHandle<()>>,
-    to_worker_thread: Sender<Message<Input>>,
-    from_worker_thread: Receiver<Output>,
+    to_worker_thread: Sender<Option<Message<Input>>>,
+    from_worker_thread: Receiver<Option<Output>>,
 }
 
 impl<Input: Send, Output: Send> Drop for ThreadWorker<Input, Output> {
     fn drop(&mut self) {
-        self.to_worker_thread.send(Message::StopThread);
+        self.to_worker_thread.send(Some(Message::StopThread));
         let thread = std::mem::replace(&mut self.thread, None);
         match thread {
             None => {}
@@ -32,20 +34,20 @@ impl<Input: Send, Output: Send> Drop for ThreadWorker<Input, Output> {
 impl<Input, Output> ThreadWorker<Input, Output>
 where
     Input: Send + 'static,
-    Output: Send + Clone + 'static,
+    Output: Send + 'static,
 {
     pub fn new<F>(mut computation: F) -> ThreadWorker<Input, Output>
     where
         F: FnMut(Input) -> Output + Send + 'static,
     {
-        let (to_worker_thread, worker_thread_source) = skipchannel();
-        let (worker_thread_sink, from_worker_thread) = skipchannel();
+        let (to_worker_thread, mut worker_thread_source) = triple_buffer();
+        let (mut worker_thread_sink, from_worker_thread) = triple_buffer();
         let thread = spawn(move || loop {
-            match worker_thread_source.recv() {
+            match worker_thread_source.recv().and_then(Option::take) {
                 None => {}
                 Some(Message::Input(input)) => {
                     let result = computation(input);
-                    worker_thread_sink.send(result);
+                    worker_thread_sink.send(Some(result));
                 }
                 Some(Message::StopThread) => break,
             };
@@ -58,12 +60,12 @@ where
         }
     }
 
-    pub fn enqueue(&self, input: Input) {
-        self.to_worker_thread.send(Message::Input(input));
+    pub fn enqueue(&mut self, input: Input) {
+        self.to_worker_thread.send(Some(Message::Input(input)));
     }
 
-    pub fn poll(&self) -> Option<Output> {
-        self.from_worker_thread.recv()
+    pub fn poll(&mut self) -> Option<Output> {
+        self.from_worker_thread.recv().and_then(Option::take)
     }
 }
 
@@ -88,7 +90,7 @@ pub mod test {
 
     #[test]
     fn allows_to_run_functions() -> Result<(), String> {
-        let thread_worker: ThreadWorker<i32, i32> = ThreadWorker::new(|x| x * 2);
+        let mut thread_worker: ThreadWorker<i32, i32> = ThreadWorker::new(|x| x * 2);
         thread_worker.enqueue(42);
         let result = wait_for(|| match thread_worker.poll() {
             None => Err("poll: no result received".to_string()),
@@ -100,7 +102,7 @@ pub mod test {
 
     #[test]
     fn runs_computations_in_different_thread() -> Result<(), String> {
-        let thread_worker: ThreadWorker<(), ThreadId> =
+        let mut thread_worker: ThreadWorker<(), ThreadId> =
             ThreadWorker::new(|()| std::thread::current().id());
         thread_worker.enqueue(());
         let result = wait_for(|| match thread_worker.poll() {
@@ -0,0 +1,116 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Set together with the index of the shared slot when that slot holds a value
+// that the receiver hasn't seen yet.
+const FRESH: usize = 0b100;
+const INDEX: usize = 0b011;
+
+// The receiver only gets the last sent value, older values are skipped. Values
+// are stored in three preallocated slots that are handed back and forth
+// between the two sides, so receiving neither allocates nor frees any memory.
+// This makes the receiver safe to use in real-time threads.
+struct Slots<T> {
+    slots: [UnsafeCell<T>; 3],
+    // The slot that neither the sender nor the receiver currently owns.
+    shared: AtomicUsize,
+}
+
+// Every slot is only accessed by the side that currently owns it.
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+pub struct Sender<T> {
+    slots: Arc<Slots<T>>,
+    index: usize,
+}
+
+pub struct Receiver<T> {
+    slots: Arc<Slots<T>>,
+    index: usize,
+}
+
+pub fn triple_buffer<T: Default>() -> (Sender<T>, Receiver<T>) {
+    let slots = Arc::new(Slots {
+        slots: [
+            UnsafeCell::new(T::default()),
+            UnsafeCell::new(T::default()),
+            UnsafeCell::new(T::default()),
+        ],
+        shared: AtomicUsize::new(1),
+    });
+    (
+        Sender {
+            slots: slots.clone(),
+            index: 0,
+        },
+        Receiver { slots, index: 2 },
+    )
+}
+
+impl<T> Sender<T> {
+    // The replaced value is dropped here, on the sending side.
+    pub fn send(&mut self, t: T) {
+        unsafe {
+            *self.slots.slots[self.index].get() = t;
+        }
+        let previous = self.slots.shared.swap(self.index | FRESH, Ordering::AcqRel);
+        self.index = previous & INDEX;
+    }
+}
+
+impl<T> Receiver<T> {
+    // Returns the last sent value, or None if no value was sent since the
+    // last call to `recv`. The value can be taken out of its slot, the slot
+    // is only overwritten by a later `send`.
+    pub fn recv(&mut self) -> Option<&mut T> {
+        if self.slots.shared.load(Ordering::Acquire) & FRESH == 0 {
+            return None;
+        }
+        let previous = self.slots.shared.swap(self.index, Ordering::AcqRel);
+        self.index = previous & INDEX;
+        Some(unsafe { &mut *self.slots.slots[self.index].get() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn returns_none_when_nothing_was_sent() {
+        let (_sender, mut receiver) = triple_buffer::<i32>();
+        assert_eq!(receiver.recv(), None);
+    }
+
+    #[test]
+    fn returns_the_last_sent_value_once() {
+        let (mut sender, mut receiver) = triple_buffer();
+        sender.send(1);
+        sender.send(2);
+        assert_eq!(receiver.recv(), Some(&mut 2));
+        assert_eq!(receiver.recv(), None);
+        sender.send(3);
+        assert_eq!(receiver.recv(), Some(&mut 3));
+    }
+
+    #[test]
+    fn hands_values_over_between_threads() {
+        let (mut sender, mut receiver) = triple_buffer();
+        let thread = thread::spawn(move || {
+            for i in 1..=10_000 {
+                sender.send(vec![i; 8]);
+            }
+        });
+        let mut last = 0;
+        while last < 10_000 {
+            if let Some(values) = receiver.recv() {
+                assert!(values.iter().all(|value| *value == values[0]));
+                assert!(values[0] > last);
+                last = values[0];
+            }
+        }
+        thread.join().unwrap();
+    }
+}